@@ -3,3 +3,9 @@ pub static STOP_FILLED: &str = "icons/stop.svg";
 pub static VOLUME_DOWN: &str = "icons/volume_down.svg";
 pub static VOLUME_MUTE: &str = "icons/volume_mute.svg";
 pub static VOLUME_UP: &str = "icons/volume_up.svg";
+pub static SKIP_NEXT: &str = "icons/skip_next.svg";
+pub static SKIP_PREVIOUS: &str = "icons/skip_previous.svg";
+pub static EQUALIZER: &str = "icons/equalizer.svg";
+pub static LYRICS: &str = "icons/lyrics.svg";
+pub static CROSSFADE: &str = "icons/crossfade.svg";
+pub static REPLAY_GAIN: &str = "icons/replay_gain.svg";
@@ -0,0 +1,213 @@
+use std::path::PathBuf;
+
+use crate::service::{
+    music_service::{
+        dsp::{DspEdit, DspKind},
+        models::OutputKind,
+    },
+    remote_service::models::RemoteCommand,
+};
+
+pub static USAGE: &str = "\
+Usage:
+  the_player [FILES...]          open window, or enqueue into running player
+  the_player play PATHS...       play files, folders or playlists without window
+      --output null              discard samples at real-time pace
+      --output wav:FILE          write samples into a wav file as fast as possible
+  the_player devices             list output devices with their formats
+  the_player ctl COMMAND         control running player
+
+Commands of ctl:
+  play | pause | toggle | stop | next | previous
+  seek TIME                      like 1:30, 90, +10 or -10
+  volume PERCENT                 from 0 to 100
+  balance PERCENT                from -100 (left) to 100 (right)
+  dsp add KIND [N]               insert equalizer, balance, gain or limiter at N
+  dsp remove N | dsp move N TO   edit processors by number shown by status
+  dsp bypass N on|off            skip a processor or use it again
+  device NAME | device default   play on a device listed by devices, kept for next runs
+  status                         show current song";
+
+/// What the player is launched to do
+#[derive(Debug)]
+pub enum Command {
+    // open window with files, or enqueue them into running player
+    Open(Vec<PathBuf>),
+    // play without window, on default device if output is None
+    Play {
+        paths: Vec<PathBuf>,
+        output: Option<OutputKind>,
+    },
+    Ctl(Ctl),
+    // list output devices
+    Devices,
+    Help,
+}
+
+/// Command sent to running player by `ctl`
+#[derive(Debug)]
+pub enum Ctl {
+    Send(RemoteCommand),
+    Status,
+}
+
+/// Parse arguments after program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, anyhow::Error> {
+    let args: Vec<String> = args.into_iter().collect();
+    let Some(first) = args.first() else {
+        return Ok(Command::Open(Vec::new()));
+    };
+    match first.as_str() {
+        "-h" | "--help" | "help" => Ok(Command::Help),
+        "play" => parse_play(&args[1..]),
+        "devices" => Ok(Command::Devices),
+        "ctl" => parse_ctl(&args[1..]).map(Command::Ctl),
+        _ => Ok(Command::Open(args.iter().map(PathBuf::from).collect())),
+    }
+}
+
+fn parse_play(args: &[String]) -> Result<Command, anyhow::Error> {
+    let mut paths = Vec::new();
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--output" {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("argument of --output expected"))?;
+            output = Some(parse_output(value)?);
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    if paths.is_empty() {
+        anyhow::bail!("nothing to play");
+    }
+    Ok(Command::Play { paths, output })
+}
+
+/// Parse sink like `null` or `wav:out.wav`
+fn parse_output(s: &str) -> Result<OutputKind, anyhow::Error> {
+    if s == "null" {
+        return Ok(OutputKind::Null { realtime: true });
+    }
+    match s.strip_prefix("wav:") {
+        Some(path) if !path.is_empty() => Ok(OutputKind::Wav(PathBuf::from(path))),
+        _ => anyhow::bail!("output expected like null or wav:out.wav, got {}", s),
+    }
+}
+
+fn parse_ctl(args: &[String]) -> Result<Ctl, anyhow::Error> {
+    let Some(name) = args.first() else {
+        anyhow::bail!("command of ctl expected");
+    };
+    let arg = || {
+        args.get(1)
+            .ok_or_else(|| anyhow::anyhow!("argument of {} expected", name))
+    };
+    let command = match name.as_str() {
+        "status" => return Ok(Ctl::Status),
+        "play" => RemoteCommand::Play,
+        "pause" => RemoteCommand::Pause,
+        "toggle" => RemoteCommand::PlayPause,
+        "stop" => RemoteCommand::Stop,
+        "next" => RemoteCommand::Next,
+        "previous" | "prev" => RemoteCommand::Previous,
+        "seek" => {
+            let time = arg()?;
+            let secs = parse_time(time.trim_start_matches(['+', '-']))?;
+            match time.chars().next() {
+                Some('+') => RemoteCommand::SeekBy(secs),
+                Some('-') => RemoteCommand::SeekBy(-secs),
+                _ => RemoteCommand::SeekTo(secs),
+            }
+        }
+        "volume" => {
+            let percent: f32 = arg()?
+                .trim_end_matches('%')
+                .parse()
+                .map_err(|_| anyhow::anyhow!("volume expected like 80"))?;
+            RemoteCommand::SetVolume((percent / 100.0).clamp(0.0, 1.0))
+        }
+        "balance" => {
+            let percent: f32 = arg()?
+                .trim_end_matches('%')
+                .parse()
+                .map_err(|_| anyhow::anyhow!("balance expected like -20"))?;
+            RemoteCommand::SetBalance((percent / 100.0).clamp(-1.0, 1.0))
+        }
+        "dsp" => RemoteCommand::EditDsp(parse_dsp(&args[1..])?),
+        "device" => match args[1..].join(" ").as_str() {
+            "" => anyhow::bail!("argument of device expected"),
+            "default" => RemoteCommand::SetOutputDevice(None),
+            name => RemoteCommand::SetOutputDevice(Some(name.to_string())),
+        },
+        _ => anyhow::bail!("unknown command of ctl: {}", name),
+    };
+    Ok(Ctl::Send(command))
+}
+
+/// Parse edit of chain like `add gain 2` or `bypass 1 on`, numbers start from 1
+fn parse_dsp(args: &[String]) -> Result<DspEdit, anyhow::Error> {
+    let number = |ix: usize| -> Result<usize, anyhow::Error> {
+        args.get(ix)
+            .and_then(|s| s.parse::<usize>().ok())
+            .and_then(|n| n.checked_sub(1))
+            .ok_or_else(|| anyhow::anyhow!("number of processor expected like 2"))
+    };
+    let edit = match args.first().map(String::as_str) {
+        Some("add") => {
+            let kind = match args.get(1).map(String::as_str) {
+                Some("equalizer" | "eq") => DspKind::Equalizer,
+                Some("balance") => DspKind::Balance,
+                Some("gain") => DspKind::Gain,
+                Some("limiter") => DspKind::Limiter,
+                _ => anyhow::bail!("kind expected: equalizer, balance, gain or limiter"),
+            };
+            let index = if args.len() > 2 {
+                number(2)?
+            } else {
+                usize::MAX
+            };
+            DspEdit::Insert { index, kind }
+        }
+        Some("remove") => DspEdit::Remove { index: number(1)? },
+        Some("move") => DspEdit::Move {
+            from: number(1)?,
+            to: number(2)?,
+        },
+        Some("bypass") => {
+            let bypassed = match args.get(2).map(String::as_str) {
+                Some("on") => true,
+                Some("off") => false,
+                _ => anyhow::bail!("bypass expected like bypass 2 on"),
+            };
+            DspEdit::Bypass {
+                index: number(1)?,
+                bypassed,
+            }
+        }
+        _ => anyhow::bail!("dsp expected add, remove, move or bypass"),
+    };
+    Ok(edit)
+}
+
+/// Parse time like `90`, `1:30` or `1:02:03` into seconds
+pub fn parse_time(s: &str) -> Result<f64, anyhow::Error> {
+    let mut secs = 0.0;
+    for part in s.split(':') {
+        let value: f64 = part
+            .parse()
+            .ok()
+            .filter(|v: &f64| v.is_finite() && *v >= 0.0)
+            .ok_or_else(|| anyhow::anyhow!("time expected like 1:30, got {}", s))?;
+        secs = secs * 60.0 + value;
+    }
+    Ok(secs)
+}
+
+/// Format seconds like `1:30`
+pub fn format_time(secs: f64) -> String {
+    let secs = secs.max(0.0) as u64;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
@@ -0,0 +1,74 @@
+use crate::{
+    cli::{
+        args::{Ctl, format_time},
+        instance::Instance,
+    },
+    service::{
+        music_service::models::PlayState,
+        remote_service::models::{PlayerStatus, SongInfo},
+    },
+};
+
+/// Run command of `ctl` on running player
+pub fn run(ctl: Ctl) -> Result<(), anyhow::Error> {
+    let mut instance =
+        Instance::connect()?.ok_or_else(|| anyhow::anyhow!("no running player found"))?;
+    match ctl {
+        Ctl::Send(command) => instance.send(command),
+        Ctl::Status => {
+            match instance.status()? {
+                Some(status) => print_status(&status),
+                None => println!("[stopped]"),
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Print state, song and position of player
+pub fn print_status(status: &PlayerStatus) {
+    let state = match status.state {
+        PlayState::Playing => "playing",
+        PlayState::Paused => "paused",
+        PlayState::Stopped => "stopped",
+    };
+    let Some(song) = &status.song else {
+        println!("[{}]", state);
+        return;
+    };
+    println!("[{}] {}", state, song_name(song));
+    println!(
+        "  {} / {}   #{}/{}   volume {}%",
+        format_time(status.position),
+        format_time(song.duration),
+        status.index.map_or(0, |i| i + 1),
+        status.queue_len,
+        (status.volume * 100.0).round()
+    );
+    if !status.dsp.is_empty() {
+        let stages: Vec<String> = status
+            .dsp
+            .iter()
+            .enumerate()
+            .map(|(ix, s)| match s.bypassed {
+                true => format!("{}.{} (bypassed)", ix + 1, s.kind.name()),
+                false => format!("{}.{}", ix + 1, s.kind.name()),
+            })
+            .collect();
+        println!("  dsp: {}", stages.join(" > "));
+    }
+}
+
+/// Name of song like `Artist - Title`, file name if not tagged
+pub fn song_name(song: &SongInfo) -> String {
+    let title = song.title.clone().unwrap_or_else(|| {
+        song.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    match &song.artist {
+        Some(artist) => format!("{} - {}", artist, title),
+        None => title,
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+
+use crate::{
+    cli::ctl::song_name,
+    service::{
+        library_service::scanner,
+        music_service::{
+            core::Core,
+            models::{Events, OutputKind, PlayState},
+        },
+        playlist_service::loader,
+        remote_service::{hub::RemoteHub, models::RemoteCommand},
+    },
+};
+
+/// Print output devices of all hosts, default and chosen ones are marked
+pub fn print_devices() {
+    let core = Core::new();
+    for info in core.output_devices() {
+        let chosen = core.output_device() == Some(&info.device);
+        println!(
+            "{} {}{}",
+            info.device.host,
+            info.device.name,
+            match (info.is_default, chosen) {
+                (true, true) => " [default, chosen]",
+                (true, false) => " [default]",
+                (false, true) => " [chosen]",
+                (false, false) => "",
+            }
+        );
+        for c in &info.configs {
+            println!(
+                "  {} ch  {}-{} Hz  {}",
+                c.channels, c.min_sample_rate, c.max_sample_rate, c.sample_format
+            );
+        }
+    }
+}
+
+/// What woke up headless player
+enum Wake {
+    Event(Option<Events>),
+    Command(Option<RemoteCommand>),
+}
+
+/// Play files, folders and playlists without window, until queue is finished or stopped.
+/// Output is default device if not given
+pub fn run(paths: Vec<PathBuf>, output: Option<OutputKind>) -> Result<(), anyhow::Error> {
+    let mut core = Core::new();
+    if let Some(output) = output {
+        core.set_output(output)?;
+    }
+    for path in expand(paths) {
+        let result = if loader::is_playlist(&path) {
+            core.load_playlist(&path).map(|missing| {
+                for entry in missing {
+                    eprintln!("not found: {}", entry.display());
+                }
+            })
+        } else {
+            core.append(path.clone())
+        };
+        if let Err(e) = result {
+            eprintln!("failed to open {}: {}", path.display(), e);
+        }
+    }
+    if core.queue().is_empty() {
+        anyhow::bail!("nothing to play");
+    }
+
+    // remote controls are served as with window, so `ctl` controls this player
+    let mut remote = RemoteHub::new();
+    #[cfg(unix)]
+    crate::service::ipc_service::server::start(remote.command_sender());
+    if let Some(address) = core.mpd_address() {
+        crate::service::mpd_service::server::start(
+            address.to_string(),
+            remote.command_sender(),
+            remote.subscribe(),
+        );
+    }
+    if let Some((address, token)) = core.http_config() {
+        crate::service::http_service::server::start(
+            address,
+            token,
+            remote.command_sender(),
+            remote.subscribe(),
+        );
+    }
+    #[cfg(target_os = "linux")]
+    crate::service::mpris_service::server::start(remote.command_sender(), remote.subscribe());
+
+    let events = core.receiver();
+    let commands = remote.commands();
+    let mut playing = None;
+    smol::block_on(async {
+        loop {
+            let status = core.status();
+            if status.song.as_ref().map(|s| &s.path) != playing.as_ref() {
+                playing = status.song.as_ref().map(|s| s.path.clone());
+                if let Some(song) = &status.song {
+                    println!("Playing: {}", song_name(song));
+                }
+            }
+            remote.publish(status);
+            if core.get_state() == PlayState::Stopped {
+                return Ok(());
+            }
+
+            let event = async { Wake::Event(events.recv().await.ok()) };
+            let command = async { Wake::Command(commands.recv().await.ok()) };
+            let result = match smol::future::or(event, command).await {
+                Wake::Event(Some(Events::PlaytimeRefresh)) => Ok(()),
+                Wake::Event(Some(Events::TrackChanged(id))) => {
+                    core.handle_track_changed(id);
+                    Ok(())
+                }
+                Wake::Event(Some(Events::PlayFinished)) => core.handle_finished(),
+                Wake::Event(Some(Events::DeviceFallback(name))) => {
+                    eprintln!("Device {} not found, using default", name);
+                    Ok(())
+                }
+                Wake::Event(Some(Events::DeviceLost(name))) => {
+                    eprintln!("Device {} lost, switching output", name);
+                    core.handle_device_lost()
+                }
+                Wake::Command(Some(command)) => core.apply_command(command),
+                Wake::Event(None) | Wake::Command(None) => return Ok(()),
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+            }
+            // no view waits on this thread, play stats are saved as they come
+            if let Some(library) = core.flush_stats()
+                && let Err(e) = library.save()
+            {
+                eprintln!("failed to save library: {}", e);
+            }
+        }
+    })
+}
+
+/// Expand folders into music files under them, other paths are kept
+fn expand(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut files = Vec::new();
+            scanner::collect_files(&path, &mut files);
+            let mut files: Vec<_> = files.into_iter().map(|(p, _)| p).collect();
+            files.sort();
+            expanded.extend(files);
+        } else {
+            expanded.push(path);
+        }
+    }
+    expanded
+}
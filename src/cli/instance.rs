@@ -0,0 +1,197 @@
+#[cfg(not(unix))]
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+#[cfg(not(unix))]
+use serde::Deserialize;
+
+#[cfg(unix)]
+use crate::service::ipc_service::{
+    client::IpcClient,
+    protocol::{Reply, Request},
+};
+use crate::service::remote_service::models::{PlayerStatus, RemoteCommand};
+#[cfg(not(unix))]
+use crate::utils::config::Config;
+
+// time to wait for running player
+#[cfg(not(unix))]
+static TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A running player, reached by socket of this user.
+/// Systems without unix sockets reach it by HTTP remote API, which must be enabled
+pub enum Instance {
+    #[cfg(unix)]
+    Socket(IpcClient),
+    #[cfg(not(unix))]
+    Http {
+        address: String,
+        token: Option<String>,
+    },
+}
+
+#[cfg(not(unix))]
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl Instance {
+    /// Find running player by socket of this user, None if it is not running
+    #[cfg(unix)]
+    pub fn connect() -> Result<Option<Self>, anyhow::Error> {
+        Ok(IpcClient::connect()?.map(Self::Socket))
+    }
+
+    /// Find running player by HTTP remote API, None if it is not running or API is disabled
+    #[cfg(not(unix))]
+    pub fn connect() -> Result<Option<Self>, anyhow::Error> {
+        let config = Config::load();
+        let Some(address) = config.http_address() else {
+            return Ok(None);
+        };
+        let reachable = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut a| a.next())
+            .is_some_and(|socket| TcpStream::connect_timeout(&socket, TIMEOUT).is_ok());
+        Ok(reachable.then_some(Self::Http {
+            address,
+            token: config.http_token,
+        }))
+    }
+
+    /// Send command to player, commands replying data are not supported
+    pub fn send(&mut self, command: RemoteCommand) -> Result<(), anyhow::Error> {
+        match self {
+            #[cfg(unix)]
+            Self::Socket(client) => {
+                let request = Request::from_command(command)
+                    .ok_or_else(|| anyhow::anyhow!("command not supported by running player"))?;
+                match client.request(&request)? {
+                    Reply::Ok => Ok(()),
+                    Reply::Error { message } => anyhow::bail!(message),
+                    reply => anyhow::bail!("unexpected reply of running player: {:?}", reply),
+                }
+            }
+            #[cfg(not(unix))]
+            Self::Http { .. } => self.send_http(command),
+        }
+    }
+
+    /// Get status of player, None if it has not published one yet
+    pub fn status(&mut self) -> Result<Option<PlayerStatus>, anyhow::Error> {
+        match self {
+            #[cfg(unix)]
+            Self::Socket(client) => match client.request(&Request::Status)? {
+                Reply::Status { status } => Ok(Some(*status)),
+                Reply::Error { message } => anyhow::bail!(message),
+                reply => anyhow::bail!("unexpected reply of running player: {:?}", reply),
+            },
+            #[cfg(not(unix))]
+            Self::Http { .. } => {
+                let body = self.request("GET", "/api/state", None)?;
+                Ok(serde_json::from_slice(&body)?)
+            }
+        }
+    }
+
+    /// Send command by HTTP remote API
+    #[cfg(not(unix))]
+    fn send_http(&self, command: RemoteCommand) -> Result<(), anyhow::Error> {
+        let (method, path, body) = match command {
+            RemoteCommand::Play => ("POST", "/api/transport/play".to_string(), None),
+            RemoteCommand::Pause => ("POST", "/api/transport/pause".to_string(), None),
+            RemoteCommand::PlayPause => ("POST", "/api/transport/toggle".to_string(), None),
+            RemoteCommand::Stop => ("POST", "/api/transport/stop".to_string(), None),
+            RemoteCommand::Next => ("POST", "/api/transport/next".to_string(), None),
+            RemoteCommand::Previous => ("POST", "/api/transport/previous".to_string(), None),
+            RemoteCommand::SeekBy(offset) => (
+                "POST",
+                "/api/seek".to_string(),
+                Some(serde_json::json!({ "offset": offset })),
+            ),
+            RemoteCommand::SeekTo(position) => (
+                "POST",
+                "/api/seek".to_string(),
+                Some(serde_json::json!({ "position": position })),
+            ),
+            RemoteCommand::SetVolume(volume) => (
+                "POST",
+                "/api/volume".to_string(),
+                Some(serde_json::json!({ "volume": volume })),
+            ),
+            RemoteCommand::SetBalance(balance) => (
+                "POST",
+                "/api/balance".to_string(),
+                Some(serde_json::json!({ "balance": balance })),
+            ),
+            RemoteCommand::Enqueue(paths) | RemoteCommand::Open(paths) => (
+                "POST",
+                "/api/queue".to_string(),
+                Some(serde_json::json!({ "paths": paths })),
+            ),
+            RemoteCommand::PlayAt(index) => ("POST", format!("/api/queue/{}/play", index), None),
+            RemoteCommand::Remove(index) => ("DELETE", format!("/api/queue/{}", index), None),
+            RemoteCommand::Clear => ("DELETE", "/api/queue".to_string(), None),
+            RemoteCommand::Move(..)
+            | RemoteCommand::EditDsp(_)
+            | RemoteCommand::SetOutputDevice(_)
+            | RemoteCommand::GetQueue(_)
+            | RemoteCommand::GetLibrary(_)
+            | RemoteCommand::GetStatus(_) => {
+                anyhow::bail!("command not supported by running player")
+            }
+        };
+        self.request(method, &path, body)?;
+        Ok(())
+    }
+
+    /// Send request to HTTP remote API, return body of successful response
+    #[cfg(not(unix))]
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let Self::Http { address, token } = self;
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            method,
+            path,
+            address,
+            body.len()
+        );
+        if let Some(token) = token {
+            head.push_str(&format!("Authorization: Bearer {}\r\n", token));
+        }
+        head.push_str("\r\n");
+
+        let mut stream = TcpStream::connect(address.as_str())?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body.as_bytes())?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+
+        let split = response
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .ok_or_else(|| anyhow::anyhow!("bad response of running player"))?;
+        let body = response.split_off(split + 4);
+        let status_line = String::from_utf8_lossy(&response);
+        let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+        if !status.starts_with('2') {
+            let message = serde_json::from_slice::<ErrorBody>(&body)
+                .map(|e| e.error)
+                .unwrap_or_else(|_| format!("running player answered {}", status));
+            anyhow::bail!(message);
+        }
+        Ok(body)
+    }
+}
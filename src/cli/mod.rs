@@ -0,0 +1,4 @@
+pub mod args;
+pub mod ctl;
+pub mod headless;
+pub mod instance;
@@ -1,9 +1,17 @@
 #![cfg_attr(all(windows, not(debug_assertions)), windows_subsystem = "windows")]
 mod assets;
+mod cli;
 mod service;
 mod ui;
 mod utils;
 
+use std::path::{PathBuf, absolute};
+
+use cli::{
+    args::{self, Command},
+    instance::Instance,
+};
+use service::remote_service::models::RemoteCommand;
 use ui::app::MyApp;
 
 use gpui::{
@@ -12,10 +20,52 @@ use gpui::{
 };
 
 fn main() {
+    let command = match args::parse(std::env::args().skip(1)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, args::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let result = match command {
+        Command::Help => {
+            println!("{}", args::USAGE);
+            Ok(())
+        }
+        Command::Play { paths, output } => cli::headless::run(paths, output),
+        Command::Ctl(ctl) => cli::ctl::run(ctl),
+        Command::Devices => {
+            cli::headless::print_devices();
+            Ok(())
+        }
+        Command::Open(paths) => {
+            // paths are given to running player instead of opening another window
+            let paths: Vec<PathBuf> = paths
+                .into_iter()
+                .map(|p| absolute(&p).unwrap_or(p))
+                .collect();
+            match Instance::connect() {
+                Ok(Some(mut instance)) => instance.send(RemoteCommand::Open(paths)),
+                Ok(None) => {
+                    open_window(paths);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Open player window, paths are opened into queue
+fn open_window(paths: Vec<PathBuf>) {
     Application::new()
         .with_assets(assets::assets::Assets)
-        .run(|cx: &mut App| {
-            let bounds = Bounds::centered(None, size(px(500.), px(500.0)), cx);
+        .run(move |cx: &mut App| {
+            let bounds = Bounds::centered(None, size(px(1100.), px(640.0)), cx);
             cx.open_window(
                 WindowOptions {
                     window_bounds: Some(WindowBounds::Windowed(bounds)),
@@ -26,7 +76,21 @@ fn main() {
                     }),
                     ..Default::default()
                 },
-                |_, cx| cx.new(|_| MyApp::init()),
+                |_, cx| {
+                    cx.new(|cx| {
+                        let mut app = MyApp::init(cx);
+                        #[cfg(unix)]
+                        app.start_ipc();
+                        app.scan_library(cx);
+                        app.watch_library(cx);
+                        #[cfg(target_os = "linux")]
+                        app.start_mpris();
+                        app.start_mpd();
+                        app.start_http();
+                        app.open_paths(&paths, cx);
+                        app
+                    })
+                },
             )
             .unwrap();
         });
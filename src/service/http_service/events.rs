@@ -0,0 +1,72 @@
+use serde::Serialize;
+
+use crate::service::{
+    music_service::models::PlayState,
+    remote_service::models::{PlayerStatus, SongInfo},
+};
+
+/// Event pushed to WebSocket clients, sent as JSON with its type
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    // whole status, sent when client connects
+    Status(&'a PlayerStatus),
+    // position of current song changed
+    PlaytimeRefresh {
+        position: f64,
+        duration: f64,
+    },
+    TrackChanged {
+        index: Option<usize>,
+        song: Option<&'a SongInfo>,
+    },
+    StateChanged {
+        state: PlayState,
+    },
+    VolumeChanged {
+        volume: f32,
+    },
+    QueueChanged {
+        length: usize,
+        version: u64,
+    },
+}
+
+impl Event<'_> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Events of changes from old status to new one
+pub fn changes<'a>(old: Option<&PlayerStatus>, new: &'a PlayerStatus) -> Vec<Event<'a>> {
+    let Some(old) = old else {
+        return vec![Event::Status(new)];
+    };
+    let mut events = Vec::new();
+    if old.state != new.state {
+        events.push(Event::StateChanged { state: new.state });
+    }
+    if old.index != new.index || old.song != new.song {
+        events.push(Event::TrackChanged {
+            index: new.index,
+            song: new.song.as_ref(),
+        });
+    }
+    if old.position != new.position {
+        events.push(Event::PlaytimeRefresh {
+            position: new.position,
+            duration: new.song.as_ref().map_or(0.0, |s| s.duration),
+        });
+    }
+    if old.volume != new.volume {
+        events.push(Event::VolumeChanged { volume: new.volume });
+    }
+    if old.queue_version != new.queue_version || old.queue_len != new.queue_len {
+        events.push(Event::QueueChanged {
+            length: new.queue_len,
+            version: new.queue_version,
+        });
+    }
+    events
+}
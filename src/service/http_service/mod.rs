@@ -0,0 +1,5 @@
+pub mod events;
+pub mod request;
+pub mod routes;
+pub mod server;
+pub mod websocket;
@@ -0,0 +1,219 @@
+use std::io;
+
+use serde::Serialize;
+use smol::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::service::playlist_service::uri;
+
+// limits of request, larger ones are refused
+static MAX_LINE: usize = 8 * 1024;
+static MAX_HEADERS: usize = 64;
+static MAX_BODY: usize = 1024 * 1024;
+
+/// A HTTP request, only one request is read from each connection
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    // path without query
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    // names are lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Read request from stream, None if stream is closed before it
+    pub async fn read(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Option<Self>> {
+        let mut line = String::new();
+        if read_line(reader, &mut line).await? == 0 {
+            return Ok(None);
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(invalid("bad request line"));
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut request = Self {
+            method: method.to_string(),
+            path: uri::decode(path),
+            query: parse_query(query),
+            headers: Vec::new(),
+            body: Vec::new(),
+        };
+        // lines of headers, counted even if they are not headers
+        for count in 0.. {
+            if read_line(reader, &mut line).await? == 0 {
+                return Err(invalid("headers not finished"));
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if count >= MAX_HEADERS {
+                return Err(invalid("too many headers"));
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                request
+                    .headers
+                    .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+        let length = match request.header("content-length") {
+            Some(len) => len.parse().map_err(|_| invalid("bad content length"))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(invalid("body too large"));
+        }
+        request.body = vec![0; length];
+        reader.read_exact(&mut request.body).await?;
+        Ok(Some(request))
+    }
+
+    /// Value of header, name is lowercase
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Value of query parameter
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether request asks to upgrade to WebSocket
+    pub fn is_websocket(&self) -> bool {
+        self.header("upgrade")
+            .is_some_and(|u| u.eq_ignore_ascii_case("websocket"))
+    }
+}
+
+/// A HTTP response, connection is closed after it
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, value: &impl Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec(value).unwrap_or_default(),
+        }
+    }
+
+    /// Empty response of done request
+    pub fn ok() -> Self {
+        Self::json(200, &serde_json::json!({ "ok": true }))
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(status, &serde_json::json!({ "error": message.into() }))
+    }
+
+    pub async fn write(&self, writer: &mut (impl AsyncWrite + Unpin)) -> io::Result<()> {
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        );
+        writer.write_all(head.as_bytes()).await?;
+        writer.write_all(&self.body).await?;
+        writer.flush().await
+    }
+}
+
+/// Read a line of at most MAX_LINE bytes into line, 0 if stream is closed
+async fn read_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    line: &mut String,
+) -> io::Result<usize> {
+    line.clear();
+    let read = (&mut *reader).take(MAX_LINE as u64).read_line(line).await?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(invalid("line too long"));
+    }
+    Ok(read)
+}
+
+/// Parse query like `q=a+b&limit=10`
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| uri::decode(&s.replace('+', " "));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        101 => "Switching Protocols",
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> io::Result<Option<Request>> {
+        smol::block_on(Request::read(&mut text.as_bytes()))
+    }
+
+    #[test]
+    fn reads_request_with_body() {
+        let request = read(
+            "POST /api/seek?token=a%20b HTTP/1.1\r\nHost: localhost:8080\r\nContent-Length: 4\r\n\r\n{}\r\n",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/seek");
+        assert_eq!(request.query("token"), Some("a b"));
+        assert_eq!(request.header("host"), Some("localhost:8080"));
+        assert_eq!(request.body, b"{}\r\n");
+        assert!(read("").unwrap().is_none());
+    }
+
+    #[test]
+    fn long_lines_are_refused() {
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(read(&long).is_err());
+        let header = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(read(&header).is_err());
+    }
+
+    #[test]
+    fn header_lines_are_limited() {
+        // lines without colon count too
+        let lines = "junk\r\n".repeat(MAX_HEADERS + 1);
+        assert!(read(&format!("GET / HTTP/1.1\r\n{}\r\n", lines)).is_err());
+        let lines = "X-A: b\r\n".repeat(MAX_HEADERS);
+        assert!(read(&format!("GET / HTTP/1.1\r\n{}\r\n", lines)).is_ok());
+    }
+}
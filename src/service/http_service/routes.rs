@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::service::{
+    http_service::{
+        request::{Request, Response},
+        server::Shared,
+    },
+    remote_service::models::{RemoteCommand, SongInfo},
+};
+
+// count of search results if not given
+static SEARCH_LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+struct EnqueueBody {
+    paths: Vec<PathBuf>,
+}
+
+/// Seek to position, or by offset from current position, in seconds
+#[derive(Deserialize)]
+struct SeekBody {
+    position: Option<f64>,
+    offset: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct VolumeBody {
+    // from 0.0 to 1.0
+    volume: f32,
+}
+
+#[derive(Deserialize)]
+struct BalanceBody {
+    // from -1.0 (left) to 1.0 (right)
+    balance: f32,
+}
+
+/// Answer request of REST API
+pub async fn handle(request: &Request, shared: &Shared) -> Response {
+    match route(request, shared).await {
+        Ok(response) => response,
+        Err(e) => Response::error(500, e.to_string()),
+    }
+}
+
+async fn route(request: &Request, shared: &Shared) -> Result<Response, anyhow::Error> {
+    let client = shared.client();
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let command = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "state"]) => return Ok(Response::json(200, &shared.status())),
+        ("GET", ["api", "queue"]) => return Ok(Response::json(200, &client.queue().await?)),
+        ("GET", ["api", "library", "search"]) => {
+            let query = request.query("q").unwrap_or_default();
+            let Ok(limit) = request
+                .query("limit")
+                .map_or(Ok(SEARCH_LIMIT), |l| l.parse::<usize>())
+            else {
+                return Ok(Response::error(400, "bad limit"));
+            };
+            let snapshot = client.library().await?;
+            let tracks = snapshot.library.tracks();
+            let songs: Vec<_> = snapshot
+                .library
+                .search(query, limit)
+                .iter()
+                .map(|r| SongInfo::from_track(&tracks[r.track]))
+                .collect();
+            return Ok(Response::json(200, &songs));
+        }
+        ("POST", ["api", "queue"]) => match serde_json::from_slice::<EnqueueBody>(&request.body) {
+            Ok(body) => RemoteCommand::Enqueue(body.paths),
+            Err(e) => return Ok(Response::error(400, e.to_string())),
+        },
+        ("DELETE", ["api", "queue"]) => RemoteCommand::Clear,
+        ("POST", ["api", "queue", index, "play"]) => match index.parse() {
+            Ok(index) => RemoteCommand::PlayAt(index),
+            Err(_) => return Ok(Response::error(400, "bad index")),
+        },
+        ("DELETE", ["api", "queue", index]) => match index.parse() {
+            Ok(index) => RemoteCommand::Remove(index),
+            Err(_) => return Ok(Response::error(400, "bad index")),
+        },
+        ("POST", ["api", "transport", action]) => match *action {
+            "play" => RemoteCommand::Play,
+            "pause" => RemoteCommand::Pause,
+            "toggle" => RemoteCommand::PlayPause,
+            "stop" => RemoteCommand::Stop,
+            "next" => RemoteCommand::Next,
+            "previous" => RemoteCommand::Previous,
+            _ => return Ok(Response::error(404, "unknown action")),
+        },
+        ("POST", ["api", "seek"]) => match serde_json::from_slice::<SeekBody>(&request.body) {
+            Ok(SeekBody {
+                position: Some(position),
+                ..
+            }) => RemoteCommand::SeekTo(position),
+            Ok(SeekBody {
+                offset: Some(offset),
+                ..
+            }) => RemoteCommand::SeekBy(offset),
+            Ok(_) => return Ok(Response::error(400, "position or offset expected")),
+            Err(e) => return Ok(Response::error(400, e.to_string())),
+        },
+        ("POST", ["api", "volume"]) => match serde_json::from_slice::<VolumeBody>(&request.body) {
+            Ok(body) => RemoteCommand::SetVolume(body.volume.clamp(0.0, 1.0)),
+            Err(e) => return Ok(Response::error(400, e.to_string())),
+        },
+        ("POST", ["api", "balance"]) => {
+            match serde_json::from_slice::<BalanceBody>(&request.body) {
+                Ok(body) => RemoteCommand::SetBalance(body.balance.clamp(-1.0, 1.0)),
+                Err(e) => return Ok(Response::error(400, e.to_string())),
+            }
+        }
+        _ => return Ok(Response::error(404, "not found")),
+    };
+    client.send(command)?;
+    Ok(Response::ok())
+}
@@ -0,0 +1,325 @@
+use std::{
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use smol::{
+    Task,
+    channel::{Receiver, Sender},
+    io::{AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::service::{
+    http_service::{
+        events::{self, Event},
+        request::{Request, Response},
+        routes,
+        websocket::{self, OP_CLOSE, OP_PING, OP_PONG, OP_TEXT},
+    },
+    remote_service::{
+        client::RemoteClient,
+        models::{PlayerStatus, RemoteCommand},
+    },
+};
+
+// path of WebSocket pushing events
+static EVENTS_PATH: &str = "/api/events";
+
+/// State shared by connections of server
+pub struct Shared {
+    client: RemoteClient,
+    // host of bound address, the name clients may reach server by
+    host: String,
+    // token clients must give, None allows all
+    token: Option<String>,
+    // last status published by app
+    status: Mutex<Option<PlayerStatus>>,
+    // channels of WebSocket connections, events are sent to them as JSON
+    sockets: Mutex<Vec<Sender<String>>>,
+}
+
+impl Shared {
+    pub fn client(&self) -> &RemoteClient {
+        &self.client
+    }
+
+    pub fn status(&self) -> Option<PlayerStatus> {
+        self.status.lock().ok().and_then(|s| s.clone())
+    }
+
+    /// Whether Host of request is bound host, localhost or an IP address.
+    /// Other names may point to this server by DNS rebinding, so pages of other sites are refused
+    fn allowed_host(&self, request: &Request) -> bool {
+        let Some(host) = request.header("host").map(host_name) else {
+            return false;
+        };
+        host.eq_ignore_ascii_case(&self.host)
+            || host.eq_ignore_ascii_case("localhost")
+            || host.parse::<IpAddr>().is_ok()
+    }
+
+    /// Whether request gives the token, by bearer header or `token` query
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let bearer = request
+            .header("authorization")
+            .and_then(|h| h.strip_prefix("Bearer "));
+        bearer == Some(token.as_str()) || request.query("token") == Some(token.as_str())
+    }
+
+    /// Register a WebSocket connection, it receives the whole status first
+    fn register(&self) -> Receiver<String> {
+        let (sender, receiver) = smol::channel::unbounded();
+        // status is locked until registered, so no change is missed between
+        let Ok(status) = self.status.lock() else {
+            return receiver;
+        };
+        if let Some(status) = status.as_ref() {
+            let _ = sender.try_send(Event::Status(status).to_json());
+        }
+        if let Ok(mut sockets) = self.sockets.lock() {
+            sockets.push(sender);
+        }
+        receiver
+    }
+
+    /// Keep status and push events of its changes to WebSocket connections
+    fn update(&self, status: PlayerStatus) {
+        let Ok(mut last) = self.status.lock() else {
+            return;
+        };
+        let messages: Vec<String> = events::changes(last.as_ref(), &status)
+            .iter()
+            .map(Event::to_json)
+            .collect();
+        *last = Some(status);
+        if let Ok(mut sockets) = self.sockets.lock() {
+            sockets.retain(|s| messages.iter().all(|m| s.try_send(m.clone()).is_ok()));
+        }
+    }
+}
+
+/// Serve HTTP remote API on address in a thread.
+/// Requests are sent to app as commands, statuses published by app are pushed to WebSockets
+pub fn start(
+    address: String,
+    token: Option<String>,
+    commands: Sender<RemoteCommand>,
+    statuses: Receiver<PlayerStatus>,
+) {
+    thread::spawn(move || {
+        smol::block_on(async move {
+            let listener = match TcpListener::bind(&address).await {
+                Ok(l) => l,
+                Err(e) => {
+                    eprintln!("failed to start http server on {}: {}", address, e);
+                    return;
+                }
+            };
+            let shared = Arc::new(Shared {
+                client: RemoteClient::new(commands),
+                host: host_name(&address).to_string(),
+                token,
+                status: Mutex::new(None),
+                sockets: Mutex::new(Vec::new()),
+            });
+            smol::spawn(watch_status(shared.clone(), statuses)).detach();
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let shared = shared.clone();
+                        smol::spawn(async move {
+                            if let Err(e) = serve(stream, shared).await {
+                                eprintln!("error in http connection: {}", e);
+                            }
+                        })
+                        .detach();
+                    }
+                    Err(e) => eprintln!("error when accept http connection: {}", e),
+                }
+            }
+        })
+    });
+}
+
+/// Take every status published by app until it is closed
+async fn watch_status(shared: Arc<Shared>, statuses: Receiver<PlayerStatus>) {
+    while let Ok(status) = statuses.recv().await {
+        shared.update(status);
+    }
+}
+
+/// Answer one request of connection, or push events if it is a WebSocket
+async fn serve(stream: TcpStream, shared: Arc<Shared>) -> io::Result<()> {
+    let mut writer = stream.clone();
+    let mut reader = BufReader::new(stream);
+    let Some(request) = Request::read(&mut reader).await? else {
+        return Ok(());
+    };
+    let response = if !shared.allowed_host(&request) {
+        Response::error(403, "host not allowed")
+    } else if !shared.authorized(&request) {
+        Response::error(401, "token required")
+    } else if is_cross_origin(&request) {
+        // pages of other sites must not control the player
+        Response::error(403, "cross origin request")
+    } else if request.is_websocket() {
+        match request.header("sec-websocket-key") {
+            Some(key) if request.path.trim_end_matches('/') == EVENTS_PATH => {
+                writer
+                    .write_all(websocket::handshake(key).as_bytes())
+                    .await?;
+                return push_events(reader, writer, shared).await;
+            }
+            Some(_) => Response::error(404, "not found"),
+            None => Response::error(400, "websocket key required"),
+        }
+    } else {
+        routes::handle(&request, &shared).await
+    };
+    response.write(&mut writer).await
+}
+
+/// Whether request is sent by a page not served from the same host
+fn is_cross_origin(request: &Request) -> bool {
+    let Some(origin) = request.header("origin") else {
+        return false;
+    };
+    let host = origin.split_once("://").map_or(origin, |(_, h)| h);
+    request.header("host") != Some(host)
+}
+
+/// Host of `host:port` without port, IPv6 addresses are without brackets
+fn host_name(address: &str) -> &str {
+    if let Some(rest) = address.strip_prefix('[') {
+        return rest.split_once(']').map_or(rest, |(host, _)| host);
+    }
+    address.rsplit_once(':').map_or(address, |(host, _)| host)
+}
+
+/// What woke up WebSocket connection
+enum Wake {
+    Message(Option<String>),
+    Frame(Option<(u8, Vec<u8>)>),
+}
+
+/// Push events to WebSocket until client or server closes it
+async fn push_events(
+    reader: BufReader<TcpStream>,
+    mut writer: TcpStream,
+    shared: Arc<Shared>,
+) -> io::Result<()> {
+    let messages = shared.register();
+    let (frames, _reader) = read_frames(reader);
+    loop {
+        let message = async { Wake::Message(messages.recv().await.ok()) };
+        let frame = async { Wake::Frame(frames.recv().await.ok()) };
+        match smol::future::or(message, frame).await {
+            Wake::Message(Some(message)) => {
+                websocket::write_frame(&mut writer, OP_TEXT, message.as_bytes()).await?
+            }
+            Wake::Frame(Some((OP_PING, payload))) => {
+                websocket::write_frame(&mut writer, OP_PONG, &payload).await?
+            }
+            // messages from client are ignored
+            Wake::Frame(Some((opcode, _))) if opcode != OP_CLOSE => {}
+            _ => {
+                let _ = websocket::write_frame(&mut writer, OP_CLOSE, &[]).await;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Read frames of client in a task, frames are received while waiting for events
+fn read_frames(mut reader: BufReader<TcpStream>) -> (Receiver<(u8, Vec<u8>)>, Task<()>) {
+    let (sender, receiver) = smol::channel::unbounded();
+    let task = smol::spawn(async move {
+        while let Ok(frame) = websocket::read_frame(&mut reader).await {
+            let closed = frame.0 == OP_CLOSE;
+            if sender.send(frame).await.is_err() || closed {
+                break;
+            }
+        }
+    });
+    (receiver, task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: "/api/state".to_string(),
+            query: Vec::new(),
+            headers: headers
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            body: Vec::new(),
+        }
+    }
+
+    fn shared(address: &str) -> Shared {
+        Shared {
+            client: RemoteClient::new(smol::channel::unbounded().0),
+            host: host_name(address).to_string(),
+            token: None,
+            status: Mutex::new(None),
+            sockets: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[test]
+    fn host_name_drops_port() {
+        assert_eq!(host_name("127.0.0.1:8080"), "127.0.0.1");
+        assert_eq!(host_name("[::1]:8080"), "::1");
+        assert_eq!(host_name("player.lan"), "player.lan");
+    }
+
+    #[test]
+    fn only_bound_host_localhost_and_addresses_are_allowed() {
+        let shared = shared("player.lan:8080");
+        let allowed = [
+            "player.lan:8080",
+            "PLAYER.lan",
+            "localhost:8080",
+            "192.168.1.5:8080",
+            "[::1]:8080",
+        ];
+        for host in allowed {
+            assert!(shared.allowed_host(&request(&[("host", host)])), "{}", host);
+        }
+        // names of other sites may resolve to this server
+        for host in ["evil.example:8080", "player.lan.evil.example"] {
+            assert!(
+                !shared.allowed_host(&request(&[("host", host)])),
+                "{}",
+                host
+            );
+        }
+        assert!(!shared.allowed_host(&request(&[])));
+    }
+
+    #[test]
+    fn pages_of_other_origins_are_cross_origin() {
+        let same = request(&[
+            ("host", "localhost:8080"),
+            ("origin", "http://localhost:8080"),
+        ]);
+        assert!(!is_cross_origin(&same));
+        let other = request(&[
+            ("host", "localhost:8080"),
+            ("origin", "http://evil.example"),
+        ]);
+        assert!(is_cross_origin(&other));
+        assert!(!is_cross_origin(&request(&[("host", "localhost:8080")])));
+    }
+}
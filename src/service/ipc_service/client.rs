@@ -0,0 +1,65 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::net::UnixStream,
+    time::Duration,
+};
+
+use crate::service::ipc_service::protocol::{
+    PROTOCOL_VERSION, Reply, Request, check_private, socket_path,
+};
+
+// time to wait for replies of running player
+static TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Connection to the running player of this user
+pub struct IpcClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl IpcClient {
+    /// Connect to running player and greet it, None if no player is running
+    pub fn connect() -> Result<Option<Self>, anyhow::Error> {
+        let path = socket_path();
+        // socket of another user must not be given files or trusted for status
+        for private in [path.parent(), Some(path.as_path())].into_iter().flatten() {
+            match check_private(private) {
+                Ok(()) => (),
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let stream = match UnixStream::connect(&path) {
+            Ok(s) => s,
+            // socket is missing or left by a closed player
+            Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut client = Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+        match client.request(&Request::Hello {
+            version: PROTOCOL_VERSION,
+        })? {
+            Reply::Hello { .. } => Ok(Some(client)),
+            Reply::Error { message } => anyhow::bail!(message),
+            reply => anyhow::bail!("unexpected reply of running player: {:?}", reply),
+        }
+    }
+
+    /// Send request and wait for its reply
+    pub fn request(&mut self, request: &Request) -> Result<Reply, anyhow::Error> {
+        let mut message = serde_json::to_string(request)?;
+        message.push('\n');
+        self.writer.write_all(message.as_bytes())?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("running player closed connection");
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
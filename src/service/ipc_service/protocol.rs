@@ -0,0 +1,189 @@
+use std::{
+    env, fs, io,
+    os::unix::fs::{DirBuilderExt, MetadataExt},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::service::{
+    music_service::dsp::DspEdit,
+    remote_service::models::{PlayerStatus, RemoteCommand},
+};
+
+// version of messages, changed when old clients can not talk with new players
+pub static PROTOCOL_VERSION: u32 = 1;
+
+/// Message from a client, sent as a JSON line.
+/// The first message must be `Hello`, others are answered by a `Reply` each
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Hello { version: u32 },
+    // open files like launched with them
+    Open { paths: Vec<PathBuf> },
+    Enqueue { paths: Vec<PathBuf> },
+    Play,
+    Pause,
+    Toggle,
+    Stop,
+    Next,
+    Previous,
+    // seconds of current song
+    SeekTo { position: f64 },
+    // seconds from current position, negative goes back
+    SeekBy { offset: f64 },
+    // from 0.0 to 1.0
+    SetVolume { volume: f32 },
+    // from -1.0 (left) to 1.0 (right)
+    SetBalance { balance: f32 },
+    EditDsp { edit: DspEdit },
+    // None for default device
+    SetOutputDevice { name: Option<String> },
+    Status,
+}
+
+/// Message from player answering a request, sent as a JSON line
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    Hello { version: u32 },
+    Ok,
+    Status { status: Box<PlayerStatus> },
+    Error { message: String },
+}
+
+impl Request {
+    /// Command applied to app, None for requests not sent as commands
+    pub fn into_command(self) -> Option<RemoteCommand> {
+        let command = match self {
+            Request::Hello { .. } | Request::Status => return None,
+            Request::Open { paths } => RemoteCommand::Open(paths),
+            Request::Enqueue { paths } => RemoteCommand::Enqueue(paths),
+            Request::Play => RemoteCommand::Play,
+            Request::Pause => RemoteCommand::Pause,
+            Request::Toggle => RemoteCommand::PlayPause,
+            Request::Stop => RemoteCommand::Stop,
+            Request::Next => RemoteCommand::Next,
+            Request::Previous => RemoteCommand::Previous,
+            Request::SeekTo { position } => RemoteCommand::SeekTo(position),
+            Request::SeekBy { offset } => RemoteCommand::SeekBy(offset),
+            Request::SetVolume { volume } => RemoteCommand::SetVolume(volume),
+            Request::SetBalance { balance } => RemoteCommand::SetBalance(balance),
+            Request::EditDsp { edit } => RemoteCommand::EditDsp(edit),
+            Request::SetOutputDevice { name } => RemoteCommand::SetOutputDevice(name),
+        };
+        Some(command)
+    }
+
+    /// Request of command, None for commands replying data
+    pub fn from_command(command: RemoteCommand) -> Option<Self> {
+        let request = match command {
+            RemoteCommand::Open(paths) => Request::Open { paths },
+            RemoteCommand::Enqueue(paths) => Request::Enqueue { paths },
+            RemoteCommand::Play => Request::Play,
+            RemoteCommand::Pause => Request::Pause,
+            RemoteCommand::PlayPause => Request::Toggle,
+            RemoteCommand::Stop => Request::Stop,
+            RemoteCommand::Next => Request::Next,
+            RemoteCommand::Previous => Request::Previous,
+            RemoteCommand::SeekTo(position) => Request::SeekTo { position },
+            RemoteCommand::SeekBy(offset) => Request::SeekBy { offset },
+            RemoteCommand::SetVolume(volume) => Request::SetVolume { volume },
+            RemoteCommand::SetBalance(balance) => Request::SetBalance { balance },
+            RemoteCommand::EditDsp(edit) => Request::EditDsp { edit },
+            RemoteCommand::SetOutputDevice(name) => Request::SetOutputDevice { name },
+            RemoteCommand::PlayAt(_)
+            | RemoteCommand::Remove(_)
+            | RemoteCommand::Move(..)
+            | RemoteCommand::Clear
+            | RemoteCommand::GetQueue(_)
+            | RemoteCommand::GetLibrary(_)
+            | RemoteCommand::GetStatus(_) => return None,
+        };
+        Some(request)
+    }
+}
+
+/// Path of socket of this user, in a folder only this user can reach
+pub fn socket_path() -> PathBuf {
+    socket_dir().join("the_player.sock")
+}
+
+/// Folder of socket, in runtime folder if there is one, otherwise in shared temp folder
+fn socket_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("the_player"),
+        None => env::temp_dir().join(format!("the_player-{}", uid())),
+    }
+}
+
+/// Create folder of socket closed to other users, or check the existing one
+pub fn create_socket_dir() -> io::Result<PathBuf> {
+    let dir = socket_dir();
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => (),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+        Err(e) => return Err(e),
+    }
+    check_private(&dir)?;
+    Ok(dir)
+}
+
+/// Refuse path not owned by this user. Folders must also be closed to other users,
+/// as another user may have made them first in shared temp folder
+pub fn check_private(path: &Path) -> io::Result<()> {
+    // links are not followed, they may point anywhere
+    let meta = fs::symlink_metadata(path)?;
+    let open = meta.is_dir() && meta.mode() & 0o077 != 0;
+    if meta.uid() != uid() || meta.file_type().is_symlink() || open {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not private to this user", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+fn uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and never fails
+    unsafe { libc::geteuid() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::utils::temp_dir::TempDir;
+
+    #[test]
+    fn folders_open_to_others_or_links_are_not_private() {
+        let root = TempDir::new("ipc");
+        let dir = root.join("socket");
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .unwrap();
+        assert!(check_private(&dir).is_ok());
+
+        let link = root.join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(check_private(&link).is_err());
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(check_private(&dir).is_err());
+        assert_eq!(
+            check_private(&root.join("missing")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn socket_is_inside_folder_of_user() {
+        let path = socket_path();
+        assert_eq!(path.parent(), Some(socket_dir().as_path()));
+        assert_ne!(path.parent(), Some(env::temp_dir().as_path()));
+    }
+}
@@ -0,0 +1,116 @@
+use std::{fs, io, os::unix::fs::PermissionsExt, thread};
+
+use smol::{
+    channel::Sender,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::unix::{UnixListener, UnixStream},
+    stream::StreamExt,
+};
+
+use crate::service::{
+    ipc_service::protocol::{
+        PROTOCOL_VERSION, Reply, Request, check_private, create_socket_dir, socket_path,
+    },
+    remote_service::{client::RemoteClient, models::RemoteCommand},
+};
+
+/// Serve socket of this user in a thread, so later launches talk to this player.
+/// Requests are sent to app as commands
+pub fn start(commands: Sender<RemoteCommand>) {
+    thread::spawn(move || {
+        smol::block_on(async move {
+            let listener = match bind().await {
+                Ok(l) => l,
+                Err(e) => {
+                    eprintln!("failed to start ipc socket: {}", e);
+                    return;
+                }
+            };
+            let client = RemoteClient::new(commands);
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let client = client.clone();
+                        smol::spawn(async move {
+                            if let Err(e) = serve(stream, client).await {
+                                eprintln!("error in ipc connection: {}", e);
+                            }
+                        })
+                        .detach();
+                    }
+                    Err(e) => eprintln!("error when accept ipc connection: {}", e),
+                }
+            }
+        })
+    });
+}
+
+/// Bind socket in private folder of this user, a socket left by a closed player is replaced
+async fn bind() -> io::Result<UnixListener> {
+    create_socket_dir()?;
+    let path = socket_path();
+    if fs::symlink_metadata(&path).is_ok() {
+        if UnixStream::connect(&path).await.is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another player is running",
+            ));
+        }
+        check_private(&path)?;
+        fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    // only this user may control the player
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Answer requests of a client until it closes connection
+async fn serve(stream: UnixStream, client: RemoteClient) -> io::Result<()> {
+    let mut writer = stream.clone();
+    let mut lines = BufReader::new(stream).lines();
+    let mut greeted = false;
+    while let Some(line) = lines.next().await {
+        let reply = match serde_json::from_str::<Request>(&line?) {
+            Err(e) => Reply::Error {
+                message: format!("bad request: {}", e),
+            },
+            Ok(Request::Hello { version }) if version == PROTOCOL_VERSION => {
+                greeted = true;
+                Reply::Hello {
+                    version: PROTOCOL_VERSION,
+                }
+            }
+            Ok(Request::Hello { version }) => Reply::Error {
+                message: format!(
+                    "protocol version {} not supported, player speaks {}",
+                    version, PROTOCOL_VERSION
+                ),
+            },
+            Ok(_) if !greeted => Reply::Error {
+                message: "hello expected".to_string(),
+            },
+            Ok(Request::Status) => match client.status().await {
+                Ok(status) => Reply::Status {
+                    status: Box::new(status),
+                },
+                Err(e) => Reply::Error {
+                    message: e.to_string(),
+                },
+            },
+            Ok(request) => match request.into_command().map(|c| client.send(c)) {
+                Some(Ok(())) => Reply::Ok,
+                Some(Err(e)) => Reply::Error {
+                    message: e.to_string(),
+                },
+                None => Reply::Error {
+                    message: "request not supported".to_string(),
+                },
+            },
+        };
+        let mut message = serde_json::to_string(&reply).unwrap_or_default();
+        message.push('\n');
+        writer.write_all(message.as_bytes()).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    service::library_service::{
+        models::{Album, Artist, ScanChanges, Track},
+        query::Query,
+        search::{SearchIndex, SearchResult},
+    },
+    utils::config::config_dir,
+};
+
+static INDEX_FILE: &str = "library.json";
+// bump when format of index changes, older index is dropped and rescanned
+static INDEX_VERSION: u32 = 1;
+
+/// Index saved on disk, albums and artists are rebuilt from tracks
+#[derive(Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    tracks: Vec<Track>,
+}
+
+/// Tracks of library folders, with albums and artists built from their tags
+#[derive(Clone, Default)]
+pub struct Library {
+    tracks: Vec<Track>,
+    // track index by path
+    paths: HashMap<PathBuf, usize>,
+    albums: Vec<Album>,
+    artists: Vec<Artist>,
+    // words of tracks, shared between copies of library
+    search: Arc<SearchIndex>,
+}
+
+impl Library {
+    /// Load index from file, empty if not exist or broken
+    pub fn load() -> Self {
+        let mut tracks = Self::path()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|s| serde_json::from_str::<IndexFile>(&s).ok())
+            .filter(|i| i.version == INDEX_VERSION)
+            .map(|i| i.tracks)
+            .unwrap_or_default();
+        // tracks indexed before added time was recorded count from their modified time
+        for track in &mut tracks {
+            if track.added == 0 {
+                track.added = track.mtime;
+            }
+        }
+        let mut library = Self {
+            tracks,
+            ..Default::default()
+        };
+        library.rebuild();
+        library
+    }
+
+    /// Save index into file
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let dir = config_dir().ok_or_else(|| anyhow::anyhow!("config folder not found"))?;
+        fs::create_dir_all(&dir)?;
+        let index = IndexFile {
+            version: INDEX_VERSION,
+            tracks: self.tracks.clone(),
+        };
+        // write aside then rename, a crash never leaves a broken index
+        let tmp = dir.join(format!("{}.tmp", INDEX_FILE));
+        fs::write(&tmp, serde_json::to_vec(&index)?)?;
+        fs::rename(tmp, dir.join(INDEX_FILE))?;
+        Ok(())
+    }
+
+    fn path() -> Option<PathBuf> {
+        config_dir().map(|d| d.join(INDEX_FILE))
+    }
+
+    /// Apply changes of a scan or watcher.
+    /// Play counts and ratings are kept for modified and renamed files.
+    pub fn apply(&mut self, changes: ScanChanges) {
+        if changes.is_empty() {
+            return;
+        }
+        for (from, to) in &changes.renamed {
+            for track in &mut self.tracks {
+                if let Some(path) = moved_path(&track.path, from, to) {
+                    track.path = path;
+                }
+            }
+        }
+
+        // drop removed files and every file under removed folders
+        let removed_paths: HashSet<&Path> = changes.removed.iter().map(PathBuf::as_path).collect();
+        let (mut removed, tracks): (Vec<Track>, Vec<Track>) = std::mem::take(&mut self.tracks)
+            .into_iter()
+            .partition(|t| t.path.ancestors().any(|p| removed_paths.contains(p)));
+        self.tracks = tracks;
+
+        let mut paths: HashMap<PathBuf, usize> = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(ix, t)| (t.path.clone(), ix))
+            .collect();
+        for mut track in changes.updated {
+            if let Some(&ix) = paths.get(&track.path) {
+                track.keep_stats(&self.tracks[ix]);
+                self.tracks[ix] = track;
+                continue;
+            }
+            // a removed track with same content was renamed outside of watcher
+            if let Some(pos) = removed.iter().position(|r| r.same_content(&track)) {
+                track.keep_stats(&removed.swap_remove(pos));
+            }
+            paths.insert(track.path.clone(), self.tracks.len());
+            self.tracks.push(track);
+        }
+        self.rebuild();
+    }
+
+    /// Count a play of track at time (unix ms), return false if not in library
+    pub fn record_play(&mut self, path: &Path, time: u64) -> bool {
+        let Some(&ix) = self.paths.get(path) else {
+            return false;
+        };
+        let track = &mut self.tracks[ix];
+        track.play_count += 1;
+        track.last_played = Some(time);
+        true
+    }
+
+    /// Rate track from 0 to 5, None to clear. Return false if not in library
+    pub fn set_rating(&mut self, path: &Path, rating: Option<u8>) -> bool {
+        let Some(&ix) = self.paths.get(path) else {
+            return false;
+        };
+        self.tracks[ix].rating = rating.map(|r| r.min(5));
+        true
+    }
+
+    /// Size and modified time of indexed tracks, used by incremental scan
+    pub fn stamps(&self) -> HashMap<PathBuf, (u64, u64)> {
+        self.tracks
+            .iter()
+            .map(|t| (t.path.clone(), (t.size, t.mtime)))
+            .collect()
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn albums(&self) -> &[Album] {
+        &self.albums
+    }
+
+    pub fn artists(&self) -> &[Artist] {
+        &self.artists
+    }
+
+    /// Search tracks by query like `artist:radiohead year:>2000`, best first
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchResult> {
+        self.search
+            .search(&self.tracks, &Query::parse(query), limit)
+    }
+
+    /// Find track by path
+    pub fn track(&self, path: &Path) -> Option<&Track> {
+        self.paths.get(path).map(|&ix| &self.tracks[ix])
+    }
+
+    /// Rebuild path lookup, albums and artists from tracks
+    fn rebuild(&mut self) {
+        self.tracks.sort_by(|a, b| a.path.cmp(&b.path));
+        self.paths = self
+            .tracks
+            .iter()
+            .enumerate()
+            .map(|(ix, t)| (t.path.clone(), ix))
+            .collect();
+
+        // group albums case-insensitively by title and artist
+        let mut album_ix: HashMap<(String, String), usize> = HashMap::new();
+        let mut albums: Vec<Album> = Vec::new();
+        for (ix, track) in self.tracks.iter().enumerate() {
+            let title = track.album.clone().unwrap_or_default();
+            let artist = track.album_artist_or_artist().map(str::to_string);
+            let key = (
+                title.to_lowercase(),
+                artist.as_deref().unwrap_or_default().to_lowercase(),
+            );
+            let album = *album_ix.entry(key).or_insert_with(|| {
+                albums.push(Album {
+                    title,
+                    artist,
+                    year: None,
+                    tracks: Vec::new(),
+                });
+                albums.len() - 1
+            });
+            let album = &mut albums[album];
+            album.tracks.push(ix);
+            album.year = match (album.year, track.year) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+        let tracks = &self.tracks;
+        for album in &mut albums {
+            album.tracks.sort_by_key(|&ix| {
+                let t = &tracks[ix];
+                (t.disc_number.unwrap_or(0), t.track_number.unwrap_or(0))
+            });
+        }
+        albums.sort_by(|a, b| {
+            let key = |a: &Album| {
+                (
+                    a.artist.as_deref().unwrap_or_default().to_lowercase(),
+                    a.year,
+                )
+            };
+            key(a)
+                .cmp(&key(b))
+                .then_with(|| a.title.to_lowercase().cmp(&b.title.to_lowercase()))
+        });
+
+        let mut artist_ix: HashMap<String, usize> = HashMap::new();
+        let mut artists: Vec<Artist> = Vec::new();
+        for (ix, album) in albums.iter().enumerate() {
+            let Some(name) = &album.artist else {
+                continue;
+            };
+            let artist = *artist_ix.entry(name.to_lowercase()).or_insert_with(|| {
+                artists.push(Artist {
+                    name: name.clone(),
+                    albums: Vec::new(),
+                    tracks: Vec::new(),
+                });
+                artists.len() - 1
+            });
+            artists[artist].albums.push(ix);
+            artists[artist].tracks.extend_from_slice(&album.tracks);
+        }
+        artists.sort_by_key(|a| a.name.to_lowercase());
+
+        self.albums = albums;
+        self.artists = artists;
+        self.search = Arc::new(SearchIndex::build(&self.tracks));
+    }
+}
+
+/// New path of a file after its path or one of its folders is renamed
+pub fn moved_path(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    let rest = path.strip_prefix(from).ok()?;
+    if rest.as_os_str().is_empty() {
+        return Some(to.to_path_buf());
+    }
+    Some(to.join(rest))
+}
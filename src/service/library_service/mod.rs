@@ -0,0 +1,7 @@
+pub mod library;
+pub mod models;
+pub mod query;
+pub mod scanner;
+pub mod search;
+pub mod smart_playlist;
+pub mod watcher;
@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// A music file in library
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Track {
+    pub path: PathBuf,
+    // file size and modified time (unix ms) when tags were read
+    pub size: u64,
+    pub mtime: u64,
+    // title falls back to file name if not tagged
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    // duration in seconds
+    pub duration: f64,
+    // listening stats, kept when file is modified or renamed
+    #[serde(default)]
+    pub play_count: u32,
+    // rating from 0 to 5, None if not rated
+    #[serde(default)]
+    pub rating: Option<u8>,
+    // time (unix ms) of last play, None if never played
+    #[serde(default)]
+    pub last_played: Option<u64>,
+    // time (unix ms) track was added into library, 0 for tracks indexed before it was recorded
+    #[serde(default)]
+    pub added: u64,
+}
+
+impl Track {
+    /// Artist the album of track is listed under
+    pub fn album_artist_or_artist(&self) -> Option<&str> {
+        self.album_artist.as_deref().or(self.artist.as_deref())
+    }
+
+    /// Whether two tracks are likely the same file, used to find renamed files
+    pub fn same_content(&self, other: &Track) -> bool {
+        self.size == other.size
+            && self.duration == other.duration
+            && self.title == other.title
+            && self.artist == other.artist
+            && self.album == other.album
+    }
+
+    /// Take play count, rating and added time of old track
+    pub fn keep_stats(&mut self, old: &Track) {
+        self.play_count = old.play_count;
+        self.rating = old.rating;
+        self.last_played = old.last_played;
+        self.added = old.added;
+    }
+}
+
+/// Tracks grouped by album title and album artist
+#[derive(Clone, Debug)]
+pub struct Album {
+    pub title: String,
+    pub artist: Option<String>,
+    // the earliest year of tracks
+    pub year: Option<u32>,
+    // indices into library tracks, sorted by disc and track number
+    pub tracks: Vec<usize>,
+}
+
+/// Albums and tracks of an artist
+#[derive(Clone, Debug)]
+pub struct Artist {
+    pub name: String,
+    // indices into library albums
+    pub albums: Vec<usize>,
+    // indices into library tracks
+    pub tracks: Vec<usize>,
+}
+
+/// Changes found by a library scan or folder watcher
+#[derive(Clone, Default)]
+pub struct ScanChanges {
+    // renamed files or folders, from old path to new path
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+    // new or modified tracks
+    pub updated: Vec<Track>,
+    // files or folders no longer exist
+    pub removed: Vec<PathBuf>,
+}
+
+impl ScanChanges {
+    pub fn is_empty(&self) -> bool {
+        self.renamed.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Events of library scanning job
+pub enum LibraryEvents {
+    // count of files to be read
+    Started(usize),
+    // read count, total count and the reading file
+    Progress(usize, usize, PathBuf),
+    Failed(PathBuf, String),
+    Finished(ScanChanges),
+    // changes found by folder watcher
+    Changed(ScanChanges),
+}
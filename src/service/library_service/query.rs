@@ -0,0 +1,367 @@
+use crate::service::library_service::{models::Track, search::normalize};
+
+static DAY_MILLIS: f64 = 86_400_000.0;
+
+/// Text fields of track
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Field {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    FileName,
+}
+
+impl Field {
+    pub const ALL: [Field; 6] = [
+        Field::Title,
+        Field::Artist,
+        Field::Album,
+        Field::AlbumArtist,
+        Field::Genre,
+        Field::FileName,
+    ];
+
+    /// Weight of matches in field when ranking
+    pub fn weight(&self) -> f32 {
+        match self {
+            Field::Title => 1.0,
+            Field::Artist => 0.9,
+            Field::Album | Field::AlbumArtist => 0.8,
+            Field::Genre => 0.5,
+            Field::FileName => 0.4,
+        }
+    }
+
+    /// Text of field in track, None if not tagged
+    pub fn text<'a>(&self, track: &'a Track) -> Option<&'a str> {
+        match self {
+            Field::Title => Some(&track.title),
+            Field::Artist => track.artist.as_deref(),
+            Field::Album => track.album.as_deref(),
+            Field::AlbumArtist => track.album_artist.as_deref(),
+            Field::Genre => track.genre.as_deref(),
+            Field::FileName => track.path.file_stem().and_then(|s| s.to_str()),
+        }
+    }
+}
+
+/// Number fields of track
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NumberField {
+    Year,
+    Plays,
+    Rating,
+    // seconds
+    Duration,
+    // days since last play, never played tracks are infinitely old
+    LastPlayed,
+    // days since added into library
+    Added,
+}
+
+impl NumberField {
+    /// Value of field in track, now is unix ms for fields counting days
+    pub fn value(&self, track: &Track, now: u64) -> Option<f64> {
+        let days = |time: u64| now.saturating_sub(time) as f64 / DAY_MILLIS;
+        match self {
+            NumberField::Year => track.year.map(f64::from),
+            NumberField::Plays => Some(track.play_count as f64),
+            NumberField::Rating => track.rating.map(f64::from),
+            NumberField::Duration => Some(track.duration),
+            NumberField::LastPlayed => Some(track.last_played.map_or(f64::INFINITY, days)),
+            NumberField::Added => Some(days(track.added)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compare {
+    Eq(f64),
+    Lt(f64),
+    Le(f64),
+    Gt(f64),
+    Ge(f64),
+    // inclusive
+    Range(f64, f64),
+}
+
+impl Compare {
+    pub fn test(&self, v: f64) -> bool {
+        match *self {
+            Compare::Eq(x) => v == x,
+            Compare::Lt(x) => v < x,
+            Compare::Le(x) => v <= x,
+            Compare::Gt(x) => v > x,
+            Compare::Ge(x) => v >= x,
+            Compare::Range(a, b) => v >= a && v <= b,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Term {
+    // normalized word, matched in any field if field is None
+    Text {
+        field: Option<Field>,
+        word: String,
+        negate: bool,
+    },
+    Number {
+        field: NumberField,
+        compare: Compare,
+    },
+}
+
+/// Parsed search query, every term must match
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+impl Query {
+    /// Parse query like `artist:radiohead year:>2000 "ok computer" -live`.
+    /// Quoted values are split into words of same field, unknown fields are searched as text.
+    pub fn parse(s: &str) -> Self {
+        let mut terms = Vec::new();
+        for (raw, quoted) in split(s) {
+            let (negate, raw) = match raw.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() && !quoted => (true, rest),
+                _ => (false, raw.as_str()),
+            };
+            let (field, value) = match raw.split_once(':') {
+                Some((name, value)) if !quoted => match parse_field(name) {
+                    Some(f) => (Some(f), value),
+                    None => (None, raw),
+                },
+                _ => (None, raw),
+            };
+
+            match field {
+                Some(FieldName::Number(field)) => {
+                    if let Some(compare) = parse_compare(value, field) {
+                        terms.push(Term::Number { field, compare });
+                    }
+                }
+                Some(FieldName::Text(field)) => push_words(&mut terms, Some(field), value, negate),
+                None => push_words(&mut terms, None, value, negate),
+            }
+        }
+        Self { terms }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Whether track matches every term, text terms match whole words without typos.
+    /// now is unix ms for fields counting days
+    pub fn matches(&self, track: &Track, now: u64) -> bool {
+        self.terms.iter().all(|term| match term {
+            Term::Text {
+                field,
+                word,
+                negate,
+            } => {
+                let found = Field::ALL
+                    .into_iter()
+                    .filter(|f| field.is_none_or(|field| field == *f))
+                    .filter_map(|f| f.text(track))
+                    .any(|text| words(&normalize(text)).any(|w| w == word));
+                found != *negate
+            }
+            Term::Number { field, compare } => {
+                field.value(track, now).is_some_and(|v| compare.test(v))
+            }
+        })
+    }
+}
+
+enum FieldName {
+    Text(Field),
+    Number(NumberField),
+}
+
+fn parse_field(name: &str) -> Option<FieldName> {
+    let field = match name.to_lowercase().as_str() {
+        "title" | "t" => FieldName::Text(Field::Title),
+        "artist" | "a" => FieldName::Text(Field::Artist),
+        "album" | "al" => FieldName::Text(Field::Album),
+        "albumartist" | "aa" => FieldName::Text(Field::AlbumArtist),
+        "genre" | "g" => FieldName::Text(Field::Genre),
+        "file" | "filename" | "f" => FieldName::Text(Field::FileName),
+        "year" | "y" => FieldName::Number(NumberField::Year),
+        "plays" | "playcount" => FieldName::Number(NumberField::Plays),
+        "rating" | "r" => FieldName::Number(NumberField::Rating),
+        "length" | "duration" => FieldName::Number(NumberField::Duration),
+        "played" | "lastplayed" => FieldName::Number(NumberField::LastPlayed),
+        "added" => FieldName::Number(NumberField::Added),
+        _ => return None,
+    };
+    Some(field)
+}
+
+fn push_words(terms: &mut Vec<Term>, field: Option<Field>, value: &str, negate: bool) {
+    for word in words(&normalize(value)) {
+        terms.push(Term::Text {
+            field,
+            word: word.to_string(),
+            negate,
+        });
+    }
+}
+
+/// Parse ">2000", "<=3", "1990..1999" or "2001". Duration accepts "m:ss"
+fn parse_compare(s: &str, field: NumberField) -> Option<Compare> {
+    let number = |s: &str| -> Option<f64> {
+        let s = s.trim();
+        match (field, s.split_once(':')) {
+            (NumberField::Duration, Some((m, sec))) => {
+                Some(m.parse::<f64>().ok()? * 60.0 + sec.parse::<f64>().ok()?)
+            }
+            _ => s.parse().ok(),
+        }
+    };
+    if let Some((a, b)) = s.split_once("..") {
+        return Some(Compare::Range(number(a)?, number(b)?));
+    }
+    let compare = if let Some(v) = s.strip_prefix(">=") {
+        Compare::Ge(number(v)?)
+    } else if let Some(v) = s.strip_prefix("<=") {
+        Compare::Le(number(v)?)
+    } else if let Some(v) = s.strip_prefix('>') {
+        Compare::Gt(number(v)?)
+    } else if let Some(v) = s.strip_prefix('<') {
+        Compare::Lt(number(v)?)
+    } else {
+        Compare::Eq(number(s.strip_prefix('=').unwrap_or(s))?)
+    };
+    Some(compare)
+}
+
+/// Split query by whitespace, keeping quoted parts like `artist:"massive attack"` together.
+/// Return parts with quotes removed and whether they had quotes.
+fn split(s: &str) -> Vec<(String, bool)> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quote = false;
+    for c in s.chars() {
+        match c {
+            '"' => {
+                in_quote = !in_quote;
+                quoted = true;
+            }
+            c if c.is_whitespace() && !in_quote => {
+                if !current.is_empty() {
+                    parts.push((std::mem::take(&mut current), quoted));
+                }
+                quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        parts.push((current, quoted));
+    }
+    // a field name before quotes is still a field, like artist:"a b"
+    parts
+        .into_iter()
+        .map(|(p, q)| match p.split_once(':') {
+            Some((name, _)) if q && parse_field(name).is_some() => (p, false),
+            _ => (p, q),
+        })
+        .collect()
+}
+
+/// Words of normalized text
+pub fn words(s: &str) -> impl Iterator<Item = &str> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn track(year: Option<u32>, play_count: u32, duration: f64) -> Track {
+        Track {
+            path: PathBuf::from("/music/Radiohead - Airbag.flac"),
+            size: 0,
+            mtime: 0,
+            title: "Airbag".to_string(),
+            artist: Some("Radiohead".to_string()),
+            album: Some("OK Computer".to_string()),
+            album_artist: None,
+            genre: None,
+            year,
+            track_number: None,
+            disc_number: None,
+            duration,
+            play_count,
+            rating: None,
+            last_played: None,
+            added: 0,
+        }
+    }
+
+    fn number(field: NumberField, compare: Compare) -> Term {
+        Term::Number { field, compare }
+    }
+
+    #[test]
+    fn parses_number_comparisons() {
+        let query = Query::parse("year:>2000 plays:<=3 length:>=4:30 y:1990..1999 rating:=5");
+        assert_eq!(
+            query.terms,
+            vec![
+                number(NumberField::Year, Compare::Gt(2000.0)),
+                number(NumberField::Plays, Compare::Le(3.0)),
+                number(NumberField::Duration, Compare::Ge(270.0)),
+                number(NumberField::Year, Compare::Range(1990.0, 1999.0)),
+                number(NumberField::Rating, Compare::Eq(5.0)),
+            ]
+        );
+        // invalid numbers are dropped rather than searched as text
+        assert!(Query::parse("year:>abc").is_empty());
+    }
+
+    #[test]
+    fn parses_fields_and_quotes() {
+        let query = Query::parse("artist:\"Massive Attack\" -live Björk");
+        let text = |field, word: &str, negate| Term::Text {
+            field,
+            word: word.to_string(),
+            negate,
+        };
+        assert_eq!(
+            query.terms,
+            vec![
+                text(Some(Field::Artist), "massive", false),
+                text(Some(Field::Artist), "attack", false),
+                text(None, "live", true),
+                text(None, "bjork", false),
+            ]
+        );
+        // unknown field names are plain text
+        assert_eq!(
+            Query::parse("foo:bar").terms,
+            vec![text(None, "foo", false), text(None, "bar", false)]
+        );
+    }
+
+    #[test]
+    fn matches_number_terms() {
+        let airbag = track(Some(1997), 2, 284.0);
+        assert!(Query::parse("year:>1990 plays:<3").matches(&airbag, 0));
+        assert!(Query::parse("year:1997 length:4:00..5:00").matches(&airbag, 0));
+        assert!(!Query::parse("year:>1997").matches(&airbag, 0));
+        // untagged fields never match
+        assert!(!Query::parse("year:>0").matches(&track(None, 0, 0.0), 0));
+        assert!(Query::parse("artist:radiohead -live").matches(&airbag, 0));
+        assert!(!Query::parse("album:airbag").matches(&airbag, 0));
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::UNIX_EPOCH,
+};
+
+use lofty::tag::{Accessor, ItemKey};
+use smol::channel::{Receiver, Sender};
+
+use crate::{
+    service::{
+        library_service::models::{LibraryEvents, ScanChanges, Track},
+        music_service::music::Music,
+    },
+    utils::utils,
+};
+
+/// Scan folders recursively on a background thread.
+/// known maps path of indexed tracks to their (size, mtime), only new or changed files are read.
+pub fn scan_folders(
+    folders: Vec<PathBuf>,
+    known: HashMap<PathBuf, (u64, u64)>,
+) -> Receiver<LibraryEvents> {
+    let (tx, rx) = smol::channel::unbounded::<LibraryEvents>();
+    thread::spawn(move || {
+        let mut files = Vec::new();
+        for folder in &folders {
+            collect_files(folder, &mut files);
+        }
+
+        let seen: HashSet<&PathBuf> = files.iter().map(|(p, _)| p).collect();
+        let removed = known
+            .keys()
+            .filter(|p| !seen.contains(p))
+            .cloned()
+            .collect();
+        let changed: Vec<_> = files
+            .iter()
+            .filter(|(path, stamp)| known.get(path) != Some(stamp))
+            .collect();
+        let total = changed.len();
+        send(&tx, LibraryEvents::Started(total));
+
+        let mut updated = Vec::with_capacity(total);
+        for (done, (path, (size, mtime))) in changed.into_iter().enumerate() {
+            send(&tx, LibraryEvents::Progress(done, total, path.clone()));
+            match read_track(path, *size, *mtime) {
+                Ok(t) => updated.push(t),
+                Err(e) => send(&tx, LibraryEvents::Failed(path.clone(), e.to_string())),
+            }
+        }
+        let changes = ScanChanges {
+            renamed: Vec::new(),
+            updated,
+            removed,
+        };
+        send(&tx, LibraryEvents::Finished(changes));
+    });
+    rx
+}
+
+/// Read tags of file into a track
+pub fn read_track(path: &Path, size: u64, mtime: u64) -> Result<Track, anyhow::Error> {
+    let music = Music::from_path(path)?;
+    let tags = music.get_tags();
+    let text = |s: Option<std::borrow::Cow<'_, str>>| {
+        s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+    };
+    let title = text(tags.and_then(|t| t.title())).unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+
+    Ok(Track {
+        path: path.to_path_buf(),
+        size,
+        mtime,
+        title,
+        artist: text(tags.and_then(|t| t.artist())),
+        album: text(tags.and_then(|t| t.album())),
+        album_artist: text(
+            tags.and_then(|t| t.get_string(&ItemKey::AlbumArtist))
+                .map(Into::into),
+        ),
+        genre: text(tags.and_then(|t| t.genre())),
+        year: tags.and_then(|t| t.year()),
+        track_number: tags.and_then(|t| t.track()),
+        disc_number: tags.and_then(|t| t.disk()),
+        duration: music.get_duration().as_secs_f64(),
+        play_count: 0,
+        rating: None,
+        last_played: None,
+        added: utils::now_millis(),
+    })
+}
+
+/// Size and modified time (unix ms) of file
+pub fn file_stamp(path: &Path) -> Option<(u64, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    Some((meta.len(), mtime))
+}
+
+/// Collect supported music files recursively with their stamps.
+/// Linked folders are followed, but a folder reached again, like by a link to its parent, is skipped
+pub fn collect_files(dir: &Path, files: &mut Vec<(PathBuf, (u64, u64))>) {
+    let mut visited = HashSet::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(real) = fs::canonicalize(&dir) else {
+            continue;
+        };
+        if !visited.insert(real) {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if Music::is_supported(&path)
+                && let Some(stamp) = file_stamp(&path)
+            {
+                files.push((path, stamp));
+            }
+        }
+    }
+}
+
+fn send(tx: &Sender<LibraryEvents>, event: LibraryEvents) {
+    if let Err(e) = tx.try_send(event) {
+        eprintln!("error when send event: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_dir::TempDir;
+
+    #[test]
+    fn collect_files_walks_every_folder_once() {
+        let root = TempDir::new("scanner_collect");
+        let song = root.file("album/disc 1/a.flac");
+        root.file("album/cover.jpg");
+        #[cfg(unix)]
+        {
+            // links back to a parent and to a folder walked already
+            std::os::unix::fs::symlink(root.path(), root.join("album/disc 1/up")).unwrap();
+            std::os::unix::fs::symlink(root.join("album"), root.join("again")).unwrap();
+        }
+
+        let mut files = Vec::new();
+        collect_files(root.path(), &mut files);
+        let paths: Vec<_> = files.into_iter().map(|(p, _)| p).collect();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(
+            fs::canonicalize(&paths[0]).ok(),
+            fs::canonicalize(song).ok()
+        );
+    }
+}
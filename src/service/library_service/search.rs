@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+use crate::{
+    service::library_service::{
+        models::Track,
+        query::{Field, Query, Term, words},
+    },
+    utils::utils,
+};
+
+// longest word compared for typos
+const MAX_WORD_LEN: usize = 32;
+
+/// A ranked track of search, index into library tracks
+#[derive(Clone, Copy, Debug)]
+pub struct SearchResult {
+    pub track: usize,
+    pub score: f32,
+}
+
+/// Inverted index from words of track fields to tracks.
+/// Query words are matched against unique words, so fuzzy matching costs by vocabulary size.
+#[derive(Default)]
+pub struct SearchIndex {
+    // unique words and tracks having them
+    words: Vec<Box<str>>,
+    postings: Vec<Vec<(u32, Field)>>,
+    // char count and char mask of words, to skip words too far for typo matching
+    lens: Vec<usize>,
+    masks: Vec<u64>,
+    // normalized title, for bonus of query matching title start
+    titles: Vec<Box<str>>,
+}
+
+impl SearchIndex {
+    /// Build index of tracks, track indices of results follow the slice
+    pub fn build(tracks: &[Track]) -> Self {
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        let mut index = Self::default();
+        for (ix, track) in tracks.iter().enumerate() {
+            for field in Field::ALL {
+                let Some(text) = field.text(track) else {
+                    continue;
+                };
+                let text = normalize(text);
+                for word in words(&text) {
+                    let id = match ids.get(word) {
+                        Some(&id) => id,
+                        None => {
+                            index.words.push(word.into());
+                            index.postings.push(Vec::new());
+                            index.lens.push(word.chars().count());
+                            index.masks.push(char_mask(word));
+                            ids.insert(word.to_string(), index.words.len() - 1);
+                            index.words.len() - 1
+                        }
+                    };
+                    let posting = &mut index.postings[id];
+                    // same word twice in a field counts once
+                    if posting.last() != Some(&(ix as u32, field)) {
+                        posting.push((ix as u32, field));
+                    }
+                }
+            }
+            index.titles.push(normalize(&track.title).into());
+        }
+        index
+    }
+
+    /// Find tracks matching every term of query, best first.
+    /// tracks must be the ones index was built from, numbers like play count are read from them.
+    pub fn search(&self, tracks: &[Track], query: &Query, limit: usize) -> Vec<SearchResult> {
+        if query.is_empty() || tracks.len() != self.titles.len() {
+            return Vec::new();
+        }
+        let mut scores = vec![0.0f32; tracks.len()];
+        let mut matched = vec![0u16; tracks.len()];
+        let mut excluded = vec![false; tracks.len()];
+        let mut required = 0u16;
+        // best score of a term in every track, reused between terms
+        let mut best = vec![0.0f32; tracks.len()];
+        let mut touched: Vec<u32> = Vec::new();
+
+        for term in &query.terms {
+            let Term::Text {
+                field,
+                word,
+                negate,
+            } = term
+            else {
+                continue;
+            };
+            let term = QueryWord {
+                chars: word.chars().collect(),
+                mask: char_mask(word),
+                text: word,
+            };
+            for (id, w) in self.words.iter().enumerate() {
+                let Some(score) = term.score(w, self.lens[id], self.masks[id]) else {
+                    continue;
+                };
+                for &(track, f) in &self.postings[id] {
+                    if field.is_some_and(|field| field != f) {
+                        continue;
+                    }
+                    let s = score * f.weight();
+                    let b = &mut best[track as usize];
+                    if *b == 0.0 {
+                        touched.push(track);
+                    }
+                    *b = b.max(s);
+                }
+            }
+
+            if !negate {
+                required += 1;
+            }
+            for track in touched.drain(..) {
+                let t = track as usize;
+                if *negate {
+                    excluded[t] = true;
+                } else {
+                    scores[t] += best[t];
+                    matched[t] += 1;
+                }
+                best[t] = 0.0;
+            }
+        }
+
+        // bonus of title starting with the text of query
+        let text: Vec<&str> = query
+            .terms
+            .iter()
+            .filter_map(|t| match t {
+                Term::Text {
+                    field: None,
+                    word,
+                    negate: false,
+                } => Some(word.as_str()),
+                _ => None,
+            })
+            .collect();
+        let text = text.join(" ");
+
+        let now = utils::now_millis();
+        let mut results: Vec<SearchResult> = (0..tracks.len())
+            .filter(|&t| matched[t] == required && !excluded[t])
+            .filter(|&t| number_terms_match(&tracks[t], query, now))
+            .map(|t| {
+                let mut score = scores[t];
+                if !text.is_empty() && self.titles[t].starts_with(&text) {
+                    score += 0.5;
+                }
+                SearchResult { track: t, score }
+            })
+            .collect();
+        results.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| tracks[b.track].play_count.cmp(&tracks[a.track].play_count))
+                .then_with(|| self.titles[a.track].cmp(&self.titles[b.track]))
+        });
+        results.truncate(limit);
+        results
+    }
+}
+
+fn number_terms_match(track: &Track, query: &Query, now: u64) -> bool {
+    query.terms.iter().all(|term| {
+        let Term::Number { field, compare } = term else {
+            return true;
+        };
+        field.value(track, now).is_some_and(|v| compare.test(v))
+    })
+}
+
+/// A word of query with data for typo matching
+struct QueryWord<'a> {
+    text: &'a str,
+    chars: Vec<char>,
+    mask: u64,
+}
+
+impl QueryWord<'_> {
+    /// Score of matching an indexed word, from exact match to typos
+    fn score(&self, word: &str, len: usize, mask: u64) -> Option<f32> {
+        if word == self.text {
+            return Some(1.0);
+        }
+        if word.starts_with(self.text) {
+            return Some(0.7 + 0.2 * self.text.len() as f32 / word.len() as f32);
+        }
+        if self.text.len() >= 2 && word.contains(self.text) {
+            return Some(0.5);
+        }
+        // typos, one for words of 4 chars and two for 8 chars
+        let max = match self.chars.len() {
+            0..4 => return None,
+            4..8 => 1,
+            _ => 2,
+        };
+        // every typo removes at most one char of term from word
+        if len.abs_diff(self.chars.len()) > max || (self.mask & !mask).count_ones() as usize > max {
+            return None;
+        }
+        let distance = bounded_distance(&self.chars, word, max)?;
+        Some(0.45 - 0.1 * distance as f32)
+    }
+}
+
+/// Bits of chars in word, folded into 64 buckets
+fn char_mask(word: &str) -> u64 {
+    word.chars().fold(0, |mask, c| mask | 1 << (c as u32 % 64))
+}
+
+/// Edit distance if not larger than max, swapping two neighbor chars counts as one edit
+fn bounded_distance(term: &[char], word: &str, max: usize) -> Option<usize> {
+    // long words are never typos of query, skip them to keep rows on stack
+    let mut chars = ['\0'; MAX_WORD_LEN];
+    let mut len = 0;
+    for c in word.chars() {
+        *chars.get_mut(len)? = c;
+        len += 1;
+    }
+    let word = &chars[..len];
+    // rows of distance matrix, two rows back are kept for swaps
+    let mut before = [0; MAX_WORD_LEN + 1];
+    let mut prev = [0; MAX_WORD_LEN + 1];
+    let mut cur = [0; MAX_WORD_LEN + 1];
+    for (j, d) in prev.iter_mut().enumerate() {
+        *d = j;
+    }
+    for (i, &a) in term.iter().enumerate() {
+        cur[0] = i + 1;
+        let mut row_min = cur[0];
+        for (j, &b) in word.iter().enumerate() {
+            let cost = usize::from(a != b);
+            let mut d = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+            if i > 0 && j > 0 && a == word[j - 1] && term[i - 1] == b {
+                d = d.min(before[j - 1] + 1);
+            }
+            cur[j + 1] = d;
+            row_min = row_min.min(d);
+        }
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut before, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    let distance = prev[word.len()];
+    (distance <= max).then_some(distance)
+}
+
+/// Lowercase text and remove diacritics, like "Björk" to "bjork"
+pub fn normalize(s: &str) -> String {
+    if s.is_ascii() {
+        return s.to_ascii_lowercase();
+    }
+    let mut normalized = String::with_capacity(s.len());
+    for c in s
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+    {
+        // letters not decomposed by unicode
+        match c {
+            'ø' => normalized.push('o'),
+            'ł' => normalized.push('l'),
+            'đ' => normalized.push('d'),
+            'ß' => normalized.push_str("ss"),
+            'æ' => normalized.push_str("ae"),
+            'œ' => normalized.push_str("oe"),
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
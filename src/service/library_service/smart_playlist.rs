@@ -0,0 +1,172 @@
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::ErrorKind,
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    service::library_service::{library::Library, query::Query},
+    utils::config::config_dir,
+};
+
+static PLAYLISTS_FILE: &str = "smart_playlists.json";
+
+/// Order of smart playlist tracks
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum PlaylistSort {
+    Random,
+    Title,
+    Artist,
+    Album,
+    Year,
+    Plays,
+    Rating,
+    LastPlayed,
+    Added,
+}
+
+impl PlaylistSort {
+    pub const ALL: [PlaylistSort; 9] = [
+        PlaylistSort::Random,
+        PlaylistSort::Title,
+        PlaylistSort::Artist,
+        PlaylistSort::Album,
+        PlaylistSort::Year,
+        PlaylistSort::Plays,
+        PlaylistSort::Rating,
+        PlaylistSort::LastPlayed,
+        PlaylistSort::Added,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlaylistSort::Random => "Random",
+            PlaylistSort::Title => "Title",
+            PlaylistSort::Artist => "Artist",
+            PlaylistSort::Album => "Album",
+            PlaylistSort::Year => "Year",
+            PlaylistSort::Plays => "Plays",
+            PlaylistSort::Rating => "Rating",
+            PlaylistSort::LastPlayed => "Last played",
+            PlaylistSort::Added => "Added",
+        }
+    }
+}
+
+/// Playlist of library tracks matching rules, evaluated again whenever library changes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SmartPlaylist {
+    pub name: String,
+    // rules in search query syntax, like `genre:rock rating:>=4 played:>30`
+    pub rules: String,
+    pub sort: PlaylistSort,
+    pub descending: bool,
+    // most tracks kept after sorting, None for all
+    pub limit: Option<usize>,
+    // seed of random order, kept so order does not change between evaluations
+    pub seed: u64,
+}
+
+impl SmartPlaylist {
+    pub fn new(name: String, rules: String) -> Self {
+        Self {
+            name,
+            rules,
+            sort: PlaylistSort::Random,
+            descending: false,
+            limit: None,
+            seed: new_seed(),
+        }
+    }
+
+    /// Shuffle random order again
+    pub fn reshuffle(&mut self) {
+        self.seed = new_seed();
+    }
+
+    /// Indices into library tracks matching rules, sorted and limited.
+    /// now is unix ms, rules like `added:<7` count days from it
+    pub fn evaluate(&self, library: &Library, now: u64) -> Vec<usize> {
+        let query = Query::parse(&self.rules);
+        let tracks = library.tracks();
+        let mut rows: Vec<usize> = (0..tracks.len())
+            .filter(|&ix| query.matches(&tracks[ix], now))
+            .collect();
+
+        let text = |s: &Option<String>| s.as_deref().unwrap_or_default().to_lowercase();
+        match self.sort {
+            PlaylistSort::Random => rows.sort_by_cached_key(|&ix| {
+                let mut hasher = DefaultHasher::new();
+                (self.seed, &tracks[ix].path).hash(&mut hasher);
+                hasher.finish()
+            }),
+            PlaylistSort::Title => rows.sort_by_cached_key(|&ix| tracks[ix].title.to_lowercase()),
+            PlaylistSort::Artist => rows.sort_by_cached_key(|&ix| text(&tracks[ix].artist)),
+            PlaylistSort::Album => rows.sort_by_cached_key(|&ix| {
+                let t = &tracks[ix];
+                (text(&t.album), t.disc_number, t.track_number)
+            }),
+            PlaylistSort::Year => rows.sort_by_key(|&ix| tracks[ix].year),
+            PlaylistSort::Plays => rows.sort_by_key(|&ix| tracks[ix].play_count),
+            PlaylistSort::Rating => rows.sort_by_key(|&ix| tracks[ix].rating),
+            PlaylistSort::LastPlayed => rows.sort_by_key(|&ix| tracks[ix].last_played),
+            PlaylistSort::Added => rows.sort_by_key(|&ix| tracks[ix].added),
+        }
+        if self.descending {
+            rows.reverse();
+        }
+        if let Some(limit) = self.limit {
+            rows.truncate(limit);
+        }
+        rows
+    }
+}
+
+/// Load smart playlists from file, examples are given if not exist.
+/// Unreadable file is an error, so it is not overwritten by empty list
+pub fn load() -> Result<Vec<SmartPlaylist>, anyhow::Error> {
+    let Some(path) = path() else {
+        return Ok(Vec::new());
+    };
+    let s = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(examples()),
+        Err(e) => anyhow::bail!("failed to read {}: {}", path.display(), e),
+    };
+    serde_json::from_str(&s)
+        .map_err(|e| anyhow::anyhow!("failed to parse {}: {}", path.display(), e))
+}
+
+/// Save smart playlists into file
+pub fn save(playlists: &[SmartPlaylist]) -> Result<(), anyhow::Error> {
+    let dir = config_dir().ok_or_else(|| anyhow::anyhow!("config folder not found"))?;
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        dir.join(PLAYLISTS_FILE),
+        serde_json::to_string_pretty(playlists)?,
+    )?;
+    Ok(())
+}
+
+fn path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join(PLAYLISTS_FILE))
+}
+
+fn examples() -> Vec<SmartPlaylist> {
+    let mut top_rated = SmartPlaylist::new("Top rated".into(), "rating:>=4".into());
+    top_rated.limit = Some(100);
+    let mut recently_added = SmartPlaylist::new("Recently added".into(), "added:<=7".into());
+    recently_added.sort = PlaylistSort::Added;
+    recently_added.descending = true;
+    let forgotten = SmartPlaylist::new("Not played lately".into(), "played:>30".into());
+    vec![top_rated, recently_added, forgotten]
+}
+
+fn new_seed() -> u64 {
+    let mut hasher = DefaultHasher::new();
+    std::time::SystemTime::now().hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,131 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use notify::{
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    event::{ModifyKind, RenameMode},
+};
+use smol::channel::{Receiver, Sender};
+
+use crate::service::{
+    library_service::{
+        models::{LibraryEvents, ScanChanges},
+        scanner,
+    },
+    music_service::music::Music,
+};
+
+// quiet time after last event before changes are sent
+static DEBOUNCE: Duration = Duration::from_millis(500);
+// send changes even if events keep coming
+static MAX_DELAY: Duration = Duration::from_secs(3);
+
+/// Watch folders recursively (inotify on Linux), watching stops when dropped
+pub struct FolderWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl FolderWatcher {
+    /// Start watching, bursts of events are merged and sent as Changed events
+    pub fn new(folders: &[PathBuf]) -> Result<(Self, Receiver<LibraryEvents>), anyhow::Error> {
+        let (raw_tx, raw_rx) = mpsc::channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(raw_tx)?;
+        for folder in folders {
+            watcher.watch(folder, RecursiveMode::Recursive)?;
+        }
+
+        let (tx, rx) = smol::channel::unbounded::<LibraryEvents>();
+        // ends when watcher is dropped
+        thread::spawn(move || debounce(raw_rx, tx));
+        Ok((Self { _watcher: watcher }, rx))
+    }
+}
+
+/// Paths touched by events since last sending
+#[derive(Default)]
+struct Pending {
+    renamed: Vec<(PathBuf, PathBuf)>,
+    touched: HashSet<PathBuf>,
+}
+
+impl Pending {
+    fn add(&mut self, event: Event) {
+        match event.kind {
+            EventKind::Access(_) => (),
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let (from, to) = (event.paths[0].clone(), event.paths[1].clone());
+                // a renamed file is read again, as it may be renamed from unsupported name
+                if to.is_file() {
+                    self.touched.insert(to.clone());
+                }
+                self.renamed.push((from, to));
+            }
+            _ => self.touched.extend(event.paths),
+        }
+    }
+
+    /// Read touched files into changes
+    fn resolve(self, tx: &Sender<LibraryEvents>) -> ScanChanges {
+        let mut changes = ScanChanges {
+            renamed: self.renamed,
+            ..Default::default()
+        };
+        let mut files = Vec::new();
+        for path in self.touched {
+            if path.is_dir() {
+                // folder moved in
+                scanner::collect_files(&path, &mut files);
+            } else if path.is_file() {
+                if Music::is_supported(&path)
+                    && let Some(stamp) = scanner::file_stamp(&path)
+                {
+                    files.push((path, stamp));
+                }
+            } else {
+                changes.removed.push(path);
+            }
+        }
+        for (path, (size, mtime)) in files {
+            match scanner::read_track(&path, size, mtime) {
+                Ok(t) => changes.updated.push(t),
+                Err(e) => {
+                    let _ = tx.try_send(LibraryEvents::Failed(path, e.to_string()));
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// Merge raw events until they are quiet, then send the changes
+fn debounce(raw_rx: mpsc::Receiver<notify::Result<Event>>, tx: Sender<LibraryEvents>) {
+    let mut pending = Pending::default();
+    let mut first: Option<Instant> = None;
+    loop {
+        let received = match first {
+            Some(f) if f.elapsed() >= MAX_DELAY => Err(RecvTimeoutError::Timeout),
+            Some(f) => raw_rx.recv_timeout(DEBOUNCE.min(MAX_DELAY.saturating_sub(f.elapsed()))),
+            None => raw_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(Ok(event)) => {
+                pending.add(event);
+                first.get_or_insert_with(Instant::now);
+            }
+            Ok(Err(e)) => eprintln!("error when watch folders: {}", e),
+            Err(RecvTimeoutError::Timeout) => {
+                first = None;
+                let changes = std::mem::take(&mut pending).resolve(&tx);
+                if !changes.is_empty() && tx.try_send(LibraryEvents::Changed(changes)).is_err() {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::f64::consts::PI;
+
+// ReplayGain 2.0 reference loudness (LUFS)
+pub static REFERENCE_LOUDNESS: f64 = -18.0;
+// oversampling factor of true peak
+static OVERSAMPLE: usize = 4;
+// taps of each true peak interpolation phase
+static INTERP_TAPS: usize = 12;
+
+/// A biquad filter of one channel
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[1] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[2] * y;
+        y
+    }
+}
+
+/// Loudness meter following ITU-R BS.1770 / EBU R128
+pub struct Meter {
+    channels: usize,
+    // K-weighting filters of every channel
+    shelf: Vec<Biquad>,
+    highpass: Vec<Biquad>,
+    // weight of every channel
+    weights: Vec<f64>,
+    // frames of a 100ms step
+    step_frames: usize,
+    // weighted square sum of every 100ms step in current block
+    steps: Vec<f64>,
+    step_sum: f64,
+    step_len: usize,
+    // mean square of every 400ms block
+    blocks: Vec<f64>,
+    // true peak interpolation
+    interp: Vec<Vec<f64>>,
+    history: Vec<Vec<f64>>,
+    peak: f64,
+}
+
+impl Meter {
+    /// Create a meter for stream
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let rate = sample_rate as f64;
+
+        // stage 1: high shelf filter
+        let (f0, g, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(g / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        };
+
+        // stage 2: high pass filter
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let highpass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        };
+
+        // surround channels of 5.1 weight more, LFE is ignored
+        let weights = (0..channels)
+            .map(|ch| match (channels, ch) {
+                (6, 3) => 0.0,
+                (6, 4) | (6, 5) => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+
+        Self {
+            channels,
+            shelf: vec![shelf; channels],
+            highpass: vec![highpass; channels],
+            weights,
+            step_frames: (sample_rate / 10).max(1) as usize,
+            steps: Vec::with_capacity(4),
+            step_sum: 0.0,
+            step_len: 0,
+            blocks: Vec::new(),
+            interp: Self::interp_coeffs(),
+            history: vec![vec![0.0; INTERP_TAPS]; channels],
+            peak: 0.0,
+        }
+    }
+
+    /// Windowed sinc coefficients of every oversampling phase
+    fn interp_coeffs() -> Vec<Vec<f64>> {
+        (0..OVERSAMPLE)
+            .map(|phase| {
+                (0..INTERP_TAPS)
+                    .map(|tap| {
+                        let x = tap as f64 - (INTERP_TAPS / 2) as f64
+                            + phase as f64 / OVERSAMPLE as f64;
+                        let sinc = if x == 0.0 {
+                            1.0
+                        } else {
+                            (PI * x).sin() / (PI * x)
+                        };
+                        let n = (tap * OVERSAMPLE + phase) as f64;
+                        let window = 0.5
+                            - 0.5 * (2.0 * PI * n / (INTERP_TAPS * OVERSAMPLE) as f64).cos();
+                        sinc * window
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Feed interleaved samples
+    pub fn process(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            let mut sum = 0.0;
+            for (ch, &sample) in frame.iter().enumerate() {
+                let x = sample as f64;
+                self.update_peak(ch, x);
+                let y = self.highpass[ch].process(self.shelf[ch].process(x));
+                sum += self.weights[ch] * y * y;
+            }
+            self.step_sum += sum;
+            self.step_len += 1;
+            if self.step_len == self.step_frames {
+                self.finish_step();
+            }
+        }
+    }
+
+    /// Interpolate between samples to find true peak
+    fn update_peak(&mut self, ch: usize, x: f64) {
+        let history = &mut self.history[ch];
+        history.rotate_left(1);
+        history[INTERP_TAPS - 1] = x;
+        for coeffs in &self.interp {
+            let y: f64 = coeffs.iter().zip(history.iter()).map(|(c, h)| c * h).sum();
+            self.peak = self.peak.max(y.abs());
+        }
+        self.peak = self.peak.max(x.abs());
+    }
+
+    /// A 100ms step finished, blocks of 400ms overlap by 75%
+    fn finish_step(&mut self) {
+        self.steps.push(self.step_sum / self.step_frames as f64);
+        self.step_sum = 0.0;
+        self.step_len = 0;
+        if self.steps.len() == 4 {
+            self.blocks.push(self.steps.iter().sum::<f64>() / 4.0);
+            self.steps.remove(0);
+        }
+    }
+
+    /// Mean square of every gating block
+    pub fn blocks(&self) -> &[f64] {
+        &self.blocks
+    }
+
+    /// True peak (linear) of all processed samples
+    pub fn true_peak(&self) -> f64 {
+        self.peak
+    }
+
+    /// Integrated loudness (LUFS), None if too short or silent
+    pub fn integrated_loudness(&self) -> Option<f64> {
+        Self::gated_loudness(&self.blocks)
+    }
+
+    /// Integrated loudness of blocks with absolute and relative gating
+    pub fn gated_loudness(blocks: &[f64]) -> Option<f64> {
+        let loudness = |z: f64| -0.691 + 10.0 * z.log10();
+        let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+        // absolute gate -70 LUFS
+        let absolute: Vec<f64> = blocks
+            .iter()
+            .copied()
+            .filter(|&z| z > 0.0 && loudness(z) > -70.0)
+            .collect();
+        if absolute.is_empty() {
+            return None;
+        }
+        // relative gate -10 LU
+        let threshold = loudness(mean(&absolute)) - 10.0;
+        let relative: Vec<f64> = absolute
+            .into_iter()
+            .filter(|&z| loudness(z) > threshold)
+            .collect();
+        if relative.is_empty() {
+            return None;
+        }
+        Some(loudness(mean(&relative)))
+    }
+}
@@ -0,0 +1,3 @@
+pub mod meter;
+pub mod models;
+pub mod scanner;
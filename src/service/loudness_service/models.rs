@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+/// Loudness scan result of a music
+#[derive(Clone)]
+pub struct ScanResult {
+    pub path: PathBuf,
+    // integrated loudness (LUFS)
+    pub loudness: f64,
+    // true peak (linear)
+    pub peak: f64,
+    // track gain (dB) to reference loudness
+    pub track_gain: f64,
+    // album gain (dB) and peak, set when album finished
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+/// Events of scanning job
+pub enum ScanEvents {
+    // count of musics to be scanned
+    Started(usize),
+    // scanned count, total count and the scanning music
+    Progress(usize, usize, PathBuf),
+    Scanned(ScanResult),
+    Failed(PathBuf, String),
+    Finished,
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use anyhow::anyhow;
+use lofty::{
+    config::WriteOptions,
+    file::TaggedFileExt,
+    tag::{Accessor, ItemKey, Tag, TagExt},
+};
+use smol::channel::{Receiver, Sender};
+
+use crate::service::{
+    library_service::scanner::collect_files,
+    loudness_service::{
+        meter::{self, Meter},
+        models::{ScanEvents, ScanResult},
+    },
+    music_service::{decoder::Decoder, music::Music, stream::Stream},
+};
+
+/// Decode a music without playing and measure its loudness
+pub fn scan_file(path: &Path) -> Result<Meter, anyhow::Error> {
+    let mut decoded = Decoder::decode_from_path(path.to_path_buf())?;
+    let track_id = decoded
+        .format
+        .default_track()
+        .ok_or_else(|| anyhow!("no track found"))?
+        .id;
+
+    let mut meter: Option<Meter> = None;
+    while let Ok(package) = decoded.format.next_packet() {
+        if package.track_id() != track_id {
+            continue;
+        }
+        let buff = match decoded.decoder.decode(&package) {
+            Ok(b) => b,
+            // broken package, skip it
+            Err(symphonia::core::errors::Error::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let (sample, sample_rate, channels, _) = Stream::transfer_to_f32(buff);
+        meter
+            .get_or_insert_with(|| Meter::new(sample_rate, channels))
+            .process(&sample);
+    }
+    meter.ok_or_else(|| anyhow!("no audio decoded"))
+}
+
+/// Scan musics of an album, album gain is computed over all of them.
+/// Return results of succeeded musics and errors of failed ones.
+pub fn scan_album(
+    paths: &[PathBuf],
+    mut on_progress: impl FnMut(&Path),
+) -> (Vec<ScanResult>, Vec<(PathBuf, anyhow::Error)>) {
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    let mut album_blocks = Vec::new();
+    let mut album_peak: f64 = 0.0;
+
+    for path in paths {
+        on_progress(path);
+        let meter = match scan_file(path) {
+            Ok(m) => m,
+            Err(e) => {
+                errors.push((path.clone(), e));
+                continue;
+            }
+        };
+        let Some(loudness) = meter.integrated_loudness() else {
+            errors.push((path.clone(), anyhow!("music is too short or silent")));
+            continue;
+        };
+        album_blocks.extend_from_slice(meter.blocks());
+        album_peak = album_peak.max(meter.true_peak());
+        results.push(ScanResult {
+            path: path.clone(),
+            loudness,
+            peak: meter.true_peak(),
+            track_gain: meter::REFERENCE_LOUDNESS - loudness,
+            album_gain: None,
+            album_peak: None,
+        });
+    }
+
+    if let Some(loudness) = Meter::gated_loudness(&album_blocks) {
+        for r in &mut results {
+            r.album_gain = Some(meter::REFERENCE_LOUDNESS - loudness);
+            r.album_peak = Some(album_peak);
+        }
+    }
+    (results, errors)
+}
+
+/// Write scan result into ReplayGain tags of music
+pub fn write_tags(result: &ScanResult) -> Result<(), anyhow::Error> {
+    let mut tagged = lofty::read_from_path(&result.path)?;
+    if tagged.primary_tag().is_none() {
+        tagged.insert_tag(Tag::new(tagged.primary_tag_type()));
+    }
+    let tag = tagged
+        .primary_tag_mut()
+        .ok_or_else(|| anyhow!("can not create tag"))?;
+
+    tag.insert_text(
+        ItemKey::ReplayGainTrackGain,
+        format!("{:.2} dB", result.track_gain),
+    );
+    tag.insert_text(ItemKey::ReplayGainTrackPeak, format!("{:.6}", result.peak));
+    if let (Some(gain), Some(peak)) = (result.album_gain, result.album_peak) {
+        tag.insert_text(ItemKey::ReplayGainAlbumGain, format!("{:.2} dB", gain));
+        tag.insert_text(ItemKey::ReplayGainAlbumPeak, format!("{:.6}", peak));
+    }
+    tag.save_to_path(&result.path, WriteOptions::default())?;
+    Ok(())
+}
+
+/// Scan musics on a background thread, folders are scanned recursively.
+/// Musics are grouped into albums by folder and album tag.
+/// If skip_tagged, albums already have ReplayGain tags will be skipped.
+pub fn scan_paths(paths: Vec<PathBuf>, write: bool, skip_tagged: bool) -> Receiver<ScanEvents> {
+    let (tx, rx) = smol::channel::unbounded::<ScanEvents>();
+    thread::spawn(move || {
+        let albums = collect_albums(paths, skip_tagged);
+        let total = albums.values().map(|a| a.len()).sum();
+        send(&tx, ScanEvents::Started(total));
+
+        let mut done = 0;
+        for paths in albums.values() {
+            let (results, errors) = scan_album(paths, |path| {
+                send(&tx, ScanEvents::Progress(done, total, path.to_path_buf()));
+                done += 1;
+            });
+            for (path, e) in errors {
+                send(&tx, ScanEvents::Failed(path, e.to_string()));
+            }
+            for r in results {
+                if write && let Err(e) = write_tags(&r) {
+                    send(&tx, ScanEvents::Failed(r.path.clone(), e.to_string()));
+                    continue;
+                }
+                send(&tx, ScanEvents::Scanned(r));
+            }
+        }
+        send(&tx, ScanEvents::Finished);
+    });
+    rx
+}
+
+/// Find musics in paths and group them into albums
+fn collect_albums(
+    paths: Vec<PathBuf>,
+    skip_tagged: bool,
+) -> BTreeMap<(PathBuf, String), Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found = Vec::new();
+            collect_files(&path, &mut found);
+            files.extend(found.into_iter().map(|(p, _)| p));
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files.dedup();
+
+    let mut albums: BTreeMap<(PathBuf, String), Vec<(PathBuf, bool)>> = BTreeMap::new();
+    for path in files {
+        let music = Music::from_path(&path).ok();
+        let album = music
+            .as_ref()
+            .and_then(|m| m.get_tags())
+            .and_then(|t| t.album().map(|a| a.to_string()))
+            .unwrap_or_default();
+        let tagged = music.is_some_and(|m| {
+            let rg = m.replay_gain();
+            rg.track_gain.is_some() && rg.album_gain.is_some()
+        });
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        albums.entry((dir, album)).or_default().push((path, tagged));
+    }
+
+    albums
+        .into_iter()
+        .filter(|(_, tracks)| !skip_tagged || tracks.iter().any(|(_, tagged)| !tagged))
+        .map(|(k, tracks)| (k, tracks.into_iter().map(|(p, _)| p).collect()))
+        .collect()
+}
+
+fn send(tx: &Sender<ScanEvents>, event: ScanEvents) {
+    if let Err(e) = tx.try_send(event) {
+        eprintln!("error when send event: {}", e);
+    }
+}
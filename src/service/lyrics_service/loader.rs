@@ -0,0 +1,80 @@
+use std::{fs, path::Path};
+
+use lofty::{
+    config::ParseOptions,
+    file::AudioFile,
+    id3::v2::{Frame, FrameFlags, FrameId, SynchronizedTextFrame, TimestampFormat},
+    mpeg::MpegFile,
+    tag::ItemKey,
+};
+
+use crate::service::{
+    lyrics_service::{
+        lrc,
+        models::{LyricLine, Lyrics},
+    },
+    music_service::music::Music,
+};
+
+/// Load lyrics of music, sidecar lrc file first, then embedded lyrics
+pub fn load(music: &Music) -> Option<Lyrics> {
+    load_sidecar(music.get_path())
+        .or_else(|| load_embedded(music))
+        .filter(|l| !l.is_empty())
+}
+
+/// Read song.lrc next to song file
+fn load_sidecar(path: &Path) -> Option<Lyrics> {
+    let data = fs::read(path.with_extension("lrc")).ok()?;
+    let text = String::from_utf8_lossy(&data);
+    Some(lrc::parse(text.trim_start_matches('\u{feff}')))
+}
+
+/// Read USLT / LYRICS tags, then SYLT frame of ID3v2
+fn load_embedded(music: &Music) -> Option<Lyrics> {
+    if let Some(text) = music
+        .get_tags()
+        .and_then(|t| t.get_string(&ItemKey::Lyrics))
+    {
+        let lyrics = lrc::parse(text);
+        if !lyrics.is_empty() {
+            return Some(lyrics);
+        }
+    }
+    let is_mp3 = music
+        .get_path()
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("mp3"));
+    if is_mp3 {
+        load_sylt(music.get_path())
+    } else {
+        None
+    }
+}
+
+/// Read synchronized lyrics frame of mp3
+fn load_sylt(path: &Path) -> Option<Lyrics> {
+    let mut file = fs::File::open(path).ok()?;
+    let mpeg = MpegFile::read_from(&mut file, ParseOptions::new()).ok()?;
+    let frame = mpeg.id3v2()?.get(&FrameId::new("SYLT").ok()?)?;
+    let Frame::Binary(binary) = frame else {
+        return None;
+    };
+    let sylt = SynchronizedTextFrame::parse(&binary.data, FrameFlags::default()).ok()?;
+    // timestamps of mpeg frames are not supported
+    if sylt.timestamp_format != TimestampFormat::MS {
+        return None;
+    }
+
+    let mut lines: Vec<LyricLine> = sylt
+        .content
+        .into_iter()
+        .map(|(ms, text)| LyricLine::new(ms as f64 / 1000.0, text.trim().to_string()))
+        .collect();
+    lines.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Some(Lyrics {
+        lines,
+        synced: true,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,183 @@
+use crate::service::lyrics_service::models::{LyricLine, LyricWord, Lyrics};
+
+/// Parse LRC text, including enhanced LRC with <mm:ss.xx> word timestamps.
+/// A line can have multiple timestamps, text without timestamp is parsed as unsynced lyrics.
+/// The second line with same timestamp is taken as translation of the first one.
+pub fn parse(text: &str) -> Lyrics {
+    let mut lyrics = Lyrics::default();
+    let mut plain = Vec::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        let mut times = Vec::new();
+        let mut is_meta = false;
+        let mut rest = line;
+        // read leading [..] tags
+        while let Some(tag) = rest.strip_prefix('[')
+            && let Some((inner, after)) = tag.split_once(']')
+        {
+            if let Some(t) = parse_timestamp(inner) {
+                times.push(t);
+            } else if times.is_empty() && parse_meta(inner, &mut lyrics) {
+                is_meta = true;
+            } else {
+                // text like "[Chorus]"
+                break;
+            }
+            rest = after;
+        }
+
+        if let Some(&first) = times.first() {
+            let (text, words, end) = parse_words(rest, first);
+            for time in times {
+                // word timestamps belong to the first time, shift them for repeated lines
+                let shift = time - first;
+                let mut line = LyricLine::new(time, text.clone());
+                line.words = words
+                    .iter()
+                    .map(|w| LyricWord {
+                        time: w.time + shift,
+                        text: w.text.clone(),
+                    })
+                    .collect();
+                line.end = end.map(|e| e + shift);
+                lyrics.lines.push(line);
+            }
+        } else if !is_meta {
+            plain.push(line.to_string());
+        }
+    }
+
+    if lyrics.lines.is_empty() {
+        // no timestamp, show as plain text
+        lyrics.lines = plain
+            .into_iter()
+            .map(|text| LyricLine::new(0.0, text))
+            .collect();
+        return lyrics;
+    }
+    lyrics.synced = true;
+    let offset = lyrics.offset as f64 / 1000.0;
+    for line in &mut lyrics.lines {
+        line.time = (line.time - offset).max(0.0);
+        for word in &mut line.words {
+            word.time = (word.time - offset).max(0.0);
+        }
+        line.end = line.end.map(|e| (e - offset).max(0.0));
+    }
+    // stable, keep file order of lines with same time
+    lyrics.lines.sort_by(|a, b| a.time.total_cmp(&b.time));
+    lyrics.lines = merge_translations(std::mem::take(&mut lyrics.lines));
+    lyrics
+}
+
+/// Split text of enhanced line into timed words.
+/// Return plain text, words and end time given by a trailing timestamp.
+fn parse_words(s: &str, line_time: f64) -> (String, Vec<LyricWord>, Option<f64>) {
+    let mut chunks = Vec::new();
+    let mut time = line_time;
+    let mut rest = s;
+    let mut found = false;
+    loop {
+        // find next <..> timestamp
+        let next = rest.find('<').and_then(|start| {
+            let close = start + rest[start..].find('>')?;
+            parse_timestamp(&rest[start + 1..close]).map(|t| (start, close, t))
+        });
+        let Some((start, close, t)) = next else {
+            chunks.push((time, rest));
+            break;
+        };
+        chunks.push((time, &rest[..start]));
+        time = t;
+        rest = &rest[close + 1..];
+        found = true;
+    }
+    if !found {
+        return (s.trim().to_string(), Vec::new(), None);
+    }
+
+    let text: String = chunks.iter().map(|(_, c)| *c).collect();
+    // a trailing timestamp without text ends the line
+    let end = match chunks.last() {
+        Some((t, c)) if c.trim().is_empty() => Some(*t),
+        _ => None,
+    };
+    if end.is_some() {
+        chunks.pop();
+    }
+    let words = chunks
+        .into_iter()
+        .filter(|(_, c)| !c.is_empty())
+        .map(|(time, c)| LyricWord {
+            time,
+            text: c.to_string(),
+        })
+        .collect();
+    (text.trim().to_string(), words, end)
+}
+
+/// Take the line following another with same time as its translation
+fn merge_translations(lines: Vec<LyricLine>) -> Vec<LyricLine> {
+    let mut merged: Vec<LyricLine> = Vec::with_capacity(lines.len());
+    for line in lines {
+        if let Some(last) = merged.last_mut()
+            && last.time == line.time
+            && last.translation.is_none()
+            && !line.text.is_empty()
+        {
+            last.translation = Some(line.text);
+            continue;
+        }
+        merged.push(line);
+    }
+    merged
+}
+
+/// Parse timestamp like "mm:ss", "mm:ss.xx", "mm:ss.xxx" or "mm:ss:xx" into seconds
+pub fn parse_timestamp(s: &str) -> Option<f64> {
+    let mut parts = s.trim().split(':');
+    let min = parts.next()?;
+    let sec = parts.next()?;
+    let (sec, frac) = match parts.next() {
+        Some(frac) => (sec, Some(frac)),
+        None => match sec.split_once('.') {
+            Some((sec, frac)) => (sec, Some(frac)),
+            None => (sec, None),
+        },
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !digits(min) || !digits(sec) {
+        return None;
+    }
+    let frac = match frac {
+        Some(f) if digits(f) => f.parse::<f64>().ok()? / 10f64.powi(f.len() as i32),
+        Some(_) => return None,
+        None => 0.0,
+    };
+    Some(min.parse::<f64>().ok()? * 60.0 + sec.parse::<f64>().ok()? + frac)
+}
+
+/// Parse metadata tag like "ti:Title", return false if not a metadata tag
+fn parse_meta(inner: &str, lyrics: &mut Lyrics) -> bool {
+    let Some((key, value)) = inner.split_once(':') else {
+        return false;
+    };
+    let value = value.trim().to_string();
+    match key.trim().to_lowercase().as_str() {
+        "ti" => lyrics.title = Some(value),
+        "ar" => lyrics.artist = Some(value),
+        "al" => lyrics.album = Some(value),
+        "offset" => lyrics.offset = value.trim_start_matches('+').parse().unwrap_or(0),
+        // other known tags are ignored
+        "by" | "au" | "length" | "re" | "ve" | "tool" | "#" => (),
+        _ => return false,
+    }
+    true
+}
@@ -0,0 +1,3 @@
+pub mod loader;
+pub mod lrc;
+pub mod models;
@@ -0,0 +1,90 @@
+/// A word of enhanced LRC line, time in seconds
+#[derive(Clone, Debug)]
+pub struct LyricWord {
+    pub time: f64,
+    pub text: String,
+}
+
+/// A line of lyrics, time in seconds
+#[derive(Clone, Debug)]
+pub struct LyricLine {
+    pub time: f64,
+    pub text: String,
+    // word timings, empty if line is not enhanced
+    pub words: Vec<LyricWord>,
+    // end time given by the last word timestamp
+    pub end: Option<f64>,
+    // translated line with same timestamp
+    pub translation: Option<String>,
+}
+
+impl LyricLine {
+    pub fn new(time: f64, text: String) -> Self {
+        Self {
+            time,
+            text,
+            words: Vec::new(),
+            end: None,
+            translation: None,
+        }
+    }
+
+    /// Sung ratio (0.0 - 1.0) of every word at time, line_end is used by the last word
+    pub fn word_progress(&self, time: f64, line_end: Option<f64>) -> Vec<f32> {
+        self.words
+            .iter()
+            .enumerate()
+            .map(|(ix, word)| {
+                let end = self
+                    .words
+                    .get(ix + 1)
+                    .map(|w| w.time)
+                    .or(self.end)
+                    .or(line_end);
+                match end {
+                    _ if time < word.time => 0.0,
+                    Some(end) if end > word.time => {
+                        ((time - word.time) / (end - word.time)).min(1.0) as f32
+                    }
+                    _ => 1.0,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Lyrics of a music, lines are sorted by time
+#[derive(Clone, Debug, Default)]
+pub struct Lyrics {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    // offset (ms) already applied to lines, positive shows lines earlier
+    pub offset: i64,
+    pub lines: Vec<LyricLine>,
+    // false if lines have no timestamp
+    pub synced: bool,
+}
+
+impl Lyrics {
+    /// Index of the line being sung at time (seconds)
+    pub fn index_at(&self, time: f64) -> Option<usize> {
+        if !self.synced {
+            return None;
+        }
+        // lines before or at time
+        let count = self.lines.partition_point(|l| l.time <= time);
+        count.checked_sub(1)
+    }
+
+    /// End time of line, the start of next line if not given
+    pub fn line_end(&self, index: usize) -> Option<f64> {
+        let line = self.lines.get(index)?;
+        line.end
+            .or_else(|| self.lines.get(index + 1).map(|l| l.time))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
@@ -1 +1,12 @@
+pub mod http_service;
+#[cfg(unix)]
+pub mod ipc_service;
+pub mod library_service;
+pub mod loudness_service;
+pub mod lyrics_service;
+pub mod mpd_service;
+#[cfg(target_os = "linux")]
+pub mod mpris_service;
 pub mod music_service;
+pub mod playlist_service;
+pub mod remote_service;
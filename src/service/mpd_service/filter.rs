@@ -0,0 +1,276 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::service::{mpd_service::protocol::MpdError, remote_service::models::SongInfo};
+
+/// Tags of song filters can test
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tag {
+    // any text tag or file
+    Any,
+    File,
+    // songs under a directory
+    Base,
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    Date,
+    Track,
+}
+
+impl Tag {
+    pub fn parse(name: &str) -> Option<Self> {
+        let tag = match name.to_ascii_lowercase().as_str() {
+            "any" => Tag::Any,
+            "file" => Tag::File,
+            "base" => Tag::Base,
+            "title" => Tag::Title,
+            "artist" => Tag::Artist,
+            "album" => Tag::Album,
+            "albumartist" => Tag::AlbumArtist,
+            "genre" => Tag::Genre,
+            "date" => Tag::Date,
+            "track" => Tag::Track,
+            _ => return None,
+        };
+        Some(tag)
+    }
+
+    /// Name of tag in responses
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tag::Any => "any",
+            Tag::File | Tag::Base => "file",
+            Tag::Title => "Title",
+            Tag::Artist => "Artist",
+            Tag::Album => "Album",
+            Tag::AlbumArtist => "AlbumArtist",
+            Tag::Genre => "Genre",
+            Tag::Date => "Date",
+            Tag::Track => "Track",
+        }
+    }
+
+    /// Value of tag in song, uri is the file told to clients
+    pub fn value(&self, song: &SongInfo, uri: &str) -> Option<String> {
+        match self {
+            Tag::Any => None,
+            Tag::File | Tag::Base => Some(uri.to_string()),
+            Tag::Title => song.title.clone(),
+            Tag::Artist => song.artist.clone(),
+            Tag::Album => song.album.clone(),
+            Tag::AlbumArtist => song.album_artist.clone(),
+            Tag::Genre => song.genre.clone(),
+            Tag::Date => song.year.map(|y| y.to_string()),
+            Tag::Track => song.number.map(|n| n.to_string()),
+        }
+    }
+}
+
+/// How value of tag is compared
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Op {
+    Equals,
+    NotEquals,
+    Contains,
+    StartsWith,
+}
+
+#[derive(Clone, Debug)]
+struct Condition {
+    tag: Tag,
+    op: Op,
+    value: String,
+    // compare ignoring case, values are lowercase then
+    fold_case: bool,
+}
+
+impl Condition {
+    fn new(tag: Tag, op: Op, value: String, fold_case: bool) -> Self {
+        let value = if fold_case {
+            value.to_lowercase()
+        } else {
+            value
+        };
+        Self {
+            tag,
+            op,
+            value,
+            fold_case,
+        }
+    }
+
+    fn matches(&self, song: &SongInfo, uri: &str) -> bool {
+        if self.tag == Tag::Base {
+            let base = self.value.trim_end_matches('/');
+            return base.is_empty()
+                || uri == base
+                || uri.strip_prefix(base).is_some_and(|r| r.starts_with('/'));
+        }
+        if self.tag == Tag::Any {
+            let tags = [
+                Tag::File,
+                Tag::Title,
+                Tag::Artist,
+                Tag::Album,
+                Tag::AlbumArtist,
+                Tag::Genre,
+            ];
+            let found = tags
+                .iter()
+                .any(|t| t.value(song, uri).is_some_and(|v| self.test(&v)));
+            return found != (self.op == Op::NotEquals);
+        }
+        match self.tag.value(song, uri) {
+            Some(v) => self.test(&v) != (self.op == Op::NotEquals),
+            None => self.op == Op::NotEquals,
+        }
+    }
+
+    /// Test value against condition, not equals tests as equals
+    fn test(&self, value: &str) -> bool {
+        let value = if self.fold_case {
+            value.to_lowercase()
+        } else {
+            value.to_string()
+        };
+        match self.op {
+            Op::Equals | Op::NotEquals => value == self.value,
+            Op::Contains => value.contains(&self.value),
+            Op::StartsWith => value.starts_with(&self.value),
+        }
+    }
+}
+
+/// Filter of songs, matches songs passing all of its conditions
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    conditions: Vec<Condition>,
+}
+
+impl Filter {
+    /// Parse arguments of `find` and `search` like commands.
+    /// Arguments are an expression like `(artist == 'x')`, or pairs of tag and value.
+    /// Pairs of `search` match parts ignoring case, pairs of `find` match exactly
+    pub fn parse(args: &[String], fold_case: bool) -> Result<Self, MpdError> {
+        match args {
+            [] => Err(MpdError::arg("too few arguments")),
+            [expression, ..] if expression.starts_with('(') => {
+                let mut conditions = Vec::new();
+                let mut chars = expression.chars().peekable();
+                parse_expression(&mut chars, &mut conditions)?;
+                skip_spaces(&mut chars);
+                if chars.next().is_some() {
+                    return Err(MpdError::arg("unexpected text after expression"));
+                }
+                Ok(Self { conditions })
+            }
+            pairs => {
+                if pairs.len() % 2 != 0 {
+                    return Err(MpdError::arg("missing value of filter"));
+                }
+                let op = if fold_case { Op::Contains } else { Op::Equals };
+                let conditions = pairs
+                    .chunks(2)
+                    .map(|pair| {
+                        let tag = Tag::parse(&pair[0])
+                            .ok_or_else(|| MpdError::arg(format!("Unknown tag: {}", pair[0])))?;
+                        Ok(Condition::new(tag, op, pair[1].clone(), fold_case))
+                    })
+                    .collect::<Result<_, MpdError>>()?;
+                Ok(Self { conditions })
+            }
+        }
+    }
+
+    pub fn matches(&self, song: &SongInfo, uri: &str) -> bool {
+        self.conditions.iter().all(|c| c.matches(song, uri))
+    }
+}
+
+/// Parse expression in parentheses, conditions joined by AND are all pushed
+fn parse_expression(
+    chars: &mut Peekable<Chars>,
+    conditions: &mut Vec<Condition>,
+) -> Result<(), MpdError> {
+    skip_spaces(chars);
+    if chars.next() != Some('(') {
+        return Err(MpdError::arg("'(' expected"));
+    }
+    skip_spaces(chars);
+    if chars.peek() == Some(&'(') {
+        loop {
+            parse_expression(chars, conditions)?;
+            skip_spaces(chars);
+            if chars.peek() == Some(&')') {
+                chars.next();
+                return Ok(());
+            }
+            if read_word(chars) != "AND" {
+                return Err(MpdError::arg("AND expected"));
+            }
+        }
+    }
+    let name = read_word(chars);
+    let tag = Tag::parse(&name).ok_or_else(|| MpdError::arg(format!("Unknown tag: {}", name)))?;
+    skip_spaces(chars);
+    let op = match read_word(chars).as_str() {
+        "==" => Op::Equals,
+        "!=" => Op::NotEquals,
+        "contains" => Op::Contains,
+        "starts_with" => Op::StartsWith,
+        op => return Err(MpdError::arg(format!("Unknown operator: {}", op))),
+    };
+    skip_spaces(chars);
+    let value = read_quoted(chars)?;
+    skip_spaces(chars);
+    if chars.next() != Some(')') {
+        return Err(MpdError::arg("')' expected"));
+    }
+    // clients write exact tests, but tags typed by people rarely match by case
+    let fold_case = matches!(op, Op::Contains | Op::StartsWith);
+    conditions.push(Condition::new(tag, op, value, fold_case));
+    Ok(())
+}
+
+fn skip_spaces(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Read word until space or parenthesis
+fn read_word(chars: &mut Peekable<Chars>) -> String {
+    skip_spaces(chars);
+    let mut word = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == '(' || c == ')' || c == '\'' || c == '"' {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+/// Read value in single or double quotes, backslash escapes the next char
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String, MpdError> {
+    let quote = match chars.next() {
+        Some(q @ ('\'' | '"')) => q,
+        _ => return Err(MpdError::arg("quoted value expected")),
+    };
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => match chars.next() {
+                Some(c) => value.push(c),
+                None => break,
+            },
+            Some(c) if c == quote => return Ok(value),
+            Some(c) => value.push(c),
+            None => break,
+        }
+    }
+    Err(MpdError::arg("unterminated quote"))
+}
@@ -0,0 +1,4 @@
+pub mod filter;
+pub mod protocol;
+pub mod server;
+pub mod session;
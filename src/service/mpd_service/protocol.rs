@@ -0,0 +1,278 @@
+use std::path::{Path, PathBuf};
+
+use crate::service::{playlist_service::uri, remote_service::models::SongInfo};
+
+// version of protocol told to clients
+pub static PROTOCOL_VERSION: &str = "0.23.0";
+
+// error codes of ACK responses
+pub static ACK_ARG: u32 = 2;
+pub static ACK_UNKNOWN: u32 = 5;
+pub static ACK_NO_EXIST: u32 = 50;
+pub static ACK_SYSTEM: u32 = 52;
+
+/// Error of a command, sent to client as ACK line
+#[derive(Debug)]
+pub struct MpdError {
+    pub code: u32,
+    pub message: String,
+}
+
+impl MpdError {
+    pub fn new(code: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn arg(message: impl Into<String>) -> Self {
+        Self::new(ACK_ARG, message)
+    }
+
+    /// ACK line of error, index is the position of command in command list
+    pub fn ack(&self, index: usize, command: &str) -> String {
+        format!(
+            "ACK [{}@{}] {{{}}} {}\n",
+            self.code, index, command, self.message
+        )
+    }
+}
+
+/// Split command line into arguments.
+/// Quoted arguments may contain spaces, backslash escapes quote and backslash in them
+pub fn tokenize(line: &str) -> Result<Vec<String>, MpdError> {
+    let mut args = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut arg = String::new();
+        if c == '"' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(escaped) => arg.push(escaped),
+                        None => return Err(MpdError::arg("unterminated quote")),
+                    },
+                    Some(c) => arg.push(c),
+                    None => return Err(MpdError::arg("unterminated quote")),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                arg.push(c);
+                chars.next();
+            }
+        }
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+/// Parse integer argument
+pub fn parse_number<T: std::str::FromStr>(arg: &str) -> Result<T, MpdError> {
+    arg.parse()
+        .map_err(|_| MpdError::arg(format!("Integer expected: {}", arg)))
+}
+
+/// Parse seconds argument like `90` or `90.5`
+pub fn parse_secs(arg: &str) -> Result<f64, MpdError> {
+    arg.parse::<f64>()
+        .ok()
+        .filter(|s| s.is_finite())
+        .ok_or_else(|| MpdError::arg(format!("Number expected: {}", arg)))
+}
+
+/// Parse range argument like `3` or `2:5`, end is exclusive and may be omitted
+pub fn parse_range(arg: &str, len: usize) -> Result<(usize, usize), MpdError> {
+    match arg.split_once(':') {
+        Some((start, "")) => Ok((parse_number(start)?, len)),
+        Some((start, end)) => Ok((parse_number(start)?, parse_number(end)?)),
+        None => {
+            let index: usize = parse_number(arg)?;
+            Ok((index, index + 1))
+        }
+    }
+}
+
+/// URI of file told to clients, relative to the library folder with its folder name.
+/// Files outside library are told by absolute path
+pub fn uri_of(path: &Path, folders: &[PathBuf]) -> String {
+    for folder in folders {
+        if let Ok(relative) = path.strip_prefix(folder)
+            && let Some(name) = folder.file_name()
+        {
+            let uri = Path::new(name).join(relative);
+            return uri.to_string_lossy().replace('\\', "/");
+        }
+    }
+    path.to_string_lossy().to_string()
+}
+
+/// Path of URI given by clients, relative ones start with name of a library folder.
+/// None if it does not exist or is outside library folders, like by `..` or links
+pub fn path_of(location: &str, folders: &[PathBuf]) -> Option<PathBuf> {
+    let is_uri = location
+        .get(..7)
+        .is_some_and(|head| head.eq_ignore_ascii_case("file://"));
+    let path = if is_uri {
+        uri::to_path(location)
+    } else if Path::new(location).is_absolute() {
+        PathBuf::from(location)
+    } else {
+        let location = location.trim_matches('/');
+        let (name, rest) = location.split_once('/').unwrap_or((location, ""));
+        let folder = folders
+            .iter()
+            .find(|f| f.file_name().is_some_and(|n| n.to_string_lossy() == name))?;
+        folder.join(rest)
+    };
+    let path = path.canonicalize().ok()?;
+    // path is given under the folder as library has it, which may be a link itself
+    folders.iter().find_map(|folder| {
+        let relative = path.strip_prefix(folder.canonicalize().ok()?).ok()?;
+        Some(if relative.as_os_str().is_empty() {
+            folder.clone()
+        } else {
+            folder.join(relative)
+        })
+    })
+}
+
+/// Write song as lines of its tags
+pub fn write_song(out: &mut String, song: &SongInfo, folders: &[PathBuf]) {
+    out.push_str(&format!("file: {}\n", uri_of(&song.path, folders)));
+    let tags = [
+        ("Title", song.title.as_ref()),
+        ("Artist", song.artist.as_ref()),
+        ("Album", song.album.as_ref()),
+        ("AlbumArtist", song.album_artist.as_ref()),
+        ("Genre", song.genre.as_ref()),
+    ];
+    for (key, value) in tags {
+        if let Some(value) = value {
+            out.push_str(&format!("{}: {}\n", key, one_line(value)));
+        }
+    }
+    if let Some(year) = song.year {
+        out.push_str(&format!("Date: {}\n", year));
+    }
+    if let Some(number) = song.number {
+        out.push_str(&format!("Track: {}\n", number));
+    }
+    if song.duration > 0.0 {
+        out.push_str(&format!("Time: {}\n", song.duration.round() as u64));
+        out.push_str(&format!("duration: {:.3}\n", song.duration));
+    }
+}
+
+/// Write song of queue with its position and id
+pub fn write_queue_song(
+    out: &mut String,
+    song: &SongInfo,
+    index: usize,
+    id: u64,
+    folders: &[PathBuf],
+) {
+    write_song(out, song, folders);
+    out.push_str(&format!("Pos: {}\nId: {}\n", index, id));
+}
+
+/// Values are single lines, the protocol has no escape for line breaks
+fn one_line(s: &str) -> String {
+    s.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_dir::TempDir;
+
+    /// Library folder `music` with a song, and a folder outside of library
+    struct Fixture {
+        root: TempDir,
+        folder: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root = TempDir::new(name);
+            let folder = root.join("music");
+            root.file("music/album/a.flac");
+            root.file("private/secret.flac");
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(root.join("private"), folder.join("link")).unwrap();
+            Self { root, folder }
+        }
+
+        fn folders(&self) -> Vec<PathBuf> {
+            vec![self.folder.clone()]
+        }
+    }
+
+    #[test]
+    fn tokenize_splits_quoted_arguments() {
+        let args = tokenize(r#"find "album" "Best \"of\" 2\\3"  title x"#).unwrap();
+        assert_eq!(args, ["find", "album", r#"Best "of" 2\3"#, "title", "x"]);
+        assert!(tokenize(r#"add "music/a"#).is_err());
+        assert!(tokenize("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_range_gives_exclusive_end() {
+        assert_eq!(parse_range("3", 10).unwrap(), (3, 4));
+        assert_eq!(parse_range("2:5", 10).unwrap(), (2, 5));
+        assert_eq!(parse_range("4:", 10).unwrap(), (4, 10));
+        assert!(parse_range("a:2", 10).is_err());
+    }
+
+    #[test]
+    fn path_of_resolves_library_uris() {
+        let fixture = Fixture::new("protocol_resolve");
+        let folders = fixture.folders();
+        let song = fixture.folder.join("album/a.flac");
+        assert_eq!(path_of("music/album/a.flac", &folders), Some(song.clone()));
+        assert_eq!(path_of("music", &folders), Some(fixture.folder.clone()));
+        assert_eq!(
+            path_of("music/album/", &folders),
+            Some(fixture.folder.join("album"))
+        );
+        // absolute paths and file URIs are accepted inside library
+        let absolute = song.to_string_lossy().to_string();
+        assert_eq!(path_of(&absolute, &folders), Some(song.clone()));
+        assert_eq!(
+            path_of(&uri::from_path(&song), &folders),
+            Some(song.clone())
+        );
+        assert_eq!(uri_of(&song, &folders), "music/album/a.flac");
+    }
+
+    #[test]
+    fn path_of_rejects_paths_outside_library() {
+        let fixture = Fixture::new("protocol_reject");
+        let folders = fixture.folders();
+        let secret = fixture.root.join("private/secret.flac");
+        let rejected = [
+            "music/../private/secret.flac".to_string(),
+            "music/album/../../private".to_string(),
+            "private/secret.flac".to_string(),
+            "music/missing.flac".to_string(),
+            secret.to_string_lossy().to_string(),
+            uri::from_path(&secret),
+            uri::from_path(&fixture.folder.join("../private/secret.flac")),
+        ];
+        for location in rejected {
+            assert_eq!(path_of(&location, &folders), None, "{}", location);
+        }
+        #[cfg(unix)]
+        assert_eq!(path_of("music/link/secret.flac", &folders), None);
+    }
+}
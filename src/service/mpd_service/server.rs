@@ -0,0 +1,167 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+use smol::{
+    channel::{Receiver, Sender},
+    net::TcpListener,
+};
+
+use crate::service::{
+    mpd_service::{
+        protocol::{ACK_SYSTEM, MpdError},
+        session,
+    },
+    music_service::models::PlayState,
+    remote_service::{
+        client::RemoteClient,
+        models::{LibrarySnapshot, PlayerStatus, QueueSong, RemoteCommand},
+    },
+};
+
+/// State shared by connections of server
+pub struct Shared {
+    client: RemoteClient,
+    // last status published by app
+    status: Mutex<Option<PlayerStatus>>,
+    // channels of connections, changed subsystems are sent to them
+    clients: Mutex<Vec<Sender<&'static str>>>,
+    started: Instant,
+}
+
+impl Shared {
+    pub fn new(commands: Sender<RemoteCommand>) -> Self {
+        Self {
+            client: RemoteClient::new(commands),
+            status: Mutex::new(None),
+            clients: Mutex::new(Vec::new()),
+            started: Instant::now(),
+        }
+    }
+
+    /// Send command to app
+    pub fn send(&self, command: RemoteCommand) -> Result<(), MpdError> {
+        self.client.send(command).map_err(system_error)
+    }
+
+    pub fn status(&self) -> Option<PlayerStatus> {
+        self.status.lock().ok().and_then(|s| s.clone())
+    }
+
+    /// Get songs of queue from app
+    pub async fn queue(&self) -> Result<Vec<QueueSong>, MpdError> {
+        self.client.queue().await.map_err(system_error)
+    }
+
+    /// Get library from app
+    pub async fn library(&self) -> Result<LibrarySnapshot, MpdError> {
+        self.client.library().await.map_err(system_error)
+    }
+
+    /// Register a connection, it receives names of changed subsystems
+    pub fn register(&self) -> Receiver<&'static str> {
+        let (sender, receiver) = smol::channel::unbounded();
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.push(sender);
+        }
+        receiver
+    }
+
+    /// Seconds since server started
+    pub fn uptime(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// Keep status and notify connections of subsystems changed by it
+    pub fn update(&self, status: PlayerStatus, elapsed: f64) {
+        let Ok(mut last) = self.status.lock() else {
+            return;
+        };
+        let changes = changed_subsystems(last.as_ref(), &status, elapsed);
+        *last = Some(status);
+        drop(last);
+        if changes.is_empty() {
+            return;
+        }
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain(|c| changes.iter().all(|s| c.try_send(s).is_ok()));
+        }
+    }
+}
+
+/// Serve MPD protocol on address in a thread.
+/// Commands of clients are sent to app, statuses published by app are told to them
+pub fn start(address: String, commands: Sender<RemoteCommand>, statuses: Receiver<PlayerStatus>) {
+    thread::spawn(move || {
+        smol::block_on(async move {
+            let listener = match TcpListener::bind(&address).await {
+                Ok(l) => l,
+                Err(e) => {
+                    eprintln!("failed to start mpd server on {}: {}", address, e);
+                    return;
+                }
+            };
+            let shared = Arc::new(Shared::new(commands));
+            smol::spawn(watch_status(shared.clone(), statuses)).detach();
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let shared = shared.clone();
+                        smol::spawn(async move {
+                            if let Err(e) = session::serve(stream, shared).await {
+                                eprintln!("error in mpd connection: {}", e);
+                            }
+                        })
+                        .detach();
+                    }
+                    Err(e) => eprintln!("error when accept mpd connection: {}", e),
+                }
+            }
+        })
+    });
+}
+
+fn system_error(e: anyhow::Error) -> MpdError {
+    MpdError::new(ACK_SYSTEM, e.to_string())
+}
+
+/// Take every status published by app until it is closed
+async fn watch_status(shared: Arc<Shared>, statuses: Receiver<PlayerStatus>) {
+    let mut updated = Instant::now();
+    while let Ok(status) = statuses.recv().await {
+        let elapsed = updated.elapsed().as_secs_f64();
+        updated = Instant::now();
+        shared.update(status, elapsed);
+    }
+}
+
+/// Subsystems changed from old status to new one.
+/// Elapsed is the seconds between them, to find out jumps of position
+fn changed_subsystems(
+    old: Option<&PlayerStatus>,
+    new: &PlayerStatus,
+    elapsed: f64,
+) -> Vec<&'static str> {
+    let Some(old) = old else {
+        return vec!["player", "mixer", "playlist"];
+    };
+    let mut changes = Vec::new();
+    let played = if old.state == PlayState::Playing {
+        elapsed
+    } else {
+        0.0
+    };
+    let jumped = (new.position - old.position - played).abs() > 2.0;
+    if old.state != new.state || old.index != new.index || old.song != new.song || jumped {
+        changes.push("player");
+    }
+    if old.volume != new.volume {
+        changes.push("mixer");
+    }
+    if old.queue_version != new.queue_version {
+        changes.push("playlist");
+    }
+    changes
+}
@@ -0,0 +1,838 @@
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use smol::{
+    channel::Receiver,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    stream::StreamExt,
+};
+
+use crate::service::{
+    mpd_service::{
+        filter::{Filter, Tag},
+        protocol::{
+            ACK_NO_EXIST, ACK_UNKNOWN, MpdError, PROTOCOL_VERSION, parse_number, parse_range,
+            parse_secs, path_of, tokenize, uri_of, write_queue_song, write_song,
+        },
+        server::Shared,
+    },
+    music_service::models::{MUSIC_EXTENSIONS, PlayState},
+    remote_service::models::{LibrarySnapshot, QueueSong, RemoteCommand, SongInfo},
+};
+
+static COMMANDS: &[&str] = &[
+    "add",
+    "addid",
+    "clear",
+    "close",
+    "commands",
+    "currentsong",
+    "delete",
+    "deleteid",
+    "find",
+    "findadd",
+    "idle",
+    "list",
+    "lsinfo",
+    "move",
+    "moveid",
+    "next",
+    "noidle",
+    "notcommands",
+    "outputs",
+    "pause",
+    "ping",
+    "play",
+    "playid",
+    "playlistid",
+    "playlistinfo",
+    "plchanges",
+    "plchangesposid",
+    "previous",
+    "replay_gain_status",
+    "search",
+    "searchadd",
+    "seek",
+    "seekcur",
+    "seekid",
+    "setvol",
+    "stats",
+    "status",
+    "stop",
+    "tagtypes",
+    "urlhandlers",
+    "volume",
+];
+
+static TAG_TYPES: &[Tag] = &[
+    Tag::Artist,
+    Tag::Album,
+    Tag::AlbumArtist,
+    Tag::Title,
+    Tag::Track,
+    Tag::Genre,
+    Tag::Date,
+];
+
+/// What to do with connection after a command
+enum Flow {
+    Continue,
+    Close,
+}
+
+/// What ended waiting of idle
+enum Wake {
+    Change(&'static str),
+    Line(Option<String>),
+}
+
+/// A client connected to server
+struct Session {
+    shared: Arc<Shared>,
+    // subsystems changed since the last idle
+    changes: Receiver<&'static str>,
+    pending: Vec<&'static str>,
+}
+
+/// Talk with client until it closes connection
+pub async fn serve(stream: TcpStream, shared: Arc<Shared>) -> io::Result<()> {
+    let mut writer = stream.clone();
+    let (lines, _reader) = read_lines(stream);
+    let mut session = Session {
+        changes: shared.register(),
+        shared,
+        pending: Vec::new(),
+    };
+    writer
+        .write_all(format!("OK MPD {}\n", PROTOCOL_VERSION).as_bytes())
+        .await?;
+    // commands of list being received, and whether each of them is answered
+    let mut list: Option<(Vec<String>, bool)> = None;
+    while let Ok(line) = lines.recv().await {
+        let line = line.trim_end_matches('\r').to_string();
+        if let Some((commands, ok_each)) = &mut list {
+            if line.trim() != "command_list_end" {
+                commands.push(line);
+                continue;
+            }
+            let ok_each = *ok_each;
+            let commands = list.take().map(|(c, _)| c).unwrap_or_default();
+            let (reply, flow) = session.run(&commands, ok_each).await;
+            writer.write_all(reply.as_bytes()).await?;
+            if let Flow::Close = flow {
+                return Ok(());
+            }
+            continue;
+        }
+        match line.trim() {
+            "command_list_begin" => list = Some((Vec::new(), false)),
+            "command_list_ok_begin" => list = Some((Vec::new(), true)),
+            _ => {
+                let reply = match tokenize(&line) {
+                    Ok(args) if args.first().map(String::as_str) == Some("idle") => {
+                        match session.idle(&args[1..], &lines).await {
+                            Some(reply) => reply,
+                            None => return Ok(()),
+                        }
+                    }
+                    Ok(args) if args.first().map(String::as_str) == Some("noidle") => {
+                        "OK\n".to_string()
+                    }
+                    _ => {
+                        let (reply, flow) = session.run(&[line], false).await;
+                        if let Flow::Close = flow {
+                            return Ok(());
+                        }
+                        reply
+                    }
+                };
+                writer.write_all(reply.as_bytes()).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read lines of stream in a task, lines are received while waiting for changes
+fn read_lines(stream: TcpStream) -> (Receiver<String>, smol::Task<()>) {
+    let (sender, receiver) = smol::channel::unbounded();
+    let task = smol::spawn(async move {
+        let mut lines = BufReader::new(stream).lines();
+        while let Some(Ok(line)) = lines.next().await {
+            if sender.send(line).await.is_err() {
+                break;
+            }
+        }
+    });
+    (receiver, task)
+}
+
+impl Session {
+    /// Run commands in order until one fails, return reply of them
+    async fn run(&mut self, lines: &[String], ok_each: bool) -> (String, Flow) {
+        let mut out = String::new();
+        for (index, line) in lines.iter().enumerate() {
+            let args = match tokenize(line) {
+                Ok(args) if !args.is_empty() => args,
+                Ok(_) => {
+                    out.push_str(&MpdError::new(ACK_UNKNOWN, "No command given").ack(index, ""));
+                    return (out, Flow::Continue);
+                }
+                Err(e) => {
+                    out.push_str(&e.ack(index, ""));
+                    return (out, Flow::Continue);
+                }
+            };
+            match self.execute(&args, &mut out).await {
+                Ok(Flow::Close) => return (out, Flow::Close),
+                Ok(Flow::Continue) if ok_each => out.push_str("list_OK\n"),
+                Ok(Flow::Continue) => {}
+                Err(e) => {
+                    out.push_str(&e.ack(index, &args[0]));
+                    return (out, Flow::Continue);
+                }
+            }
+        }
+        out.push_str("OK\n");
+        (out, Flow::Continue)
+    }
+
+    /// Wait for changes of subsystems, any subsystem if none given.
+    /// Return None if client sent other command than `noidle`, the connection is closed then
+    async fn idle(&mut self, subsystems: &[String], lines: &Receiver<String>) -> Option<String> {
+        let wanted =
+            |s: &str| subsystems.is_empty() || subsystems.iter().any(|w| w.eq_ignore_ascii_case(s));
+        loop {
+            while let Ok(change) = self.changes.try_recv() {
+                self.push_change(change);
+            }
+            let changed: Vec<_> = self.pending.iter().copied().filter(|s| wanted(s)).collect();
+            if !changed.is_empty() {
+                self.pending.retain(|s| !changed.contains(s));
+                let mut out = String::new();
+                for subsystem in changed {
+                    out.push_str(&format!("changed: {}\n", subsystem));
+                }
+                out.push_str("OK\n");
+                return Some(out);
+            }
+            let change = async {
+                match self.changes.recv().await {
+                    Ok(change) => Wake::Change(change),
+                    // server is closed, wait for client only
+                    Err(_) => smol::future::pending().await,
+                }
+            };
+            let line = async { Wake::Line(lines.recv().await.ok()) };
+            match smol::future::or(change, line).await {
+                Wake::Change(change) => self.push_change(change),
+                Wake::Line(Some(line)) if line.trim() == "noidle" => {
+                    return Some("OK\n".to_string());
+                }
+                Wake::Line(_) => return None,
+            }
+        }
+    }
+
+    fn push_change(&mut self, change: &'static str) {
+        if !self.pending.contains(&change) {
+            self.pending.push(change);
+        }
+    }
+
+    /// Execute command, write its response without the final OK
+    async fn execute(&mut self, args: &[String], out: &mut String) -> Result<Flow, MpdError> {
+        let (command, args) = (args[0].as_str(), &args[1..]);
+        match command {
+            "ping" => {}
+            "close" => return Ok(Flow::Close),
+            "status" => self.write_status(out),
+            "currentsong" => {
+                if let Some(status) = self.shared.status()
+                    && let (Some(index), Some(id), Some(song)) =
+                        (status.index, status.song_id, &status.song)
+                {
+                    let snapshot = self.shared.library().await?;
+                    write_queue_song(out, song, index, id, &snapshot.folders);
+                }
+            }
+            "stats" => {
+                let snapshot = self.shared.library().await?;
+                let library = &snapshot.library;
+                let playtime: f64 = library.tracks().iter().map(|t| t.duration).sum();
+                out.push_str(&format!(
+                    "artists: {}\nalbums: {}\nsongs: {}\nuptime: {}\ndb_playtime: {}\n",
+                    library.artists().len(),
+                    library.albums().len(),
+                    library.tracks().len(),
+                    self.shared.uptime(),
+                    playtime.round() as u64,
+                ));
+            }
+            "play" => match args.first() {
+                Some(index) => self
+                    .shared
+                    .send(RemoteCommand::PlayAt(parse_number(index)?))?,
+                None => self.shared.send(RemoteCommand::Play)?,
+            },
+            "playid" => match args.first() {
+                Some(id) => {
+                    let index = index_of_id(&self.shared.queue().await?, id)?;
+                    self.shared.send(RemoteCommand::PlayAt(index))?;
+                }
+                None => self.shared.send(RemoteCommand::Play)?,
+            },
+            "pause" => {
+                let command = match args.first().map(String::as_str) {
+                    None => RemoteCommand::PlayPause,
+                    Some("1") => RemoteCommand::Pause,
+                    Some("0") => RemoteCommand::Play,
+                    Some(arg) => return Err(MpdError::arg(format!("Boolean expected: {}", arg))),
+                };
+                self.shared.send(command)?;
+            }
+            "stop" => self.shared.send(RemoteCommand::Stop)?,
+            "next" => self.shared.send(RemoteCommand::Next)?,
+            "previous" => self.shared.send(RemoteCommand::Previous)?,
+            "seekcur" => {
+                let time = required(args, 0)?;
+                let secs = parse_secs(time)?;
+                let command = if time.starts_with(['+', '-']) {
+                    RemoteCommand::SeekBy(secs)
+                } else {
+                    RemoteCommand::SeekTo(secs)
+                };
+                self.shared.send(command)?;
+            }
+            "seek" | "seekid" => {
+                let index = if command == "seekid" {
+                    index_of_id(&self.shared.queue().await?, required(args, 0)?)?
+                } else {
+                    parse_number(required(args, 0)?)?
+                };
+                let secs = parse_secs(required(args, 1)?)?;
+                if self.shared.status().and_then(|s| s.index) != Some(index) {
+                    self.shared.send(RemoteCommand::PlayAt(index))?;
+                }
+                self.shared.send(RemoteCommand::SeekTo(secs))?;
+            }
+            "setvol" => {
+                let volume: u32 = parse_number(required(args, 0)?)?;
+                self.shared
+                    .send(RemoteCommand::SetVolume(volume.min(100) as f32 / 100.0))?;
+            }
+            "volume" => {
+                let change: i32 = parse_number(required(args, 0)?)?;
+                let volume = self.shared.status().map_or(1.0, |s| s.volume);
+                let volume = (volume + change as f32 / 100.0).clamp(0.0, 1.0);
+                self.shared.send(RemoteCommand::SetVolume(volume))?;
+            }
+            "add" => {
+                let snapshot = self.shared.library().await?;
+                let paths = songs_at(required(args, 0)?, &snapshot)?;
+                self.shared.send(RemoteCommand::Enqueue(paths))?;
+            }
+            "addid" => {
+                if args.len() > 1 {
+                    return Err(MpdError::arg("adding at position is not supported"));
+                }
+                let snapshot = self.shared.library().await?;
+                let path = path_of(required(args, 0)?, &snapshot.folders)
+                    .filter(|p| p.is_file())
+                    .ok_or_else(|| MpdError::new(ACK_NO_EXIST, "No such song"))?;
+                let len = self.shared.queue().await?.len();
+                self.shared.send(RemoteCommand::Enqueue(vec![path]))?;
+                // commands are applied in order, so queue asked after has the song
+                let id = self.shared.queue().await?.get(len).map(|s| s.id);
+                let id = id.ok_or_else(|| MpdError::new(ACK_NO_EXIST, "No such song"))?;
+                out.push_str(&format!("Id: {}\n", id));
+            }
+            "clear" => self.shared.send(RemoteCommand::Clear)?,
+            "deleteid" => {
+                let index = index_of_id(&self.shared.queue().await?, required(args, 0)?)?;
+                self.shared.send(RemoteCommand::Remove(index))?;
+            }
+            "move" | "moveid" => {
+                let queue = self.shared.queue().await?;
+                let from = if command == "moveid" {
+                    index_of_id(&queue, required(args, 0)?)?
+                } else {
+                    parse_number(required(args, 0)?)?
+                };
+                let to: usize = parse_number(required(args, 1)?)?;
+                if from >= queue.len() || to >= queue.len() {
+                    return Err(MpdError::arg("Bad song index"));
+                }
+                self.shared.send(RemoteCommand::Move(from, to))?;
+            }
+            "delete" => {
+                let len = self.shared.queue().await?.len();
+                let (start, end) = parse_range(required(args, 0)?, len)?;
+                if start >= end || end > len {
+                    return Err(MpdError::arg("Bad song index"));
+                }
+                // remove from the end, indexes before it stay the same
+                for index in (start..end).rev() {
+                    self.shared.send(RemoteCommand::Remove(index))?;
+                }
+            }
+            "playlistinfo" | "playlistid" | "plchanges" | "plchangesposid" => {
+                let queue = self.shared.queue().await?;
+                // songs added or moved after the version the client has
+                let since: Option<u64> = match command {
+                    "plchanges" | "plchangesposid" => Some(parse_number(required(args, 0)?)?),
+                    _ => None,
+                };
+                let (start, end) = match (command, args) {
+                    ("playlistid", [id, ..]) => {
+                        let index = index_of_id(&queue, id)?;
+                        (index, index + 1)
+                    }
+                    ("playlistinfo", [range, ..])
+                    | ("plchanges" | "plchangesposid", [_, range]) => {
+                        parse_range(range, queue.len())?
+                    }
+                    _ => (0, queue.len()),
+                };
+                if start > end || end > queue.len() {
+                    return Err(MpdError::arg("Bad song index"));
+                }
+                let snapshot = self.shared.library().await?;
+                let songs = queue
+                    .iter()
+                    .enumerate()
+                    .take(end)
+                    .skip(start)
+                    .filter(|(_, song)| since.is_none_or(|v| song.changed > v));
+                for (index, song) in songs {
+                    if command == "plchangesposid" {
+                        out.push_str(&format!("cpos: {}\nId: {}\n", index, song.id));
+                    } else {
+                        write_queue_song(out, &song.song, index, song.id, &snapshot.folders);
+                    }
+                }
+            }
+            "lsinfo" => {
+                let snapshot = self.shared.library().await?;
+                lsinfo(args.first().map_or("", String::as_str), &snapshot, out)?;
+            }
+            "find" | "search" | "findadd" | "searchadd" => {
+                let (args, window) = split_window(args);
+                let filter = Filter::parse(args, command.starts_with("search"))?;
+                let snapshot = self.shared.library().await?;
+                let found = find(&filter, &snapshot);
+                let (start, end) = match window {
+                    Some(range) => parse_range(range, found.len())?,
+                    None => (0, found.len()),
+                };
+                let found = found.into_iter().take(end).skip(start);
+                if command.ends_with("add") {
+                    let paths = found.map(|(song, _)| song.path).collect();
+                    self.shared.send(RemoteCommand::Enqueue(paths))?;
+                } else {
+                    for (song, _) in found {
+                        write_song(out, &song, &snapshot.folders);
+                    }
+                }
+            }
+            "list" => {
+                let name = required(args, 0)?;
+                let tag = Tag::parse(name)
+                    .filter(|t| !matches!(t, Tag::Any | Tag::Base))
+                    .ok_or_else(|| MpdError::arg(format!("Unknown tag: {}", name)))?;
+                let filter = match &args[1..] {
+                    [] => Filter::default(),
+                    // old clients give artist of albums
+                    [artist] if tag == Tag::Album && !artist.starts_with('(') => {
+                        Filter::parse(&["artist".to_string(), artist.clone()], false)?
+                    }
+                    filter => Filter::parse(filter, false)?,
+                };
+                let snapshot = self.shared.library().await?;
+                let values: BTreeSet<String> = find(&filter, &snapshot)
+                    .iter()
+                    .filter_map(|(song, uri)| tag.value(song, uri))
+                    .collect();
+                for value in values {
+                    out.push_str(&format!("{}: {}\n", tag.name(), value));
+                }
+            }
+            "outputs" => out.push_str("outputid: 0\noutputname: default\noutputenabled: 1\n"),
+            "tagtypes" => {
+                // tags are always all sent, changing them is accepted and ignored
+                if args.is_empty() {
+                    for tag in TAG_TYPES {
+                        out.push_str(&format!("tagtype: {}\n", tag.name()));
+                    }
+                }
+            }
+            "commands" => {
+                for command in COMMANDS {
+                    out.push_str(&format!("command: {}\n", command));
+                }
+            }
+            "notcommands" => {}
+            "urlhandlers" => out.push_str("handler: file://\n"),
+            "replay_gain_status" => out.push_str("replay_gain_mode: off\n"),
+            _ => {
+                return Err(MpdError::new(
+                    ACK_UNKNOWN,
+                    format!("unknown command \"{}\"", command),
+                ));
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn write_status(&self, out: &mut String) {
+        let Some(status) = self.shared.status() else {
+            out.push_str("volume: 100\nrepeat: 0\nrandom: 0\nsingle: 0\nconsume: 0\n");
+            out.push_str("playlist: 0\nplaylistlength: 0\nstate: stop\n");
+            return;
+        };
+        let state = match status.state {
+            PlayState::Playing => "play",
+            PlayState::Paused => "pause",
+            PlayState::Stopped => "stop",
+        };
+        out.push_str(&format!(
+            "volume: {}\nrepeat: 0\nrandom: 0\nsingle: 0\nconsume: 0\n",
+            (status.volume * 100.0).round() as u32
+        ));
+        out.push_str(&format!(
+            "playlist: {}\nplaylistlength: {}\nstate: {}\n",
+            status.queue_version, status.queue_len, state
+        ));
+        if let (Some(index), Some(id)) = (status.index, status.song_id) {
+            out.push_str(&format!("song: {}\nsongid: {}\n", index, id));
+            if let Some(next_id) = status.next_song_id {
+                out.push_str(&format!(
+                    "nextsong: {}\nnextsongid: {}\n",
+                    index + 1,
+                    next_id
+                ));
+            }
+        }
+        if let Some(song) = &status.song
+            && status.state != PlayState::Stopped
+        {
+            out.push_str(&format!(
+                "time: {}:{}\nelapsed: {:.3}\nduration: {:.3}\n",
+                status.position as u64,
+                song.duration.round() as u64,
+                status.position,
+                song.duration
+            ));
+        }
+    }
+}
+
+fn required(args: &[String], index: usize) -> Result<&str, MpdError> {
+    args.get(index)
+        .map(String::as_str)
+        .ok_or_else(|| MpdError::arg("too few arguments"))
+}
+
+/// Index in queue of song with id argument
+fn index_of_id(queue: &[QueueSong], arg: &str) -> Result<usize, MpdError> {
+    let id: u64 = parse_number(arg)?;
+    queue
+        .iter()
+        .position(|s| s.id == id)
+        .ok_or_else(|| MpdError::new(ACK_NO_EXIST, "No such song"))
+}
+
+/// Split `window start:end` from the end of filter arguments
+fn split_window(args: &[String]) -> (&[String], Option<&str>) {
+    match args {
+        [filter @ .., key, range] if key == "window" => (filter, Some(range)),
+        _ => (args, None),
+    }
+}
+
+/// Library tracks matching filter with their URIs
+fn find(filter: &Filter, snapshot: &LibrarySnapshot) -> Vec<(SongInfo, String)> {
+    snapshot
+        .library
+        .tracks()
+        .iter()
+        .filter_map(|track| {
+            let uri = uri_of(&track.path, &snapshot.folders);
+            let song = SongInfo::from_track(track);
+            filter.matches(&song, &uri).then_some((song, uri))
+        })
+        .collect()
+}
+
+/// Paths of songs at URI, a directory gives library tracks under it
+fn songs_at(location: &str, snapshot: &LibrarySnapshot) -> Result<Vec<PathBuf>, MpdError> {
+    let path = path_of(location, &snapshot.folders)
+        .ok_or_else(|| MpdError::new(ACK_NO_EXIST, "No such directory"))?;
+    if path.is_file() {
+        return Ok(vec![path]);
+    }
+    let paths: Vec<_> = snapshot
+        .library
+        .tracks()
+        .iter()
+        .filter(|t| t.path.starts_with(&path))
+        .map(|t| t.path.clone())
+        .collect();
+    if paths.is_empty() {
+        return Err(MpdError::new(ACK_NO_EXIST, "No such directory"));
+    }
+    Ok(paths)
+}
+
+/// List directory at URI, the root lists library folders
+fn lsinfo(location: &str, snapshot: &LibrarySnapshot, out: &mut String) -> Result<(), MpdError> {
+    let folders = &snapshot.folders;
+    if location.trim_matches('/').is_empty() {
+        for folder in folders {
+            if let Some(name) = folder.file_name() {
+                out.push_str(&format!("directory: {}\n", name.to_string_lossy()));
+            }
+        }
+        return Ok(());
+    }
+    let no_exist = || MpdError::new(ACK_NO_EXIST, "No such directory");
+    let path = path_of(location, folders).ok_or_else(no_exist)?;
+    if path.is_file() {
+        write_file(&path, snapshot, out);
+        return Ok(());
+    }
+    let mut entries: Vec<_> = fs::read_dir(&path)
+        .map_err(|_| no_exist())?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .is_some_and(|n| !n.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            out.push_str(&format!("directory: {}\n", uri_of(&entry, folders)));
+        } else {
+            write_file(&entry, snapshot, out);
+        }
+    }
+    Ok(())
+}
+
+/// Write file with tags of library, music files not scanned yet only have their URI
+fn write_file(path: &Path, snapshot: &LibrarySnapshot, out: &mut String) {
+    if let Some(track) = snapshot.library.track(path) {
+        write_song(out, &SongInfo::from_track(track), &snapshot.folders);
+        return;
+    }
+    let is_music = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MUSIC_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+    if is_music {
+        out.push_str(&format!("file: {}\n", uri_of(path, &snapshot.folders)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        service::{library_service::library::Library, remote_service::models::PlayerStatus},
+        utils::temp_dir::TempDir,
+    };
+
+    use super::*;
+
+    fn song(id: u64, changed: u64, path: &str) -> QueueSong {
+        QueueSong {
+            id,
+            changed,
+            song: SongInfo {
+                path: PathBuf::from(path),
+                title: Some(format!("Song {}", id)),
+                duration: 120.0,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn empty_library() -> LibrarySnapshot {
+        LibrarySnapshot {
+            library: Arc::new(Library::default()),
+            folders: Vec::new(),
+        }
+    }
+
+    /// Run lines as a command list against an app with queue, library and status.
+    /// Return reply and commands the app got
+    fn run(
+        lines: &[&str],
+        queue: Vec<QueueSong>,
+        library: LibrarySnapshot,
+        status: Option<PlayerStatus>,
+    ) -> (String, Vec<RemoteCommand>) {
+        let (sender, receiver) = smol::channel::unbounded();
+        let shared = Arc::new(Shared::new(sender));
+        if let Some(status) = status {
+            shared.update(status, 0.0);
+        }
+        let mut session = Session {
+            changes: shared.register(),
+            shared,
+            pending: Vec::new(),
+        };
+        smol::block_on(async move {
+            let app = smol::spawn(async move {
+                let mut sent = Vec::new();
+                while let Ok(command) = receiver.recv().await {
+                    match command {
+                        RemoteCommand::GetQueue(reply) => {
+                            let _ = reply.try_send(queue.clone());
+                        }
+                        RemoteCommand::GetLibrary(reply) => {
+                            let _ = reply.try_send(library.clone());
+                        }
+                        command => sent.push(command),
+                    }
+                }
+                sent
+            });
+            let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+            let (reply, _) = session.run(&lines, lines.len() > 1).await;
+            // app stops when session drops its channel
+            drop(session);
+            (reply, app.await)
+        })
+    }
+
+    fn queue() -> Vec<QueueSong> {
+        vec![
+            song(7, 1, "/music/a.flac"),
+            song(8, 3, "/music/b.flac"),
+            song(12, 4, "/music/c.flac"),
+        ]
+    }
+
+    #[test]
+    fn status_tells_ids_of_songs() {
+        let status = PlayerStatus {
+            state: PlayState::Playing,
+            position: 12.5,
+            volume: 0.5,
+            index: Some(1),
+            song_id: Some(8),
+            next_song_id: Some(12),
+            queue_len: 3,
+            queue_version: 4,
+            song: Some(queue()[1].song.clone()),
+            dsp: Vec::new(),
+        };
+        let (reply, _) = run(&["status"], queue(), empty_library(), Some(status));
+        assert!(reply.contains("volume: 50\n"));
+        assert!(reply.contains("playlist: 4\nplaylistlength: 3\nstate: play\n"));
+        assert!(reply.contains("song: 1\nsongid: 8\nnextsong: 2\nnextsongid: 12\n"));
+        assert!(reply.contains("elapsed: 12.500\n"));
+        assert!(reply.ends_with("OK\n"));
+    }
+
+    #[test]
+    fn id_commands_act_on_song_with_id() {
+        let (reply, sent) = run(
+            &["playid 12", "deleteid 7", "moveid 8 0", "seekid 12 30"],
+            queue(),
+            empty_library(),
+            None,
+        );
+        assert_eq!(reply, "list_OK\nlist_OK\nlist_OK\nlist_OK\nOK\n");
+        assert!(matches!(
+            sent.as_slice(),
+            [
+                RemoteCommand::PlayAt(2),
+                RemoteCommand::Remove(0),
+                RemoteCommand::Move(1, 0),
+                RemoteCommand::PlayAt(2),
+                RemoteCommand::SeekTo(30.0),
+            ]
+        ));
+    }
+
+    #[test]
+    fn unknown_id_is_refused() {
+        let (reply, sent) = run(&["playid 3"], queue(), empty_library(), None);
+        assert_eq!(reply, "ACK [50@0] {playid} No such song\n");
+        assert!(sent.is_empty());
+    }
+
+    #[test]
+    fn playlistinfo_writes_position_and_id() {
+        let (reply, _) = run(&["playlistinfo 1:"], queue(), empty_library(), None);
+        assert_eq!(
+            reply,
+            "file: /music/b.flac\nTitle: Song 8\nTime: 120\nduration: 120.000\nPos: 1\nId: 8\n\
+             file: /music/c.flac\nTitle: Song 12\nTime: 120\nduration: 120.000\nPos: 2\nId: 12\n\
+             OK\n"
+        );
+        let (reply, _) = run(&["playlistid 12"], queue(), empty_library(), None);
+        assert!(reply.contains("Pos: 2\nId: 12\n"));
+    }
+
+    #[test]
+    fn plchanges_lists_songs_changed_after_version() {
+        let (reply, _) = run(&["plchangesposid 2"], queue(), empty_library(), None);
+        assert_eq!(reply, "cpos: 1\nId: 8\ncpos: 2\nId: 12\nOK\n");
+        let (reply, _) = run(&["plchangesposid 0 0:1"], queue(), empty_library(), None);
+        assert_eq!(reply, "cpos: 0\nId: 7\nOK\n");
+        let (reply, _) = run(&["plchanges 4"], queue(), empty_library(), None);
+        assert_eq!(reply, "OK\n");
+        let (reply, _) = run(&["plchanges"], queue(), empty_library(), None);
+        assert_eq!(reply, "ACK [2@0] {plchanges} too few arguments\n");
+    }
+
+    #[test]
+    fn command_list_stops_at_failing_command() {
+        let (reply, sent) = run(&["next", "bogus", "stop"], queue(), empty_library(), None);
+        assert_eq!(
+            reply,
+            "list_OK\nACK [5@1] {bogus} unknown command \"bogus\"\n"
+        );
+        assert!(matches!(sent.as_slice(), [RemoteCommand::Next]));
+        let (reply, _) = run(&["pause 2"], queue(), empty_library(), None);
+        assert_eq!(reply, "ACK [2@0] {pause} Boolean expected: 2\n");
+    }
+
+    #[test]
+    fn paths_outside_library_are_not_listed_or_added() {
+        let root = TempDir::new("session");
+        let folder = root.join("music");
+        root.file("music/a.flac");
+        let secret = root.file("private/secret.flac");
+        let library = LibrarySnapshot {
+            library: Arc::new(Library::default()),
+            folders: vec![folder.clone()],
+        };
+
+        let (reply, _) = run(&["lsinfo music"], queue(), library.clone(), None);
+        assert_eq!(reply, "file: music/a.flac\nOK\n");
+        let (reply, _) = run(&["lsinfo music/../private"], queue(), library.clone(), None);
+        assert_eq!(reply, "ACK [50@0] {lsinfo} No such directory\n");
+        let lsinfo = format!("lsinfo \"{}\"", root.join("private").display());
+        let (reply, _) = run(&[&lsinfo], queue(), library.clone(), None);
+        assert_eq!(reply, "ACK [50@0] {lsinfo} No such directory\n");
+        let add = format!("add \"{}\"", secret.display());
+        let (reply, sent) = run(&[&add], queue(), library.clone(), None);
+        assert_eq!(reply, "ACK [50@0] {add} No such directory\n");
+        assert!(sent.is_empty());
+        let (reply, sent) = run(&["add music/a.flac"], queue(), library, None);
+        assert_eq!(reply, "OK\n");
+        assert!(
+            matches!(sent.as_slice(), [RemoteCommand::Enqueue(paths)] if paths == &[folder.join("a.flac")])
+        );
+    }
+}
@@ -0,0 +1,466 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use smol::channel::Sender;
+use zbus::{
+    fdo, interface,
+    object_server::SignalEmitter,
+    zvariant::{ObjectPath, OwnedValue, Value},
+};
+
+use crate::service::{
+    music_service::models::PlayState,
+    playlist_service::uri,
+    remote_service::{
+        cover,
+        models::{PlayerStatus, RemoteCommand},
+    },
+};
+
+// track id of MPRIS meaning no song
+static NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// The `org.mpris.MediaPlayer2` interface, the player can not be raised or quit
+pub struct RootInterface;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl RootInterface {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "The Player".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["file".to_string()]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        [
+            "audio/mpeg",
+            "audio/flac",
+            "audio/wav",
+            "audio/ogg",
+            "audio/mp4",
+            "audio/aac",
+        ]
+        .iter()
+        .map(|m| m.to_string())
+        .collect()
+    }
+}
+
+/// Properties and signals of player interface changed between two statuses
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub state: bool,
+    // song or its index changed, metadata is notified
+    pub song: bool,
+    // file of song changed, cover is exported again
+    pub path: bool,
+    pub volume: bool,
+    // index or length of queue changed, abilities like next are notified
+    pub queue: bool,
+    // position in microseconds if it jumped, not by playing
+    pub seeked: Option<i64>,
+}
+
+impl Changes {
+    /// Compare status with the last one, None if there was none.
+    /// Elapsed is the seconds since the last status, to find out jumps of position
+    pub fn between(old: Option<&PlayerStatus>, status: &PlayerStatus, elapsed: f64) -> Self {
+        let song = old.map(|s| (s.index, &s.song)) != Some((status.index, &status.song));
+        // position expected from playing, it jumped if far from it
+        let jumped = match old {
+            Some(o) if !song => {
+                let played = if o.state == PlayState::Playing {
+                    elapsed
+                } else {
+                    0.0
+                };
+                (status.position - o.position - played).abs() > 1.0
+            }
+            _ => false,
+        };
+        Self {
+            state: old.map(|s| s.state) != Some(status.state),
+            song,
+            path: old.and_then(|s| s.song.as_ref()).map(|s| &s.path)
+                != status.song.as_ref().map(|s| &s.path),
+            volume: old.map(|s| s.volume) != Some(status.volume),
+            queue: old.map(|s| (s.index, s.queue_len)) != Some((status.index, status.queue_len)),
+            seeked: jumped.then_some((status.position * 1_000_000.0) as i64),
+        }
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface, methods are sent to app as commands
+pub struct PlayerInterface {
+    commands: Sender<RemoteCommand>,
+    // last status published by app
+    status: Option<PlayerStatus>,
+    // cover of current song exported to file
+    cover: Option<PathBuf>,
+}
+
+impl PlayerInterface {
+    pub fn new(commands: Sender<RemoteCommand>) -> Self {
+        Self {
+            commands,
+            status: None,
+            cover: None,
+        }
+    }
+
+    /// Take status published by app, notify clients of changed properties.
+    /// Elapsed is the seconds since the last status, to find out jumps of position
+    pub async fn update(
+        &mut self,
+        status: PlayerStatus,
+        elapsed: f64,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        let changes = Changes::between(self.status.as_ref(), &status, elapsed);
+        if changes.path {
+            if let Some(old_cover) = self.cover.take() {
+                let _ = fs::remove_file(old_cover);
+            }
+            self.cover = status.song.as_ref().and_then(|s| cover::export(&s.path));
+        }
+        self.status = Some(status);
+
+        if changes.state {
+            self.playback_status_changed(emitter).await?;
+        }
+        if changes.song {
+            self.metadata_changed(emitter).await?;
+        }
+        if changes.volume {
+            self.volume_changed(emitter).await?;
+        }
+        if changes.song || changes.queue {
+            self.can_go_next_changed(emitter).await?;
+            self.can_go_previous_changed(emitter).await?;
+            self.can_play_changed(emitter).await?;
+            self.can_pause_changed(emitter).await?;
+            self.can_seek_changed(emitter).await?;
+        }
+        if let Some(position) = changes.seeked {
+            Self::seeked(emitter, position).await?;
+        }
+        Ok(())
+    }
+
+    fn send(&self, command: RemoteCommand) {
+        if let Err(e) = self.commands.try_send(command) {
+            eprintln!("error when send mpris command: {}", e);
+        }
+    }
+
+    fn state(&self) -> PlayState {
+        self.status.as_ref().map_or(PlayState::Stopped, |s| s.state)
+    }
+
+    fn has_song(&self) -> bool {
+        self.status.as_ref().is_some_and(|s| s.song.is_some())
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl PlayerInterface {
+    fn next(&self) {
+        self.send(RemoteCommand::Next);
+    }
+
+    fn previous(&self) {
+        self.send(RemoteCommand::Previous);
+    }
+
+    fn pause(&self) {
+        self.send(RemoteCommand::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.send(RemoteCommand::PlayPause);
+    }
+
+    fn stop(&self) {
+        self.send(RemoteCommand::Stop);
+    }
+
+    fn play(&self) {
+        self.send(RemoteCommand::Play);
+    }
+
+    /// Seek by offset in microseconds
+    fn seek(&self, offset: i64) {
+        self.send(RemoteCommand::SeekBy(offset as f64 / 1_000_000.0));
+    }
+
+    /// Seek to position in microseconds, ignored if track is not the current one
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let current = self.status.as_ref().and_then(|s| s.index).map(track_id_of);
+        if position < 0 || current.as_deref() != Some(track_id.as_str()) {
+            return;
+        }
+        self.send(RemoteCommand::SeekTo(position as f64 / 1_000_000.0));
+    }
+
+    fn open_uri(&self, uri: String) -> fdo::Result<()> {
+        let is_file = uri
+            .get(..7)
+            .is_some_and(|head| head.eq_ignore_ascii_case("file://"));
+        if !is_file {
+            return Err(fdo::Error::NotSupported(format!("can not open {}", uri)));
+        }
+        self.send(RemoteCommand::Enqueue(vec![uri::to_path(&uri)]));
+        Ok(())
+    }
+
+    /// Position jumped, not by playing
+    #[zbus(signal)]
+    pub async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        match self.state() {
+            PlayState::Playing => "Playing",
+            PlayState::Paused => "Paused",
+            PlayState::Stopped => "Stopped",
+        }
+        .to_string()
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut map = HashMap::new();
+        let song = self
+            .status
+            .as_ref()
+            .and_then(|s| Some((s.index?, s.song.as_ref()?)));
+        let Some((index, song)) = song else {
+            insert(&mut map, "mpris:trackid", object_path(NO_TRACK.to_string()));
+            return map;
+        };
+        insert(&mut map, "mpris:trackid", object_path(track_id_of(index)));
+        insert(
+            &mut map,
+            "mpris:length",
+            (song.duration * 1_000_000.0) as i64,
+        );
+        insert(&mut map, "xesam:url", uri::from_path(&song.path));
+        if let Some(title) = &song.title {
+            insert(&mut map, "xesam:title", title.clone());
+        }
+        if let Some(artist) = &song.artist {
+            insert(&mut map, "xesam:artist", vec![artist.clone()]);
+        }
+        if let Some(album) = &song.album {
+            insert(&mut map, "xesam:album", album.clone());
+        }
+        if let Some(artist) = &song.album_artist {
+            insert(&mut map, "xesam:albumArtist", vec![artist.clone()]);
+        }
+        if let Some(genre) = &song.genre {
+            insert(&mut map, "xesam:genre", vec![genre.clone()]);
+        }
+        if let Some(number) = song.number {
+            insert(&mut map, "xesam:trackNumber", number as i32);
+        }
+        if let Some(cover) = &self.cover {
+            insert(&mut map, "mpris:artUrl", uri::from_path(cover));
+        }
+        map
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.status.as_ref().map_or(1.0, |s| s.volume as f64)
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        self.send(RemoteCommand::SetVolume(volume as f32));
+    }
+
+    /// Position in microseconds, clients read it when needed
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.status
+            .as_ref()
+            .map_or(0, |s| (s.position * 1_000_000.0) as i64)
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|s| s.index.map_or(0, |i| i + 1) < s.queue_len)
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.has_song()
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.status.as_ref().is_some_and(|s| s.queue_len > 0)
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.has_song()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.has_song()
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// Track id of song at index of queue
+pub fn track_id_of(index: usize) -> String {
+    format!("/org/the_player/Track/{}", index)
+}
+
+fn object_path(path: String) -> Value<'static> {
+    match ObjectPath::try_from(path) {
+        Ok(p) => Value::from(p),
+        Err(_) => Value::from(ObjectPath::from_static_str_unchecked(NO_TRACK)),
+    }
+}
+
+/// Insert value into metadata, values not able to be owned are skipped
+fn insert<'a>(map: &mut HashMap<String, OwnedValue>, key: &str, value: impl Into<Value<'a>>) {
+    if let Ok(value) = OwnedValue::try_from(value.into()) {
+        map.insert(key.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::remote_service::models::SongInfo;
+
+    fn status(index: usize, path: &str, position: f64) -> PlayerStatus {
+        PlayerStatus {
+            state: PlayState::Playing,
+            position,
+            volume: 1.0,
+            index: Some(index),
+            song_id: Some(index as u64 + 1),
+            next_song_id: None,
+            queue_len: 3,
+            queue_version: 1,
+            song: Some(SongInfo {
+                path: PathBuf::from(path),
+                duration: 200.0,
+                ..Default::default()
+            }),
+            dsp: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn first_status_notifies_everything() {
+        let changes = Changes::between(None, &status(0, "/a.flac", 5.0), 0.0);
+        assert!(changes.state && changes.song && changes.path);
+        assert!(changes.volume && changes.queue);
+        assert_eq!(changes.seeked, None);
+    }
+
+    #[test]
+    fn playing_moves_position_without_signal() {
+        let old = status(0, "/a.flac", 10.0);
+        let changes = Changes::between(Some(&old), &status(0, "/a.flac", 10.5), 0.5);
+        assert_eq!(changes, Changes::default());
+    }
+
+    #[test]
+    fn jump_of_position_is_seeked() {
+        let old = status(0, "/a.flac", 10.0);
+        let changes = Changes::between(Some(&old), &status(0, "/a.flac", 60.0), 0.5);
+        assert_eq!(changes.seeked, Some(60_000_000));
+        assert!(!changes.song);
+
+        // paused position does not move by time
+        let mut paused = old.clone();
+        paused.state = PlayState::Paused;
+        let changes = Changes::between(Some(&paused), &paused, 5.0);
+        assert_eq!(changes.seeked, None);
+        let mut moved = paused.clone();
+        moved.position = 15.0;
+        assert_eq!(
+            Changes::between(Some(&paused), &moved, 0.0).seeked,
+            Some(15_000_000)
+        );
+    }
+
+    #[test]
+    fn new_song_is_not_seeked() {
+        let old = status(0, "/a.flac", 180.0);
+        let changes = Changes::between(Some(&old), &status(1, "/b.flac", 0.0), 0.5);
+        assert!(changes.song && changes.path && changes.queue);
+        assert!(!changes.state && !changes.volume);
+        assert_eq!(changes.seeked, None);
+    }
+
+    #[test]
+    fn same_file_at_other_index_keeps_cover() {
+        let old = status(0, "/a.flac", 180.0);
+        let changes = Changes::between(Some(&old), &status(2, "/a.flac", 0.0), 0.5);
+        assert!(changes.song && changes.queue);
+        assert!(!changes.path);
+    }
+
+    #[test]
+    fn state_and_volume_are_notified_alone() {
+        let old = status(0, "/a.flac", 10.0);
+        let mut new = old.clone();
+        new.state = PlayState::Paused;
+        new.volume = 0.5;
+        let changes = Changes::between(Some(&old), &new, 0.0);
+        assert!(changes.state && changes.volume);
+        assert!(!changes.song && !changes.queue);
+    }
+}
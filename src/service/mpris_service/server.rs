@@ -0,0 +1,64 @@
+use std::{thread, time::Instant};
+
+use smol::channel::{Receiver, Sender};
+use zbus::{Connection, connection};
+
+use crate::service::{
+    mpris_service::interface::{PlayerInterface, RootInterface},
+    remote_service::models::{PlayerStatus, RemoteCommand},
+};
+
+static BUS_NAME: &str = "org.mpris.MediaPlayer2.the_player";
+static OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// Serve MPRIS on session bus in a thread.
+/// Methods called by clients are sent as commands, statuses published by app are shown to them
+pub fn start(commands: Sender<RemoteCommand>, statuses: Receiver<PlayerStatus>) {
+    thread::spawn(move || {
+        smol::block_on(async move {
+            match connect(commands).await {
+                Ok(connection) => run(connection, statuses).await,
+                Err(e) => eprintln!("failed to start mpris: {}", e),
+            }
+        })
+    });
+}
+
+/// Connect to session bus and own the player name
+async fn connect(commands: Sender<RemoteCommand>) -> zbus::Result<Connection> {
+    let connection = connection::Builder::session()?
+        .serve_at(OBJECT_PATH, RootInterface)?
+        .serve_at(OBJECT_PATH, PlayerInterface::new(commands))?
+        .build()
+        .await?;
+    // name is owned by another running player, use a name of this instance
+    if connection.request_name(BUS_NAME).await.is_err() {
+        let name = format!("{}.instance{}", BUS_NAME, std::process::id());
+        connection.request_name(name).await?;
+    }
+    Ok(connection)
+}
+
+/// Update player interface with every status until app is closed
+async fn run(connection: Connection, statuses: Receiver<PlayerStatus>) {
+    let player = match connection
+        .object_server()
+        .interface::<_, PlayerInterface>(OBJECT_PATH)
+        .await
+    {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("failed to start mpris: {}", e);
+            return;
+        }
+    };
+    let mut updated = Instant::now();
+    while let Ok(status) = statuses.recv().await {
+        let elapsed = updated.elapsed().as_secs_f64();
+        updated = Instant::now();
+        let mut iface = player.get_mut().await;
+        if let Err(e) = iface.update(status, elapsed, player.signal_emitter()).await {
+            eprintln!("error when update mpris: {}", e);
+        }
+    }
+}
@@ -2,6 +2,8 @@ use std::sync::{Condvar, Mutex};
 
 use symphonia::core::units::Time;
 
+use crate::service::music_service::{decoder::Decoder, models::TrackRange};
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum ServiceState {
     Playing,
@@ -10,10 +12,29 @@ pub enum ServiceState {
     Seek(Time),
 }
 
+/// A music handed to the decode thread
+pub struct QueuedMusic {
+    pub decoder: Decoder,
+    // id of song in queue
+    pub id: u64,
+    // whether can be crossfaded with the music before it
+    pub crossfade: bool,
+    // loudness normalization gain factor
+    pub gain: f32,
+    // position to start from
+    pub start: Option<Time>,
+    // part of file to play
+    pub range: Option<TrackRange>,
+}
+
 /// The decode thread controller
 pub struct Controller {
     state: Mutex<ServiceState>,
     condvar: Condvar,
+    // music to replace current one immediately
+    load: Mutex<Option<QueuedMusic>>,
+    // music to be played after current one
+    next: Mutex<Option<QueuedMusic>>,
 }
 
 impl Controller {
@@ -22,6 +43,8 @@ impl Controller {
         Self {
             state: Mutex::new(ServiceState::Playing),
             condvar: Condvar::new(),
+            load: Mutex::new(None),
+            next: Mutex::new(None),
         }
     }
 
@@ -49,6 +72,61 @@ impl Controller {
         *state = ServiceState::Seek(seek_to);
     }
 
+    /// Replace current music, the music set to next will be dropped.
+    /// Fails if decode thread has stopped and will not take it
+    pub fn load(&self, music: QueuedMusic) -> Result<(), anyhow::Error> {
+        let state = self.state.lock().unwrap();
+        if *state == ServiceState::Stopped {
+            anyhow::bail!("decode thread has exited");
+        }
+        *self.load.lock().unwrap() = Some(music);
+        *self.next.lock().unwrap() = None;
+        Ok(())
+    }
+
+    /// Stop when play finished, unless a music is loaded meanwhile.
+    /// Return whether stopped
+    pub fn finish(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if self.load.lock().unwrap().is_some() {
+            return false;
+        }
+        *state = ServiceState::Stopped;
+        true
+    }
+
+    /// Set the music to be played after current one
+    pub fn set_next(&self, next: Option<QueuedMusic>) {
+        *self.next.lock().unwrap() = next;
+    }
+
+    /// Put back the music taken for crossfade, unless another one is set meanwhile
+    pub fn restore_next(&self, music: QueuedMusic) {
+        let mut next = self.next.lock().unwrap();
+        if next.is_none() {
+            *next = Some(music);
+        }
+    }
+
+    /// Take the music to replace current one
+    pub fn take_load(&self) -> Option<QueuedMusic> {
+        self.load.lock().unwrap().take()
+    }
+
+    /// Take the music to be played after current one
+    pub fn take_next(&self) -> Option<QueuedMusic> {
+        self.next.lock().unwrap().take()
+    }
+
+    /// Take the music to be played after current one if it can be crossfaded
+    pub fn take_next_crossfade(&self) -> Option<QueuedMusic> {
+        let mut next = self.next.lock().unwrap();
+        if next.as_ref().is_some_and(|n| n.crossfade) {
+            return next.take();
+        }
+        None
+    }
+
     pub fn state(&self) -> ServiceState {
         *self.state.lock().unwrap()
     }
@@ -1,28 +1,130 @@
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, atomic::Ordering},
 };
 
 use atomic_float::AtomicF32;
+use lofty::tag::Accessor;
+use smol::channel::{Receiver, Sender};
+use symphonia::core::units::Time;
 
-use crate::service::music_service::{models::PlayState, music::Music, player::Player};
+use crate::{
+    service::{
+        library_service::{
+            library::{self, Library},
+            models::{LibraryEvents, ScanChanges},
+            scanner,
+            smart_playlist::{self, SmartPlaylist},
+            watcher::FolderWatcher,
+        },
+        loudness_service::{models::ScanEvents, scanner as loudness_scanner},
+        music_service::{
+            cpal_output::CpalOutput,
+            cue,
+            dsp::{DspEdit, DspKind, DspLayout, DspStage},
+            equalizer::{Equalizer, Preset},
+            models::{DeviceInfo, Events, OutputDevice, OutputKind, PlayItem, PlayState},
+            music::Music,
+            player::Player,
+            queue::Queue,
+            replaygain::ReplayGainMode,
+        },
+        playlist_service::{
+            loader,
+            models::{Playlist, PlaylistEntry},
+        },
+        remote_service::models::{
+            LibrarySnapshot, PlayerStatus, QueueSong, RemoteCommand, SongInfo,
+        },
+    },
+    utils::{config::Config, utils},
+};
 
 pub struct Core {
     player: Option<Player>,
-    current: Option<Music>,
+    queue: Queue,
     gain: Arc<AtomicF32>,
+    // stereo balance, -1.0 left to 1.0 right
+    balance: Arc<AtomicF32>,
+    // crossfade seconds between songs, 0 means disabled
+    crossfade_secs: Arc<AtomicF32>,
+    // equalizer shared with players
+    equalizer: Arc<Equalizer>,
+    // order of processors, every player follows it with its own processors
+    dsp: Arc<DspLayout>,
+    // loudness normalization mode and preamp (dB)
+    replay_gain_mode: ReplayGainMode,
+    replay_gain_preamp: f32,
     state: PlayState,
-    // queue: Vec<Music>,
+    // saved user settings
+    config: Config,
+    // sink of players
+    output: OutputKind,
+    // tracks of library folders, shared with views and copied on write
+    library: Arc<Library>,
+    // plays (path, unix ms) not counted into library yet, see flush_stats
+    pending_plays: Vec<(PathBuf, u64)>,
+    // library has changes not saved yet
+    library_dirty: bool,
+    // watcher of library folders, stops when dropped
+    watcher: Option<FolderWatcher>,
+    // rules of smart playlists, their tracks are evaluated from library by views
+    smart_playlists: Vec<SmartPlaylist>,
+    // error of loading smart playlists, their file is not overwritten while it is set
+    smart_playlists_error: Option<String>,
+    // event channel shared by every player
+    sender: Sender<Events>,
+    receiver: Arc<Receiver<Events>>,
 }
 
 impl Core {
     /// Create a new core
     pub fn new() -> Self {
+        let (sender, receiver) = smol::channel::unbounded::<Events>();
+        let gain = Arc::new(AtomicF32::new(1.0));
+        let balance = Arc::new(AtomicF32::new(0.0));
+        let equalizer = Arc::new(Equalizer::new());
+        let config = Config::load();
+        let (smart_playlists, smart_playlists_error) = match smart_playlist::load() {
+            Ok(playlists) => (playlists, None),
+            Err(e) => {
+                eprintln!("{}", e);
+                (Vec::new(), Some(e.to_string()))
+            }
+        };
+
+        let stages = match &config.dsp_chain {
+            Some(stages) => stages.clone(),
+            None => DspLayout::DEFAULT.map(DspStage::new).to_vec(),
+        };
+        let dsp = Arc::new(DspLayout::new(
+            stages,
+            equalizer.clone(),
+            balance.clone(),
+            gain.clone(),
+        ));
+
         Self {
             player: None,
-            current: None,
-            gain: Arc::new(AtomicF32::new(1.0)),
+            queue: Queue::new(),
+            gain,
+            balance,
+            crossfade_secs: Arc::new(AtomicF32::new(config.crossfade_secs.max(0.0))),
+            equalizer,
+            dsp,
+            replay_gain_mode: config.replay_gain_mode,
+            replay_gain_preamp: config.replay_gain_preamp,
             state: PlayState::Stopped,
+            output: OutputKind::Device(config.output_device.clone()),
+            config,
+            library: Arc::new(Library::load()),
+            pending_plays: Vec::new(),
+            library_dirty: false,
+            watcher: None,
+            smart_playlists,
+            smart_playlists_error,
+            sender,
+            receiver: Arc::new(receiver),
         }
     }
 
@@ -31,18 +133,337 @@ impl Core {
     }
 
     pub fn current(&self) -> Option<&Music> {
-        self.current.as_ref()
+        self.queue.current()
     }
 
-    // TODO: move to list struct
-    /// Append a new song to core
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+
+    /// Get equalizer, changes of bands apply to playing song
+    /// Get event receiver, events of all players will be received here
+    pub fn receiver(&self) -> Arc<Receiver<Events>> {
+        self.receiver.clone()
+    }
+
+    /// Append a new song to queue, start play if core is stopped
     pub fn append(&mut self, path: PathBuf) -> Result<(), anyhow::Error> {
-        self.player = Some(Player::new(path.clone(), self.gain.clone())?);
-        self.current = Some(Music::from_path(path)?);
+        let first = self.queue.len();
+        for music in Music::load_all(path)? {
+            self.queue.append(music);
+        }
+        if self.state == PlayState::Stopped {
+            self.play_at(first)?;
+        } else {
+            self.preload_next();
+        }
+        Ok(())
+    }
+
+    /// Insert a new song into queue at index, return count of songs inserted
+    pub fn insert(&mut self, index: usize, path: PathBuf) -> Result<usize, anyhow::Error> {
+        let musics = Music::load_all(path)?;
+        let count = musics.len();
+        for (offset, music) in musics.into_iter().enumerate() {
+            self.queue.insert(index + offset, music);
+        }
+        self.preload_next();
+        Ok(count)
+    }
+
+    /// Replace queue with songs and play the one at index.
+    /// Songs failed to open are skipped.
+    pub fn play_paths(&mut self, paths: Vec<PathBuf>, index: usize) -> Result<(), anyhow::Error> {
+        let target = paths
+            .get(index)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("index {} out of songs", index))?;
+        self.clear();
+        // a path may give several songs, like tracks of CUE sheet
+        let mut target_index = None;
+        for (ix, path) in paths.iter().enumerate() {
+            match Music::load_all(path) {
+                Ok(musics) => {
+                    if ix == index {
+                        target_index = Some(self.queue.len());
+                    }
+                    for music in musics {
+                        self.queue.append(music);
+                    }
+                }
+                Err(e) => eprintln!("failed to open {}: {}", path.display(), e),
+            }
+        }
+        let index =
+            target_index.ok_or_else(|| anyhow::anyhow!("failed to open {}", target.display()))?;
+        self.play_at(index)
+    }
+
+    /// Append songs of playlist file to queue.
+    /// Return entries not found or failed to open, the others are still appended
+    pub fn load_playlist(&mut self, path: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+        let loaded = loader::load(path)?;
+        let mut missing = loaded.missing;
+        for entry in loaded.playlist.entries {
+            if let Err(e) = self.append(entry.path.clone()) {
+                eprintln!("failed to open {}: {}", entry.path.display(), e);
+                missing.push(entry.path);
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Save songs of queue as playlist file, format is chosen by extension
+    pub fn save_queue_as_playlist(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let entry = |music: &Music, path: PathBuf| {
+            let tags = music.get_tags();
+            PlaylistEntry {
+                path,
+                title: tags.and_then(|t| t.title()).map(|s| s.to_string()),
+                artist: tags.and_then(|t| t.artist()).map(|s| s.to_string()),
+                duration: Some(music.get_duration().as_secs_f64()).filter(|d| *d > 0.0),
+            }
+        };
+        let list = self.queue.list();
+        let mut entries: Vec<PlaylistEntry> = Vec::new();
+        let mut ix = 0;
+        while ix < list.len() {
+            let music = &list[ix];
+            if music.get_range().is_none() {
+                entries.push(entry(music, music.get_path().clone()));
+                ix += 1;
+                continue;
+            }
+            // tracks are split again when loaded, so a whole sheet is written once
+            // and other tracks one by one like `album.cue#3`
+            let source = music.get_sheet().unwrap_or(music.get_path());
+            let run = list[ix..]
+                .iter()
+                .take_while(|m| {
+                    m.get_range().is_some() && m.get_sheet().unwrap_or(m.get_path()) == source
+                })
+                .count();
+            let tracks = &list[ix..ix + run];
+            let whole = Music::load_all(source).is_ok_and(|all| {
+                all.iter()
+                    .map(Music::track_number)
+                    .eq(tracks.iter().map(Music::track_number))
+            });
+            if whole {
+                entries.push(PlaylistEntry::new(source.clone()));
+            } else {
+                for track in tracks {
+                    let path = match track.track_number() {
+                        Some(number) => cue::track_path(source, number),
+                        None => source.clone(),
+                    };
+                    entries.push(entry(track, path));
+                }
+            }
+            ix += run;
+        }
+        let playlist = Playlist {
+            title: path.file_stem().map(|s| s.to_string_lossy().to_string()),
+            entries,
+        };
+        loader::save(path, &playlist)
+    }
+
+    /// Insert songs after the current one
+    pub fn play_next(&mut self, paths: Vec<PathBuf>) -> Result<(), anyhow::Error> {
+        let mut index = self.queue.current_index().map_or(0, |i| i + 1);
+        for path in paths {
+            index += self.insert(index, path)?;
+        }
+        Ok(())
+    }
+
+    /// Remove a song from queue
+    /// If the removed one is playing, the song taking its place will be played.
+    pub fn remove(&mut self, index: usize) -> Result<(), anyhow::Error> {
+        let is_current = self.queue.current_index() == Some(index);
+        self.queue.remove(index);
+        if is_current {
+            if index < self.queue.len() {
+                self.play_at(index)?;
+            } else {
+                self.stop();
+            }
+        } else {
+            self.preload_next();
+        }
+        Ok(())
+    }
+
+    /// Move a song in queue
+    pub fn move_item(&mut self, from: usize, to: usize) -> bool {
+        let moved = self.queue.move_item(from, to);
+        self.preload_next();
+        moved
+    }
+
+    /// Remove all songs and stop play
+    pub fn clear(&mut self) {
+        self.stop();
+        self.queue.clear();
+    }
+
+    /// Play the song at index of queue
+    pub fn play_at(&mut self, index: usize) -> Result<(), anyhow::Error> {
+        if self.queue.set_current(index).is_none() {
+            return Err(anyhow::anyhow!("index {} out of queue", index));
+        }
+        let item = self.play_item(index, false);
+        // keep the output stream if player is running
+        let loaded = self.player.as_ref().map(|p| p.load(item));
+        let result = match loaded {
+            Some(Ok(())) => Ok(()),
+            // decode thread has exited or is missing, create a new player
+            _ => {
+                // drop the old one first to release output device
+                self.player = None;
+                let item = self.play_item(index, false);
+                self.new_player(item).map(|p| self.player = Some(p))
+            }
+        };
+        if let Err(e) = result {
+            self.stop();
+            return Err(e);
+        }
+        self.preload_next();
         self.play();
+        self.record_play();
+        Ok(())
+    }
+
+    /// Create a player on chosen output device
+    fn new_player(&self, item: PlayItem) -> Result<Player, anyhow::Error> {
+        Player::new(
+            item,
+            &self.output,
+            self.crossfade_secs.clone(),
+            self.dsp.clone(),
+            self.sender.clone(),
+        )
+    }
+
+    /// Recreate player of current song, continue at current position
+    fn reopen_player(&mut self) -> Result<(), anyhow::Error> {
+        let Some(p) = self.player.as_ref().filter(|p| !p.is_stopped()) else {
+            return Ok(());
+        };
+        let start = p.play_time().played_time();
+        let Some(index) = self.queue.current_index() else {
+            return Ok(());
+        };
+        let mut item = self.play_item(index, false);
+        item.start = Some(start);
+
+        // drop the old one first to release output device
+        self.player = None;
+        match self.new_player(item) {
+            Ok(p) => self.player = Some(p),
+            Err(e) => {
+                self.stop();
+                return Err(e);
+            }
+        }
+        self.preload_next();
+        match self.state {
+            PlayState::Playing => self.play(),
+            PlayState::Paused => self.pause(),
+            PlayState::Stopped => (),
+        }
+        Ok(())
+    }
+
+    /// Hand the song after current one to player, so that it can be played without gap
+    fn preload_next(&self) {
+        let Some(p) = self.player.as_ref() else {
+            return;
+        };
+        let index = self.queue.current_index().map_or(0, |i| i + 1);
+        let item = self.queue.peek_next().map(|next| {
+            // never crossfade songs marked gapless
+            let crossfade = !next.is_gapless() && !self.current().is_some_and(|m| m.is_gapless());
+            self.play_item(index, crossfade)
+        });
+        if let Err(e) = p.set_next(item) {
+            eprintln!("error when preload next song: {}", e);
+            let _ = p.set_next(None);
+        }
+    }
+
+    /// Create play item of song with current options
+    fn play_item(&self, index: usize, crossfade: bool) -> PlayItem {
+        let music = &self.queue.list()[index];
+        PlayItem {
+            path: music.get_path().clone(),
+            id: self.queue.id(index).unwrap_or_default(),
+            crossfade,
+            gain: music
+                .replay_gain()
+                .factor(self.replay_gain_mode, self.replay_gain_preamp),
+            start: None,
+            range: music.get_range(),
+        }
+    }
+
+    /// Play next song in queue, stop if no more songs
+    pub fn next(&mut self) -> Result<(), anyhow::Error> {
+        let index = match self.queue.current_index() {
+            Some(i) => i + 1,
+            None => 0,
+        };
+        if index < self.queue.len() {
+            self.play_at(index)
+        } else {
+            self.stop();
+            Ok(())
+        }
+    }
+
+    /// Play previous song in queue, restart current one if it is the first
+    pub fn previous(&mut self) -> Result<(), anyhow::Error> {
+        if self.queue.current_index().is_none() {
+            return Ok(());
+        }
+        if self.queue.previous().is_some() {
+            let index = self.queue.current_index().unwrap_or(0);
+            return self.play_at(index);
+        }
+        if let Some(p) = self.player.as_ref() {
+            p.seek_to(Time::from(0.0));
+        }
         Ok(())
     }
 
+    /// Handle the track changed event, the next song with queue id is playing
+    pub fn handle_track_changed(&mut self, id: u64) {
+        // ignore the event if queue moved on before it is handled
+        let next = self.queue.current_index().map_or(0, |i| i + 1);
+        if self.queue.id(next) != Some(id) {
+            return;
+        }
+        self.queue.next();
+        self.preload_next();
+        self.record_play();
+    }
+
+    /// Handle the play finished event, advance to next song
+    pub fn handle_finished(&mut self) -> Result<(), anyhow::Error> {
+        // ignore the event sent by a replaced player
+        if !self.player.as_ref().is_some_and(|p| p.is_stopped()) {
+            return Ok(());
+        }
+        self.next()
+    }
+
+    /// Handle the device lost event, continue on chosen device or default one
+    pub fn handle_device_lost(&mut self) -> Result<(), anyhow::Error> {
+        self.reopen_player()
+    }
+
     /// Control core start/continue current play
     pub fn play(&mut self) {
         self.state = PlayState::Playing;
@@ -51,6 +472,18 @@ impl Core {
         }
     }
 
+    /// Continue play, start from the first song if stopped
+    pub fn resume(&mut self) -> Result<(), anyhow::Error> {
+        match self.state {
+            PlayState::Stopped if !self.queue.is_empty() => self.play_at(0),
+            PlayState::Stopped => Ok(()),
+            _ => {
+                self.play();
+                Ok(())
+            }
+        }
+    }
+
     /// Control core pause current play
     pub fn pause(&mut self) {
         self.state = PlayState::Paused;
@@ -63,7 +496,7 @@ impl Core {
     pub fn stop(&mut self) {
         self.state = PlayState::Stopped;
         self.player = None;
-        self.current = None
+        self.queue.reset_current();
     }
 
     pub fn get_state(&self) -> PlayState {
@@ -73,4 +506,415 @@ impl Core {
     pub fn set_gain(&self, new_value: f32) {
         self.gain.store(new_value, Ordering::Relaxed);
     }
+
+    pub fn get_gain(&self) -> f32 {
+        self.gain.load(Ordering::Relaxed)
+    }
+
+    /// Get address MPD server listens on, None if disabled
+    pub fn mpd_address(&self) -> Option<&str> {
+        self.config.mpd_address.as_deref()
+    }
+
+    /// Get address HTTP remote API listens on with its token, None if disabled
+    pub fn http_config(&self) -> Option<(String, Option<String>)> {
+        let address = self.config.http_address()?;
+        Some((address, self.config.http_token.clone()))
+    }
+
+    /// Get seconds played of current song
+    pub fn position(&self) -> f64 {
+        self.player.as_ref().map_or(0.0, |p| {
+            let t = p.play_time().played_time();
+            t.seconds as f64 + t.frac
+        })
+    }
+
+    /// Seek current song to seconds
+    pub fn seek(&self, secs: f64) {
+        if let Some(p) = self.player.as_ref() {
+            p.seek_to(Time::from(secs.max(0.0)));
+        }
+    }
+
+    /// Get snapshot of playing state for remote controls
+    pub fn status(&self) -> PlayerStatus {
+        PlayerStatus {
+            state: self.state,
+            position: self.position(),
+            volume: self.get_gain(),
+            index: self.queue.current_index(),
+            song_id: self.queue.current_index().and_then(|i| self.queue.id(i)),
+            next_song_id: self
+                .queue
+                .current_index()
+                .and_then(|i| self.queue.id(i + 1)),
+            queue_len: self.queue.len(),
+            queue_version: self.queue.version(),
+            song: self.current().map(SongInfo::from_music),
+            dsp: self.dsp.stages(),
+        }
+    }
+
+    /// Apply a command of remote controls
+    pub fn apply_command(&mut self, command: RemoteCommand) -> Result<(), anyhow::Error> {
+        match command {
+            RemoteCommand::Play => self.resume()?,
+            RemoteCommand::Pause if self.state == PlayState::Playing => self.pause(),
+            RemoteCommand::Pause => (),
+            RemoteCommand::PlayPause if self.state == PlayState::Playing => self.pause(),
+            RemoteCommand::PlayPause => self.resume()?,
+            RemoteCommand::Stop => self.stop(),
+            RemoteCommand::Next => self.next()?,
+            RemoteCommand::Previous => self.previous()?,
+            RemoteCommand::SeekBy(offset) => {
+                let target = self.position() + offset;
+                let duration = self
+                    .current()
+                    .map_or(0.0, |m| m.get_duration().as_secs_f64());
+                // seeking over the end goes to next song
+                if duration > 0.0 && target >= duration {
+                    self.next()?;
+                } else {
+                    self.seek(target);
+                }
+            }
+            RemoteCommand::SeekTo(secs) => self.seek(secs),
+            RemoteCommand::SetVolume(volume) => self.set_gain(volume.clamp(0.0, 1.0)),
+            RemoteCommand::SetBalance(balance) => self.set_balance(balance),
+            RemoteCommand::EditDsp(edit) => self.edit_dsp(edit)?,
+            RemoteCommand::SetOutputDevice(name) => {
+                let device = match name {
+                    Some(name) => Some(
+                        self.output_devices()
+                            .into_iter()
+                            .map(|d| d.device)
+                            .find(|d| d.name == name)
+                            .ok_or_else(|| anyhow::anyhow!("output device {} not found", name))?,
+                    ),
+                    None => None,
+                };
+                self.set_output_device(device)?
+            }
+            RemoteCommand::Enqueue(paths) => {
+                for path in paths {
+                    self.append(path)?;
+                }
+            }
+            RemoteCommand::Open(paths) => {
+                for path in paths {
+                    if loader::is_playlist(&path) {
+                        self.load_playlist(&path)?;
+                    } else {
+                        self.append(path)?;
+                    }
+                }
+            }
+            RemoteCommand::PlayAt(index) => self.play_at(index)?,
+            RemoteCommand::Remove(index) => self.remove(index)?,
+            RemoteCommand::Move(from, to) => {
+                if !self.move_item(from, to) {
+                    anyhow::bail!("index out of queue");
+                }
+            }
+            RemoteCommand::Clear => self.clear(),
+            RemoteCommand::GetQueue(reply) => {
+                let songs = self
+                    .queue
+                    .list()
+                    .iter()
+                    .enumerate()
+                    .map(|(ix, music)| QueueSong {
+                        id: self.queue.id(ix).unwrap_or_default(),
+                        changed: self.queue.changed(ix).unwrap_or_default(),
+                        song: SongInfo::from_music(music),
+                    })
+                    .collect();
+                let _ = reply.try_send(songs);
+            }
+            RemoteCommand::GetLibrary(reply) => {
+                let _ = reply.try_send(LibrarySnapshot {
+                    library: self.library.clone(),
+                    folders: self.config.library_folders.clone(),
+                });
+            }
+            RemoteCommand::GetStatus(reply) => {
+                let _ = reply.try_send(self.status());
+            }
+        }
+        Ok(())
+    }
+
+    /// Set stereo balance, -1.0 is left only and 1.0 is right only
+    pub fn set_balance(&self, balance: f32) {
+        self.balance
+            .store(balance.clamp(-1.0, 1.0), Ordering::Relaxed);
+    }
+
+    /// Get processors of chain in order
+    pub fn dsp_stages(&self) -> Vec<DspStage> {
+        self.dsp.stages()
+    }
+
+    /// Add, remove, reorder or bypass a processor while playing, and save the chain
+    pub fn edit_dsp(&mut self, edit: DspEdit) -> Result<(), anyhow::Error> {
+        if !self.dsp.apply(edit) {
+            anyhow::bail!("no processor at index of chain");
+        }
+        self.config.dsp_chain = Some(self.dsp.stages());
+        self.config.save()
+    }
+
+    /// Apply equalizer preset, the flat one bypasses equalizer processors
+    pub fn apply_eq_preset(&mut self, preset: Preset) -> Result<(), anyhow::Error> {
+        self.equalizer.apply_preset(preset);
+        let bypassed = preset == Preset::Flat;
+        for (index, stage) in self.dsp_stages().into_iter().enumerate() {
+            if stage.kind == DspKind::Equalizer && stage.bypassed != bypassed {
+                self.edit_dsp(DspEdit::Bypass { index, bypassed })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// List output devices of all hosts with their supported configs
+    pub fn output_devices(&self) -> Vec<DeviceInfo> {
+        CpalOutput::list_devices()
+    }
+
+    /// Get chosen output device, None means default device
+    pub fn output_device(&self) -> Option<&OutputDevice> {
+        self.config.output_device.as_ref()
+    }
+
+    /// Choose output device and save it, the playing song moves to it.
+    /// None to use default device.
+    pub fn set_output_device(&mut self, device: Option<OutputDevice>) -> Result<(), anyhow::Error> {
+        if let Some(d) = &device
+            && CpalOutput::find_device(d).is_none()
+        {
+            return Err(anyhow::anyhow!("output device {} not found", d.name));
+        }
+        self.config.output_device = device.clone();
+        self.config.save()?;
+        self.set_output(OutputKind::Device(device))
+    }
+
+    /// Change sink of players, like writing into a wav file.
+    /// The playing song moves to it, but it is not saved.
+    pub fn set_output(&mut self, output: OutputKind) -> Result<(), anyhow::Error> {
+        self.output = output;
+        self.reopen_player()
+    }
+
+    pub fn library(&self) -> &Arc<Library> {
+        &self.library
+    }
+
+    pub fn library_folders(&self) -> &[PathBuf] {
+        &self.config.library_folders
+    }
+
+    /// Add a folder into library and save it, scan_library to read its musics
+    pub fn add_library_folder(&mut self, folder: PathBuf) -> Result<(), anyhow::Error> {
+        if !folder.is_dir() {
+            return Err(anyhow::anyhow!("{} is not a folder", folder.display()));
+        }
+        if !self.config.library_folders.contains(&folder) {
+            self.config.library_folders.push(folder);
+            self.config.save()?;
+        }
+        Ok(())
+    }
+
+    /// Scan library folders on a background thread, only new or changed files are read.
+    /// Pass the changes of Finished event to apply_library_changes.
+    pub fn scan_library(&self) -> Receiver<LibraryEvents> {
+        scanner::scan_folders(self.config.library_folders.clone(), self.library.stamps())
+    }
+
+    /// Measure loudness of musics on a background thread and write ReplayGain tags.
+    /// Albums already tagged are skipped, the written files are reloaded by library watcher.
+    pub fn scan_loudness(&self, paths: Vec<PathBuf>) -> Receiver<ScanEvents> {
+        loudness_scanner::scan_paths(paths, true, true)
+    }
+
+    /// Watch library folders for changes, replacing the previous watcher.
+    /// Pass the changes of Changed events to apply_library_changes.
+    pub fn watch_library(&mut self) -> Result<Receiver<LibraryEvents>, anyhow::Error> {
+        self.watcher = None;
+        let (watcher, receiver) = FolderWatcher::new(&self.config.library_folders)?;
+        self.watcher = Some(watcher);
+        Ok(receiver)
+    }
+
+    /// Apply changes of library scan or watcher, index is saved with stats by flush_stats.
+    /// Songs in queue follow renamed files and reload tags of modified ones.
+    pub fn apply_library_changes(&mut self, changes: ScanChanges) {
+        if changes.is_empty() {
+            return;
+        }
+        for index in 0..self.queue.len() {
+            let Some(music) = self.queue.get_mut(index) else {
+                continue;
+            };
+            for (from, to) in &changes.renamed {
+                if let Some(path) = library::moved_path(music.get_path(), from, to) {
+                    music.set_path(path);
+                }
+            }
+            // tracks of CUE sheets keep tags and length given by their sheet
+            if music.get_range().is_none()
+                && changes.updated.iter().any(|t| &t.path == music.get_path())
+                && let Err(e) = music.read_tags()
+            {
+                eprintln!("failed to reload tags: {}", e);
+            }
+        }
+        // batched plays are counted before their files are renamed
+        let library = Arc::make_mut(&mut self.library);
+        for (path, time) in self.pending_plays.drain(..) {
+            library.record_play(&path, time);
+        }
+        library.apply(changes);
+        self.library_dirty = true;
+    }
+
+    /// Rate a track of library from 0 to 5, None to clear.
+    /// Saved with play stats by flush_stats
+    pub fn set_rating(&mut self, path: &Path, rating: Option<u8>) -> Result<(), anyhow::Error> {
+        if self.library.track(path).is_none() {
+            return Err(anyhow::anyhow!("{} is not in library", path.display()));
+        }
+        Arc::make_mut(&mut self.library).set_rating(path, rating);
+        self.library_dirty = true;
+        Ok(())
+    }
+
+    /// Whether play stats, ratings or scan changes are waiting for flush_stats
+    pub fn has_pending_stats(&self) -> bool {
+        self.library_dirty || !self.pending_plays.is_empty()
+    }
+
+    /// Count batched plays into library, return library to be saved if it changed.
+    /// Saving is left to caller, so that it can be done in background
+    pub fn flush_stats(&mut self) -> Option<Arc<Library>> {
+        if !self.pending_plays.is_empty() {
+            let library = Arc::make_mut(&mut self.library);
+            for (path, time) in self.pending_plays.drain(..) {
+                library.record_play(&path, time);
+            }
+            self.library_dirty = true;
+        }
+        if !self.library_dirty {
+            return None;
+        }
+        self.library_dirty = false;
+        Some(self.library.clone())
+    }
+
+    pub fn smart_playlists(&self) -> &[SmartPlaylist] {
+        &self.smart_playlists
+    }
+
+    /// Error of loading smart playlists file, None if it was loaded
+    pub fn smart_playlists_error(&self) -> Option<&str> {
+        self.smart_playlists_error.as_deref()
+    }
+
+    /// Refuse changes which would overwrite smart playlists file failed to load
+    fn check_smart_playlists(&self) -> Result<(), anyhow::Error> {
+        match &self.smart_playlists_error {
+            Some(e) => anyhow::bail!("smart playlists are not saved, fix or remove file: {}", e),
+            None => Ok(()),
+        }
+    }
+
+    /// Replace smart playlist at index, or add it if index is None. Return its index
+    pub fn save_smart_playlist(
+        &mut self,
+        index: Option<usize>,
+        playlist: SmartPlaylist,
+    ) -> Result<usize, anyhow::Error> {
+        self.check_smart_playlists()?;
+        if playlist.name.trim().is_empty() {
+            return Err(anyhow::anyhow!("playlist name is empty"));
+        }
+        let index = match index {
+            Some(ix) => {
+                let slot = self
+                    .smart_playlists
+                    .get_mut(ix)
+                    .ok_or_else(|| anyhow::anyhow!("playlist index out of range"))?;
+                *slot = playlist;
+                ix
+            }
+            None => {
+                self.smart_playlists.push(playlist);
+                self.smart_playlists.len() - 1
+            }
+        };
+        smart_playlist::save(&self.smart_playlists)?;
+        Ok(index)
+    }
+
+    pub fn remove_smart_playlist(&mut self, index: usize) -> Result<(), anyhow::Error> {
+        self.check_smart_playlists()?;
+        if index >= self.smart_playlists.len() {
+            return Err(anyhow::anyhow!("playlist index out of range"));
+        }
+        self.smart_playlists.remove(index);
+        smart_playlist::save(&self.smart_playlists)
+    }
+
+    /// Count a play of current song if it is in library, counted in batch by flush_stats
+    fn record_play(&mut self) {
+        let Some(path) = self.queue.current().map(|m| m.get_path().clone()) else {
+            return;
+        };
+        if self.library.track(&path).is_some() {
+            self.pending_plays.push((path, utils::now_millis()));
+        }
+    }
+
+    /// Set crossfade seconds between songs and save it, 0 to disable
+    pub fn set_crossfade(&mut self, secs: f32) -> Result<(), anyhow::Error> {
+        let secs = secs.max(0.0);
+        self.crossfade_secs.store(secs, Ordering::Relaxed);
+        self.config.crossfade_secs = secs;
+        self.config.save()
+    }
+
+    pub fn get_crossfade(&self) -> f32 {
+        self.crossfade_secs.load(Ordering::Relaxed)
+    }
+
+    /// Set loudness normalization mode and preamp (dB) and save them, applied from next song
+    pub fn set_replay_gain(
+        &mut self,
+        mode: ReplayGainMode,
+        preamp: f32,
+    ) -> Result<(), anyhow::Error> {
+        self.replay_gain_mode = mode;
+        self.replay_gain_preamp = preamp;
+        self.preload_next();
+        self.config.replay_gain_mode = mode;
+        self.config.replay_gain_preamp = preamp;
+        self.config.save()
+    }
+
+    pub fn get_replay_gain(&self) -> (ReplayGainMode, f32) {
+        (self.replay_gain_mode, self.replay_gain_preamp)
+    }
+}
+
+impl Drop for Core {
+    fn drop(&mut self) {
+        // save stats batched since last flush
+        if let Some(library) = self.flush_stats()
+            && let Err(e) = library.save()
+        {
+            eprintln!("failed to save library: {}", e);
+        }
+    }
 }
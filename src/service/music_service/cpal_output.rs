@@ -0,0 +1,177 @@
+use anyhow::anyhow;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{ChannelCount, SampleRate};
+use smol::channel::Sender;
+
+use crate::service::music_service::{
+    models::{DeviceConfig, DeviceInfo, Events, OutputDevice},
+    output::{Output, OutputBuffer},
+};
+
+/// The struct storage Output device
+pub struct CpalOutput {
+    pub supported_config: cpal::StreamConfig,
+    pub stream: cpal::Stream,
+    // chosen device is missing, default device is used
+    pub fallback: bool,
+}
+
+impl CpalOutput {
+    /// Create a output device, use default device if chosen one is None or missing.
+    /// Device lost event will be sent by sender.
+    pub fn new(
+        mut buffer: OutputBuffer,
+        output_device: Option<&OutputDevice>,
+        target_sample_rate: SampleRate,
+        target_channels: ChannelCount,
+        sender: Sender<Events>,
+    ) -> Result<Self, anyhow::Error> {
+        let found = output_device.and_then(Self::find_device);
+        let fallback = output_device.is_some() && found.is_none();
+        let device = match found {
+            Some((_, device)) => device,
+            None => cpal::default_host()
+                .default_output_device()
+                .ok_or(anyhow!("no output device available"))?,
+        };
+
+        // init config
+        let supported_config;
+        let supported_configs_range = device.supported_output_configs()?;
+        // find a config for device have suitable sample rate, prefer same channels
+        let suitable_configs: Vec<_> = supported_configs_range
+            .filter(|config| {
+                config.min_sample_rate() <= target_sample_rate
+                    && target_sample_rate <= config.max_sample_rate()
+            })
+            .collect();
+        let support_config_range = suitable_configs
+            .iter()
+            .find(|config| config.channels() == target_channels)
+            .or(suitable_configs.first())
+            .cloned();
+        if let Some(config) = support_config_range {
+            // have suitable sample rate
+            supported_config = config.with_sample_rate(target_sample_rate).config();
+        } else {
+            // do not have suitable sample rate, use max sample rate
+            let mut supported_configs_range = device.supported_output_configs()?;
+            supported_config = supported_configs_range
+                .next()
+                .ok_or(anyhow!("no supported config"))?
+                .with_max_sample_rate()
+                .config();
+        }
+
+        // build output stream
+        let device_name = device.name().unwrap_or_default();
+        let mut lost = false;
+        let stream = device.build_output_stream(
+            &supported_config,
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                let r_lenth = buffer.read(data);
+                for sample in &mut data[r_lenth..] {
+                    *sample = 0.0;
+                }
+            },
+            move |err| match err {
+                // only report once, the stream will be rebuilt
+                cpal::StreamError::DeviceNotAvailable if !lost => {
+                    lost = true;
+                    if let Err(e) = sender.try_send(Events::DeviceLost(device_name.clone())) {
+                        eprintln!("error when send event: {}", e);
+                    }
+                }
+                err => eprintln!("error: {}", err),
+            },
+            None,
+        )?;
+
+        Ok(Self {
+            supported_config,
+            stream,
+            fallback,
+        })
+    }
+
+    /// List output devices of all available hosts
+    pub fn list_devices() -> Vec<DeviceInfo> {
+        let mut list = Vec::new();
+        for id in cpal::available_hosts() {
+            let Ok(host) = cpal::host_from_id(id) else {
+                continue;
+            };
+            let default_name = host.default_output_device().and_then(|d| d.name().ok());
+            let Ok(devices) = host.output_devices() else {
+                continue;
+            };
+            for device in devices {
+                let Ok(name) = device.name() else {
+                    continue;
+                };
+                let configs = device
+                    .supported_output_configs()
+                    .map(|configs| {
+                        configs
+                            .map(|c| DeviceConfig {
+                                channels: c.channels(),
+                                min_sample_rate: c.min_sample_rate().0,
+                                max_sample_rate: c.max_sample_rate().0,
+                                sample_format: c.sample_format().to_string(),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                list.push(DeviceInfo {
+                    is_default: default_name.as_ref() == Some(&name),
+                    device: OutputDevice {
+                        host: id.name().to_string(),
+                        name,
+                    },
+                    configs,
+                });
+            }
+        }
+        list
+    }
+
+    /// Find chosen device by host and name
+    pub fn find_device(output_device: &OutputDevice) -> Option<(cpal::Host, cpal::Device)> {
+        let id = cpal::available_hosts()
+            .into_iter()
+            .find(|id| id.name() == output_device.host)?;
+        let host = cpal::host_from_id(id).ok()?;
+        let device = host
+            .output_devices()
+            .ok()?
+            .find(|d| d.name().is_ok_and(|n| n == output_device.name))?;
+        Some((host, device))
+    }
+}
+
+impl Output for CpalOutput {
+    fn sample_rate(&self) -> u32 {
+        self.supported_config.sample_rate.0
+    }
+
+    fn channels(&self) -> usize {
+        self.supported_config.channels as usize
+    }
+
+    fn play(&self) {
+        // device may be lost
+        if let Err(e) = self.stream.play() {
+            eprintln!("error: {}", e);
+        }
+    }
+
+    fn pause(&self) {
+        if let Err(e) = self.stream.pause() {
+            eprintln!("error: {}", e);
+        }
+    }
+
+    fn fallback(&self) -> bool {
+        self.fallback
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::service::music_service::models;
+
+/// A track of CUE sheet
+#[derive(Clone, Debug, Default)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    // position of INDEX 01 inside its file
+    pub start: Duration,
+}
+
+/// An audio file of CUE sheet and tracks inside it
+#[derive(Clone, Debug, Default)]
+pub struct CueFile {
+    // path as written, resolved by `CueSheet::read`
+    pub path: PathBuf,
+    pub tracks: Vec<CueTrack>,
+}
+
+/// A parsed CUE sheet, describes tracks of one or more album image files
+#[derive(Clone, Debug, Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub genre: Option<String>,
+    pub date: Option<String>,
+    pub files: Vec<CueFile>,
+}
+
+impl CueSheet {
+    /// Parse CUE text, tracks without INDEX 01 and data tracks are dropped.
+    /// Paths are kept as written
+    pub fn parse(text: &str) -> Self {
+        let mut sheet = CueSheet::default();
+        let mut track: Option<PendingTrack> = None;
+        for line in text.lines() {
+            let line = line.trim();
+            let (command, rest) = match line.split_once(char::is_whitespace) {
+                Some((c, r)) => (c.to_ascii_uppercase(), r.trim()),
+                None => (line.to_ascii_uppercase(), ""),
+            };
+            match command.as_str() {
+                "FILE" => {
+                    sheet.finish_track(track.take());
+                    sheet.files.push(CueFile {
+                        path: PathBuf::from(file_name(rest)),
+                        tracks: Vec::new(),
+                    });
+                }
+                "TRACK" => {
+                    sheet.finish_track(track.take());
+                    let mut words = rest.split_whitespace();
+                    let number = words.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+                    let audio = words.next().is_none_or(|t| t.eq_ignore_ascii_case("AUDIO"));
+                    track = Some(PendingTrack {
+                        track: CueTrack {
+                            number,
+                            ..Default::default()
+                        },
+                        audio,
+                        indexed: false,
+                    });
+                }
+                "INDEX" => {
+                    let mut words = rest.split_whitespace();
+                    let is_start = words.next().and_then(|n| n.parse::<u32>().ok()) == Some(1);
+                    if let Some(t) = track.as_mut()
+                        && is_start
+                        && let Some(start) = words.next().and_then(parse_time)
+                    {
+                        t.track.start = start;
+                        t.indexed = true;
+                    }
+                }
+                "TITLE" => {
+                    let title = unquote(rest);
+                    match track.as_mut() {
+                        Some(t) => t.track.title = title,
+                        None => sheet.title = title,
+                    }
+                }
+                "PERFORMER" => {
+                    let performer = unquote(rest);
+                    match track.as_mut() {
+                        Some(t) => t.track.performer = performer,
+                        None => sheet.performer = performer,
+                    }
+                }
+                "REM" => {
+                    let (key, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    if key.eq_ignore_ascii_case("GENRE") {
+                        sheet.genre = unquote(value);
+                    } else if key.eq_ignore_ascii_case("DATE") {
+                        sheet.date = unquote(value);
+                    }
+                }
+                _ => (),
+            }
+        }
+        sheet.finish_track(track);
+        sheet.files.retain(|f| !f.tracks.is_empty());
+        sheet
+    }
+
+    /// Read CUE file, paths of audio files are resolved against its folder.
+    /// Files written with another extension are found by name, like `.wav` ripped then encoded to `.flac`
+    pub fn read(path: &Path) -> Result<Self, anyhow::Error> {
+        let mut sheet = Self::parse(&decode(&fs::read(path)?));
+        let base = path.parent().unwrap_or(Path::new(""));
+        for file in &mut sheet.files {
+            let written = if cfg!(windows) {
+                file.path.clone()
+            } else {
+                // sheets written on windows use backslash
+                PathBuf::from(file.path.to_string_lossy().replace('\\', "/"))
+            };
+            file.path = find_audio(&base.join(written));
+        }
+        Ok(sheet)
+    }
+
+    /// All tracks of sheet, like of a sheet embedded in the file it describes
+    pub fn tracks(&self) -> Vec<CueTrack> {
+        self.files.iter().flat_map(|f| f.tracks.clone()).collect()
+    }
+
+    /// Keep track if it can be played
+    fn finish_track(&mut self, pending: Option<PendingTrack>) {
+        let Some(PendingTrack {
+            track,
+            audio: true,
+            indexed: true,
+        }) = pending
+        else {
+            return;
+        };
+        match self.files.last_mut() {
+            Some(file) => file.tracks.push(track),
+            // broken sheet without FILE
+            None => self.files.push(CueFile {
+                path: PathBuf::new(),
+                tracks: vec![track],
+            }),
+        }
+    }
+}
+
+/// A track being parsed
+struct PendingTrack {
+    track: CueTrack,
+    // data tracks are not played
+    audio: bool,
+    // whether INDEX 01 is read
+    indexed: bool,
+}
+
+/// Whether file is a CUE sheet
+pub fn is_cue<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    path.as_ref()
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+}
+
+/// Path of one track of a sheet or a file with embedded sheet, like `album.cue#3`.
+/// Playlists keep single tracks by it
+pub fn track_path(path: &Path, number: u32) -> PathBuf {
+    let mut text = path.as_os_str().to_os_string();
+    text.push(format!("#{}", number));
+    PathBuf::from(text)
+}
+
+/// Sheet or file and track number of path given by track_path, None for other paths
+pub fn split_track_path(path: &Path) -> Option<(PathBuf, u32)> {
+    // a file may be named like a track path
+    if path.is_file() {
+        return None;
+    }
+    let (file, number) = path.to_str()?.rsplit_once('#')?;
+    Some((PathBuf::from(file), number.parse().ok()?))
+}
+
+/// Parse time like `03:25:40`, minutes, seconds and frames of 1/75 second
+fn parse_time(s: &str) -> Option<Duration> {
+    let mut parts = s.split(':').map(|p| p.parse::<u64>().ok());
+    let (Some(Some(m)), Some(Some(sec)), Some(Some(f)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let frames = (m * 60 + sec) * 75 + f;
+    Some(Duration::from_nanos(frames * 1_000_000_000 / 75))
+}
+
+/// Value of command, quoted or the rest of line. None if empty
+fn unquote(s: &str) -> Option<String> {
+    let s = s.trim();
+    let value = match s.strip_prefix('"') {
+        Some(rest) => rest.split_once('"').map_or(rest, |(v, _)| v),
+        None => s,
+    };
+    Some(value.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// File name of FILE command, like `"album.flac" WAVE` or `album.flac WAVE`
+fn file_name(s: &str) -> String {
+    if s.starts_with('"') {
+        return unquote(s).unwrap_or_default();
+    }
+    // the last word is file type
+    match s.rsplit_once(char::is_whitespace) {
+        Some((name, _)) => name.trim().to_string(),
+        None => s.to_string(),
+    }
+}
+
+/// The path if it exists, otherwise a supported music with same name in the folder
+fn find_audio(path: &Path) -> PathBuf {
+    if path.is_file() {
+        return path.to_path_buf();
+    }
+    models::MUSIC_EXTENSIONS
+        .iter()
+        .map(|ext| path.with_extension(ext))
+        .find(|p| p.is_file())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Text of sheet, UTF-8 with or without BOM, otherwise Latin-1
+fn decode(data: &[u8]) -> String {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    match std::str::from_utf8(data) {
+        Ok(text) => text.to_string(),
+        Err(_) => data.iter().map(|&b| b as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp_dir::TempDir;
+
+    static SHEET: &str = r#"REM GENRE Rock
+REM DATE 1997
+PERFORMER "Radiohead"
+TITLE "OK Computer"
+FILE "ok computer.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Airbag"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Paranoid Android"
+    PERFORMER "Radiohead"
+    INDEX 00 04:40:10
+    INDEX 01 04:44:37
+  TRACK 03 MODE1/2352
+    INDEX 01 10:00:00
+FILE bonus.flac WAVE
+  TRACK 04 AUDIO
+    INDEX 00 00:00:00
+  TRACK 05 AUDIO
+    INDEX 01 01:02:75
+"#;
+
+    #[test]
+    fn parses_index_01_as_start() {
+        let sheet = CueSheet::parse(SHEET);
+        assert_eq!(sheet.title.as_deref(), Some("OK Computer"));
+        assert_eq!(sheet.genre.as_deref(), Some("Rock"));
+        assert_eq!(sheet.date.as_deref(), Some("1997"));
+        assert_eq!(sheet.files.len(), 2);
+        assert_eq!(sheet.files[0].path, PathBuf::from("ok computer.wav"));
+        assert_eq!(sheet.files[1].path, PathBuf::from("bonus.flac"));
+
+        // data track and track without INDEX 01 are dropped
+        let tracks = sheet.tracks();
+        let numbers: Vec<u32> = tracks.iter().map(|t| t.number).collect();
+        assert_eq!(numbers, [1, 2, 5]);
+        assert_eq!(tracks[0].start, Duration::ZERO);
+        assert_eq!(tracks[1].title.as_deref(), Some("Paranoid Android"));
+        // frames are 1/75 second
+        assert_eq!(tracks[1].start, Duration::from_nanos(284_493_333_333));
+        assert_eq!(tracks[2].start, Duration::from_secs(63));
+    }
+
+    #[test]
+    fn splits_track_paths() {
+        let path = track_path(Path::new("/music/album #2.cue"), 3);
+        assert_eq!(path, PathBuf::from("/music/album #2.cue#3"));
+        assert_eq!(
+            split_track_path(&path),
+            Some((PathBuf::from("/music/album #2.cue"), 3))
+        );
+        assert_eq!(split_track_path(Path::new("/music/a.cue#x")), None);
+        assert_eq!(split_track_path(Path::new("/music/a.cue")), None);
+
+        // existing files are never track paths
+        let dir = TempDir::new("cue_split");
+        let file = dir.file("song #1");
+        assert_eq!(split_track_path(&file), None);
+    }
+}
@@ -15,7 +15,7 @@ pub struct Decoder {
 impl Decoder {
     /// Decode from a file path
     pub fn decode_from_path(file_path: PathBuf) -> Result<Self, anyhow::Error> {
-        Ok(Self::decode_file(Box::new(File::open(file_path)?))?)
+        Self::decode_file(Box::new(File::open(file_path)?))
     }
 
     /// Decode from file
@@ -0,0 +1,363 @@
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+
+use atomic_float::AtomicF32;
+use serde::{Deserialize, Serialize};
+
+use crate::service::music_service::{
+    equalizer::{EqProcessor, Equalizer},
+    stream::Stream,
+};
+
+/// A stage of audio processing, running in decode thread
+pub trait DspProcessor: Send {
+    /// Process interleaved samples in place
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32);
+
+    /// Clear internal states, called after seek or music replaced
+    fn reset(&mut self) {}
+}
+
+/// Built-in processors a chain can hold
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DspKind {
+    Equalizer,
+    Balance,
+    Gain,
+    Limiter,
+}
+
+impl DspKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DspKind::Equalizer => "Equalizer",
+            DspKind::Balance => "Balance",
+            DspKind::Gain => "Gain",
+            DspKind::Limiter => "Limiter",
+        }
+    }
+}
+
+/// A processor in chain layout
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct DspStage {
+    pub kind: DspKind,
+    // skipped by chain if true, its state is kept
+    #[serde(default)]
+    pub bypassed: bool,
+}
+
+impl DspStage {
+    pub fn new(kind: DspKind) -> Self {
+        Self {
+            kind,
+            bypassed: false,
+        }
+    }
+}
+
+/// A change of chain layout
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DspEdit {
+    // index larger than length appends the processor
+    Insert { index: usize, kind: DspKind },
+    Remove { index: usize },
+    Move { from: usize, to: usize },
+    Bypass { index: usize, bypassed: bool },
+}
+
+/// Order of processors and their settings, edited by core during playing.
+/// Every player builds its own processors from it, so filter states are never shared.
+pub struct DspLayout {
+    stages: Mutex<Vec<DspStage>>,
+    // increased on every change of stages
+    version: AtomicU64,
+    equalizer: Arc<Equalizer>,
+    balance: Arc<AtomicF32>,
+    gain: Arc<AtomicF32>,
+}
+
+impl DspLayout {
+    /// Default order: equalizer -> balance -> gain -> limiter
+    pub const DEFAULT: [DspKind; 4] = [
+        DspKind::Equalizer,
+        DspKind::Balance,
+        DspKind::Gain,
+        DspKind::Limiter,
+    ];
+
+    /// Create layout of stages with shared settings
+    pub fn new(
+        stages: Vec<DspStage>,
+        equalizer: Arc<Equalizer>,
+        balance: Arc<AtomicF32>,
+        gain: Arc<AtomicF32>,
+    ) -> Self {
+        Self {
+            stages: Mutex::new(stages),
+            version: AtomicU64::new(0),
+            equalizer,
+            balance,
+            gain,
+        }
+    }
+
+    /// Get stages in order
+    pub fn stages(&self) -> Vec<DspStage> {
+        self.stages.lock().unwrap().clone()
+    }
+
+    /// Change stages, players follow it from their next block.
+    /// Return false if index is out of chain
+    pub fn apply(&self, edit: DspEdit) -> bool {
+        let mut stages = self.stages.lock().unwrap();
+        let len = stages.len();
+        match edit {
+            DspEdit::Insert { index, kind } => stages.insert(index.min(len), DspStage::new(kind)),
+            DspEdit::Remove { index } if index < len => {
+                stages.remove(index);
+            }
+            DspEdit::Move { from, to } if from < len && to < len => {
+                let stage = stages.remove(from);
+                stages.insert(to, stage);
+            }
+            DspEdit::Bypass { index, bypassed } if index < len => {
+                stages[index].bypassed = bypassed;
+            }
+            _ => return false,
+        }
+        self.version.fetch_add(1, Ordering::Release);
+        true
+    }
+
+    /// Create a processor of kind with shared settings
+    fn create(&self, kind: DspKind) -> Box<dyn DspProcessor> {
+        match kind {
+            DspKind::Equalizer => Box::new(EqProcessor::new(self.equalizer.clone())),
+            DspKind::Balance => Box::new(Balance::new(self.balance.clone())),
+            DspKind::Gain => Box::new(Gain::new(self.gain.clone())),
+            DspKind::Limiter => Box::new(Limiter::new(0.99, 0.1)),
+        }
+    }
+}
+
+/// Processors of a player, running in its decode thread.
+/// Rebuilt from layout when it changes, processors of kept stages keep their states.
+pub struct DspChain {
+    layout: Arc<DspLayout>,
+    // layout version of processors
+    version: Option<u64>,
+    processors: Vec<(DspStage, Box<dyn DspProcessor>)>,
+}
+
+impl DspChain {
+    /// Create chain following layout
+    pub fn new(layout: Arc<DspLayout>) -> Self {
+        Self {
+            layout,
+            version: None,
+            processors: Vec::new(),
+        }
+    }
+
+    /// Process interleaved samples with every processor in order
+    pub fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        self.sync();
+        for (stage, p) in self.processors.iter_mut() {
+            if !stage.bypassed {
+                p.process(samples, channels, sample_rate);
+            }
+        }
+    }
+
+    /// Reset every processor
+    pub fn reset(&mut self) {
+        for (_, p) in self.processors.iter_mut() {
+            p.reset();
+        }
+    }
+
+    /// Follow layout changes
+    fn sync(&mut self) {
+        if self.version == Some(self.layout.version.load(Ordering::Acquire)) {
+            return;
+        }
+        // version is bumped while stages locked, so it matches stages read here
+        let stages = self.layout.stages.lock().unwrap();
+        let version = self.layout.version.load(Ordering::Acquire);
+        let mut old = std::mem::take(&mut self.processors);
+        for stage in stages.iter() {
+            let p = match old.iter().position(|(s, _)| s.kind == stage.kind) {
+                Some(ix) => old.remove(ix).1,
+                None => self.layout.create(stage.kind),
+            };
+            self.processors.push((*stage, p));
+        }
+        self.version = Some(version);
+    }
+}
+
+/// Linear gain, ramped in a block to avoid clicks
+pub struct Gain {
+    gain: Arc<AtomicF32>,
+    current: f32,
+}
+
+impl Gain {
+    pub fn new(gain: Arc<AtomicF32>) -> Self {
+        let current = gain.load(Ordering::Relaxed);
+        Self { gain, current }
+    }
+}
+
+impl DspProcessor for Gain {
+    fn process(&mut self, samples: &mut [f32], channels: usize, _: u32) {
+        let target = self.gain.load(Ordering::Relaxed);
+        if target == 1.0 && self.current == 1.0 {
+            return;
+        }
+        let frames = (samples.len() / channels.max(1)).max(1);
+        let step = (target - self.current) / frames as f32;
+        for frame in samples.chunks_exact_mut(channels.max(1)) {
+            self.current += step;
+            for s in frame {
+                *s = Stream::apply_gain(*s, self.current);
+            }
+        }
+        self.current = target;
+    }
+}
+
+/// Stereo balance, -1.0 is left only and 1.0 is right only
+pub struct Balance {
+    balance: Arc<AtomicF32>,
+}
+
+impl Balance {
+    pub fn new(balance: Arc<AtomicF32>) -> Self {
+        Self { balance }
+    }
+}
+
+impl DspProcessor for Balance {
+    fn process(&mut self, samples: &mut [f32], channels: usize, _: u32) {
+        let balance = self.balance.load(Ordering::Relaxed).clamp(-1.0, 1.0);
+        if channels != 2 || balance == 0.0 {
+            return;
+        }
+        let left = (1.0 - balance).min(1.0);
+        let right = (1.0 + balance).min(1.0);
+        for frame in samples.chunks_exact_mut(2) {
+            frame[0] = Stream::apply_gain(frame[0], left);
+            frame[1] = Stream::apply_gain(frame[1], right);
+        }
+    }
+}
+
+/// Peak limiter keeping samples under threshold
+pub struct Limiter {
+    // linear threshold
+    threshold: f32,
+    // release time in seconds
+    release: f32,
+    // current gain reduction
+    envelope: f32,
+}
+
+impl Limiter {
+    pub fn new(threshold: f32, release: f32) -> Self {
+        Self {
+            threshold,
+            release,
+            envelope: 1.0,
+        }
+    }
+}
+
+impl DspProcessor for Limiter {
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        let release = (-1.0 / (self.release * sample_rate as f32)).exp();
+        for frame in samples.chunks_exact_mut(channels.max(1)) {
+            let peak = frame.iter().fold(0f32, |m, s| m.max(s.abs()));
+            let target = if peak > self.threshold {
+                self.threshold / peak
+            } else {
+                1.0
+            };
+            // attack immediately, release smoothly
+            self.envelope = if target < self.envelope {
+                target
+            } else {
+                target + (self.envelope - target) * release
+            };
+            for s in frame {
+                *s = Stream::apply_gain(*s, self.envelope);
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.envelope = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(gain: f32) -> Arc<DspLayout> {
+        Arc::new(DspLayout::new(
+            vec![DspStage::new(DspKind::Gain)],
+            Arc::new(Equalizer::new()),
+            Arc::new(AtomicF32::new(0.0)),
+            Arc::new(AtomicF32::new(gain)),
+        ))
+    }
+
+    #[test]
+    fn chains_follow_layout_edits() {
+        let layout = layout(0.5);
+        let mut chain = DspChain::new(layout.clone());
+        let mut samples = [1.0; 4];
+        chain.process(&mut samples, 2, 48000);
+        assert_eq!(samples, [0.5; 4]);
+
+        assert!(layout.apply(DspEdit::Bypass {
+            index: 0,
+            bypassed: true
+        }));
+        let mut samples = [1.0; 4];
+        chain.process(&mut samples, 2, 48000);
+        assert_eq!(samples, [1.0; 4]);
+
+        layout.apply(DspEdit::Insert {
+            index: 0,
+            kind: DspKind::Balance,
+        });
+        assert!(layout.apply(DspEdit::Move { from: 1, to: 0 }));
+        assert!(!layout.apply(DspEdit::Remove { index: 2 }));
+        let kinds: Vec<_> = layout.stages().iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, [DspKind::Gain, DspKind::Balance]);
+    }
+
+    #[test]
+    fn every_chain_has_its_own_processors() {
+        let layout = layout(1.0);
+        let mut first = DspChain::new(layout.clone());
+        let mut second = DspChain::new(layout.clone());
+        first.process(&mut [1.0; 4], 2, 48000);
+        second.process(&mut [1.0; 4], 2, 48000);
+
+        // a ramp running in one chain does not move the other one
+        layout.gain.store(0.0, Ordering::Relaxed);
+        first.process(&mut [1.0; 4], 2, 48000);
+        layout.gain.store(1.0, Ordering::Relaxed);
+        let mut samples = [1.0; 4];
+        second.process(&mut samples, 2, 48000);
+        assert_eq!(samples, [1.0; 4]);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    f32::consts::PI,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::service::music_service::dsp::DspProcessor;
+
+/// Filter type of equalizer band
+#[derive(PartialEq, Clone, Copy)]
+pub enum FilterType {
+    Peaking,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+}
+
+/// A band of equalizer, gain in dB
+#[derive(PartialEq, Clone, Copy)]
+pub struct Band {
+    pub filter: FilterType,
+    pub freq: f32,
+    pub gain: f32,
+    pub q: f32,
+}
+
+impl Band {
+    pub fn new(filter: FilterType, freq: f32, gain: f32, q: f32) -> Self {
+        Self {
+            filter,
+            freq,
+            gain,
+            q,
+        }
+    }
+}
+
+/// Built-in equalizer presets
+#[derive(PartialEq, Clone, Copy)]
+pub enum Preset {
+    Flat,
+    BassBoost,
+    TrebleBoost,
+    Vocal,
+    Loudness,
+    Warm,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 6] = [
+        Preset::Flat,
+        Preset::BassBoost,
+        Preset::TrebleBoost,
+        Preset::Vocal,
+        Preset::Loudness,
+        Preset::Warm,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Flat => "Flat",
+            Preset::BassBoost => "Bass Boost",
+            Preset::TrebleBoost => "Treble Boost",
+            Preset::Vocal => "Vocal",
+            Preset::Loudness => "Loudness",
+            Preset::Warm => "Warm",
+        }
+    }
+
+    /// Bands of preset
+    pub fn bands(&self) -> Vec<Band> {
+        use FilterType::*;
+        match self {
+            Preset::Flat => vec![],
+            Preset::BassBoost => vec![
+                Band::new(LowShelf, 100.0, 6.0, 0.707),
+                Band::new(Peaking, 250.0, 2.0, 1.0),
+            ],
+            Preset::TrebleBoost => vec![
+                Band::new(Peaking, 4000.0, 2.0, 1.0),
+                Band::new(HighShelf, 8000.0, 6.0, 0.707),
+            ],
+            Preset::Vocal => vec![
+                Band::new(HighPass, 80.0, 0.0, 0.707),
+                Band::new(Peaking, 300.0, -2.0, 1.0),
+                Band::new(Peaking, 2500.0, 4.0, 1.0),
+            ],
+            Preset::Loudness => vec![
+                Band::new(LowShelf, 80.0, 5.0, 0.707),
+                Band::new(Peaking, 2500.0, -1.5, 1.0),
+                Band::new(HighShelf, 10000.0, 4.0, 0.707),
+            ],
+            Preset::Warm => vec![
+                Band::new(LowShelf, 150.0, 3.0, 0.707),
+                Band::new(LowPass, 12000.0, 0.0, 0.707),
+            ],
+        }
+    }
+}
+
+/// Equalizer settings shared with decode thread, changes apply live
+pub struct Equalizer {
+    bands: Mutex<Vec<Band>>,
+    // increase when bands changed
+    version: AtomicU64,
+}
+
+impl Equalizer {
+    /// Create a flat equalizer
+    pub fn new() -> Self {
+        Self {
+            bands: Mutex::new(Vec::new()),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Get all bands
+    pub fn bands(&self) -> Vec<Band> {
+        self.bands.lock().unwrap().clone()
+    }
+
+    /// Replace all bands
+    pub fn set_bands(&self, bands: Vec<Band>) {
+        *self.bands.lock().unwrap() = bands;
+        self.version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Replace all bands with preset
+    pub fn apply_preset(&self, preset: Preset) {
+        self.set_bands(preset.bands());
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+}
+
+/// A biquad filter of one channel
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 3],
+    z: [f32; 2],
+}
+
+impl Biquad {
+    /// Compute coefficients of band at sample rate (RBJ audio EQ cookbook)
+    fn new(band: &Band, sample_rate: u32) -> Self {
+        // keep frequency below nyquist
+        let freq = band.freq.clamp(1.0, sample_rate as f32 * 0.49);
+        let w0 = 2.0 * PI * freq / sample_rate as f32;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * band.q.max(0.01));
+        let a = 10f32.powf(band.gain / 40.0);
+
+        let (b, a) = match band.filter {
+            FilterType::Peaking => (
+                [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+                [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+            ),
+            FilterType::LowShelf => {
+                let s = 2.0 * a.sqrt() * alpha;
+                (
+                    [
+                        a * ((a + 1.0) - (a - 1.0) * cos + s),
+                        2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                        a * ((a + 1.0) - (a - 1.0) * cos - s),
+                    ],
+                    [
+                        (a + 1.0) + (a - 1.0) * cos + s,
+                        -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                        (a + 1.0) + (a - 1.0) * cos - s,
+                    ],
+                )
+            }
+            FilterType::HighShelf => {
+                let s = 2.0 * a.sqrt() * alpha;
+                (
+                    [
+                        a * ((a + 1.0) + (a - 1.0) * cos + s),
+                        -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                        a * ((a + 1.0) + (a - 1.0) * cos - s),
+                    ],
+                    [
+                        (a + 1.0) - (a - 1.0) * cos + s,
+                        2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                        (a + 1.0) - (a - 1.0) * cos - s,
+                    ],
+                )
+            }
+            FilterType::LowPass => (
+                [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            FilterType::HighPass => (
+                [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+        };
+
+        // normalize by a0
+        Self {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [1.0, a[1] / a[0], a[2] / a[0]],
+            z: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[1] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[2] * y;
+        y
+    }
+}
+
+/// Equalizer filters running in decode thread
+pub struct EqProcessor {
+    equalizer: Arc<Equalizer>,
+    // settings version of filters
+    version: Option<u64>,
+    sample_rate: u32,
+    channels: usize,
+    // filters of every band, every channel
+    filters: Vec<Vec<Biquad>>,
+}
+
+impl EqProcessor {
+    /// Create processor of shared settings
+    pub fn new(equalizer: Arc<Equalizer>) -> Self {
+        Self {
+            equalizer,
+            version: None,
+            sample_rate: 0,
+            channels: 0,
+            filters: Vec::new(),
+        }
+    }
+
+    /// Recompute filters from bands, keep filter states if band count unchanged
+    fn update_filters(&mut self) {
+        let bands = self.equalizer.bands();
+        if bands.len() != self.filters.len() {
+            self.filters = bands
+                .iter()
+                .map(|b| vec![Biquad::new(b, self.sample_rate); self.channels])
+                .collect();
+            return;
+        }
+        for (band, filters) in bands.iter().zip(self.filters.iter_mut()) {
+            let new = Biquad::new(band, self.sample_rate);
+            for f in filters.iter_mut() {
+                f.b = new.b;
+                f.a = new.a;
+            }
+        }
+    }
+}
+
+impl DspProcessor for EqProcessor {
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        // rebuild filters if stream format changed
+        if self.channels != channels || self.sample_rate != sample_rate {
+            self.channels = channels;
+            self.sample_rate = sample_rate;
+            self.filters.clear();
+            self.version = None;
+        }
+        // recompute coefficients if bands changed
+        let version = self.equalizer.version();
+        if self.version != Some(version) {
+            self.version = Some(version);
+            self.update_filters();
+        }
+        if self.filters.is_empty() {
+            return;
+        }
+        for frame in samples.chunks_exact_mut(self.channels) {
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                *sample = self
+                    .filters
+                    .iter_mut()
+                    .fold(*sample, |x, band| band[ch].process(x));
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for band in &mut self.filters {
+            for f in band.iter_mut() {
+                f.z = [0.0; 2];
+            }
+        }
+    }
+}
@@ -1,10 +1,19 @@
 pub mod controller;
 pub mod core;
+pub mod cpal_output;
+pub mod cue;
 pub mod decoder;
+pub mod dsp;
+pub mod equalizer;
 pub mod models;
 pub mod music;
+pub mod null_output;
 pub mod output;
 pub mod player;
+pub mod queue;
+pub mod replaygain;
 pub mod service;
+pub mod source;
 pub mod stream;
 pub mod time;
+pub mod wav_output;
@@ -1,14 +1,89 @@
+use std::{path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use symphonia::core::units::Time;
+
 /// A struct for play state
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PlayState {
     Playing,
     Paused,
     Stopped,
 }
 
+/// A song handed to player with its play options
+pub struct PlayItem {
+    pub path: PathBuf,
+    // id of song in queue, reported back when output reaches it
+    pub id: u64,
+    // whether can be crossfaded with the song before it
+    pub crossfade: bool,
+    // loudness normalization gain factor
+    pub gain: f32,
+    // position to start from, None plays from beginning
+    pub start: Option<Time>,
+    // part of file to play, None plays the whole file
+    pub range: Option<TrackRange>,
+}
+
+/// Part of a file played as a song, like a track of CUE sheet
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct TrackRange {
+    // position in file where song starts
+    pub start: Duration,
+    // position in file where song ends, None plays to the end
+    pub end: Option<Duration>,
+}
+
+/// An output device chosen by user
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct OutputDevice {
+    pub host: String,
+    pub name: String,
+}
+
+/// Where the decoded stream goes
+#[derive(PartialEq, Clone, Debug)]
+pub enum OutputKind {
+    // sound device, None means default device
+    Device(Option<OutputDevice>),
+    // discard samples, at real-time pace or as fast as possible
+    Null { realtime: bool },
+    // write samples into a wav file
+    Wav(PathBuf),
+}
+
+/// A stream config range supported by device
+#[derive(Clone, Debug)]
+pub struct DeviceConfig {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: String,
+}
+
+/// An output device found on system
+#[derive(Clone, Debug)]
+pub struct DeviceInfo {
+    pub device: OutputDevice,
+    // whether it is default device of its host
+    pub is_default: bool,
+    pub configs: Vec<DeviceConfig>,
+}
+
 pub enum Events {
     PlaytimeRefresh,
+    // output reached the next song without gap, carries its queue id
+    TrackChanged(u64),
     PlayFinished,
+    // chosen output device is missing, default device is used
+    DeviceFallback(String),
+    // output device is unplugged or disabled
+    DeviceLost(String),
 }
 
-pub static RINGBUF_SIZE: usize = 48000 * 1;
+pub static RINGBUF_SIZE: usize = 48000;
+
+// file extensions of supported music formats
+pub static MUSIC_EXTENSIONS: &[&str] = &["mp3", "flac", "wav", "ogg", "oga", "m4a", "mp4", "aac"];
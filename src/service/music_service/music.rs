@@ -1,8 +1,18 @@
-use lofty::{file::TaggedFileExt, tag::Tag};
+use lofty::{
+    file::{AudioFile, TaggedFileExt},
+    tag::{Accessor, ItemKey, Tag, TagType},
+};
+
+use crate::service::music_service::{
+    cue::{self, CueSheet, CueTrack},
+    models::{self, TrackRange},
+    replaygain::ReplayGain,
+};
 use std::{
     fs::File,
     io,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 /// The music data struct
@@ -10,6 +20,11 @@ use std::{
 pub struct Music {
     path: PathBuf,
     tags: Option<Tag>,
+    duration: Duration,
+    // part of file played, like a track of CUE sheet
+    range: Option<TrackRange>,
+    // CUE sheet file the music is read from
+    sheet: Option<PathBuf>,
 }
 
 impl Music {
@@ -22,6 +37,9 @@ impl Music {
         let mut music = Self {
             path: path.as_ref().to_path_buf(),
             tags: None,
+            duration: Duration::ZERO,
+            range: None,
+            sheet: None,
         };
 
         // read metadata
@@ -29,6 +47,128 @@ impl Music {
         Ok(music)
     }
 
+    /// Create musics from path. A CUE sheet or a music with one embedded
+    /// gives a music for each of its tracks
+    pub fn load_all<P>(path: P) -> Result<Vec<Self>, anyhow::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if let Some((file, number)) = cue::split_track_path(path) {
+            let track = Self::load_all(&file)?
+                .into_iter()
+                .find(|m| m.range.is_some() && m.track_number() == Some(number));
+            return track
+                .map(|m| vec![m])
+                .ok_or_else(|| anyhow::anyhow!("no track {} in {}", number, file.display()));
+        }
+        if cue::is_cue(path) {
+            return Self::from_cue(path);
+        }
+        let music = Self::from_path(path)?;
+        let sheet = music
+            .get_tags()
+            .and_then(|t| t.get_string(&ItemKey::Unknown("CUESHEET".to_string())))
+            .map(CueSheet::parse);
+        let Some(sheet) = sheet else {
+            return Ok(vec![music]);
+        };
+        let tracks = sheet.tracks();
+        if tracks.is_empty() {
+            return Ok(vec![music]);
+        }
+        Ok(music.split(&sheet, &tracks))
+    }
+
+    /// Create musics of tracks in CUE sheet, files failed to open are skipped
+    pub fn from_cue(path: &Path) -> Result<Vec<Self>, anyhow::Error> {
+        let sheet = CueSheet::read(path)?;
+        let mut musics = Vec::new();
+        for file in &sheet.files {
+            match Self::from_path(&file.path) {
+                Ok(music) => {
+                    for mut track in music.split(&sheet, &file.tracks) {
+                        track.sheet = Some(path.to_path_buf());
+                        musics.push(track);
+                    }
+                }
+                Err(e) => eprintln!("failed to open {}: {}", file.path.display(), e),
+            }
+        }
+        if musics.is_empty() {
+            return Err(anyhow::anyhow!("no playable track in {}", path.display()));
+        }
+        Ok(musics)
+    }
+
+    /// Split music into tracks of sheet, each one ends where the next one starts.
+    /// Tags of file are kept for what sheet does not tell
+    fn split(&self, sheet: &CueSheet, tracks: &[CueTrack]) -> Vec<Self> {
+        let year = sheet
+            .date
+            .as_ref()
+            .and_then(|d| d.get(..4)?.parse::<u32>().ok());
+        tracks
+            .iter()
+            .enumerate()
+            .map(|(ix, track)| {
+                let end = tracks
+                    .get(ix + 1)
+                    .map(|t| t.start)
+                    .filter(|end| *end > track.start);
+                let mut tags = self
+                    .tags
+                    .clone()
+                    .unwrap_or_else(|| Tag::new(TagType::VorbisComments));
+                if let Some(title) = &track.title {
+                    tags.set_title(title.clone());
+                }
+                if let Some(artist) = track.performer.as_ref().or(sheet.performer.as_ref()) {
+                    tags.set_artist(artist.clone());
+                }
+                if tags.album().is_none()
+                    && let Some(album) = &sheet.title
+                {
+                    tags.set_album(album.clone());
+                }
+                if tags.genre().is_none()
+                    && let Some(genre) = &sheet.genre
+                {
+                    tags.set_genre(genre.clone());
+                }
+                if tags.year().is_none()
+                    && let Some(year) = year
+                {
+                    tags.set_year(year);
+                }
+                tags.set_track(track.number);
+                Self {
+                    path: self.path.clone(),
+                    tags: Some(tags),
+                    duration: end.unwrap_or(self.duration).saturating_sub(track.start),
+                    sheet: None,
+                    range: Some(TrackRange {
+                        start: track.start,
+                        end,
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    /// Whether file extension is a supported music format
+    pub fn is_supported<P>(path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        let Some(ext) = path.as_ref().extension().and_then(|e| e.to_str()) else {
+            return false;
+        };
+        models::MUSIC_EXTENSIONS
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(ext))
+    }
+
     /// Open music file
     pub fn open_file(&self) -> io::Result<File> {
         File::open(&self.path)
@@ -38,19 +178,66 @@ impl Music {
     pub fn read_tags(&mut self) -> Result<(), anyhow::Error> {
         let mut file = self.open_file()?;
         // Try to read music metas
-        if let Some(tags) = lofty::read_from(&mut file)?.primary_tag() {
+        let tagged = lofty::read_from(&mut file)?;
+        if let Some(tags) = tagged.primary_tag() {
             self.tags = Some(tags.clone());
         }
+        self.duration = tagged.properties().duration();
 
         Ok(())
     }
 
     /// Get tags reference of music if exists
     pub fn get_tags(&self) -> Option<&Tag> {
-        if let Some(t) = &self.tags {
-            return Some(&t);
+        self.tags.as_ref()
+    }
+
+    /// Get duration read with tags
+    pub fn get_duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Whether music is marked as gapless, which should never be crossfaded
+    pub fn is_gapless(&self) -> bool {
+        // tracks cut from one file are continuous
+        if self.range.is_some() {
+            return true;
+        }
+        let Some(tags) = self.get_tags() else {
+            return false;
+        };
+        ["ITUNESGAPLESS", "ITUNPGAP", "GAPLESS"].iter().any(|k| {
+            tags.get_string(&ItemKey::Unknown(k.to_string()))
+                .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        })
+    }
+
+    /// Get ReplayGain info from tags
+    pub fn replay_gain(&self) -> ReplayGain {
+        match self.get_tags() {
+            Some(t) => ReplayGain::from_tag(t),
+            None => ReplayGain::default(),
         }
-        None
+    }
+
+    /// Get track number from tags, tracks of CUE sheet always have it
+    pub fn track_number(&self) -> Option<u32> {
+        self.get_tags().and_then(|t| t.track())
+    }
+
+    /// Get the part of file played, None if the whole file is played
+    pub fn get_range(&self) -> Option<TrackRange> {
+        self.range
+    }
+
+    /// Get the CUE sheet file music is read from, None if not from a sheet file
+    pub fn get_sheet(&self) -> Option<&PathBuf> {
+        self.sheet.as_ref()
+    }
+
+    /// Change path of music, like when file is renamed
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.path = path;
     }
 
     /// Get the path reference of music
@@ -58,3 +245,17 @@ impl Music {
         &self.path
     }
 }
+
+#[cfg(test)]
+impl Music {
+    /// Music not read from file, for tests of what holds musics
+    pub fn untagged(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            tags: None,
+            duration: Duration::ZERO,
+            range: None,
+            sheet: None,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::service::music_service::output::{Output, OutputBuffer};
+
+// frames consumed at once
+static BLOCK_FRAMES: usize = 1024;
+
+/// A sink discarding samples, used when no sound is needed
+pub struct NullOutput {
+    sample_rate: u32,
+    channels: usize,
+    playing: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl NullOutput {
+    /// Create a null sink, consume at real-time pace or as fast as possible
+    pub fn new(
+        mut buffer: OutputBuffer,
+        sample_rate: u32,
+        channels: usize,
+        realtime: bool,
+    ) -> Self {
+        let playing = Arc::new(AtomicBool::new(false));
+        let closed = Arc::new(AtomicBool::new(false));
+        let (p, c) = (playing.clone(), closed.clone());
+        let handle = thread::spawn(move || {
+            let mut data = vec![0.0; BLOCK_FRAMES * channels];
+            let block = Duration::from_secs_f64(BLOCK_FRAMES as f64 / sample_rate as f64);
+            let mut next = Instant::now();
+            while !c.load(Ordering::Relaxed) {
+                if !p.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(10));
+                    next = Instant::now();
+                    continue;
+                }
+                let len = buffer.read(&mut data);
+                if realtime {
+                    // keep pace with clock instead of sleeping a fixed time
+                    next += block;
+                    thread::sleep(next.saturating_duration_since(Instant::now()));
+                } else if len == 0 {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        });
+        Self {
+            sample_rate,
+            channels,
+            playing,
+            closed,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Output for NullOutput {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn play(&self) {
+        self.playing.store(true, Ordering::Relaxed);
+    }
+
+    fn pause(&self) {
+        self.playing.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Drop for NullOutput {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
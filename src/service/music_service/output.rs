@@ -1,100 +1,104 @@
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
 
-use anyhow::anyhow;
-use atomic_float::AtomicF32;
 use cpal::SampleRate;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use ringbuf::traits::Observer;
 use ringbuf::{HeapCons, traits::Consumer};
+use smol::channel::Sender;
 
-use crate::service::music_service::stream::Stream;
+use crate::service::music_service::{
+    cpal_output::CpalOutput,
+    models::{Events, OutputKind},
+    null_output::NullOutput,
+    wav_output::WavOutput,
+};
 
-/// The struct storage Output device
-pub struct Output {
-    pub host: cpal::Host,
-    pub device: cpal::Device,
-    pub supported_config: cpal::StreamConfig,
-    pub stream: cpal::Stream,
-}
+/// A sink consuming the decoded stream
+pub trait Output {
+    /// Sample rate of the sink
+    fn sample_rate(&self) -> u32;
 
-impl Output {
-    /// Create a output device
-    pub fn new(
-        mut consumer: HeapCons<f32>,
-        target_sample_rate: SampleRate,
-        gain: Arc<AtomicF32>,
-        buf_occupied: Arc<AtomicUsize>,
-        clear_buf: Arc<AtomicBool>,
-    ) -> Result<Self, anyhow::Error> {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .ok_or(anyhow!("no output device available"))?;
+    /// Count of channels of the sink
+    fn channels(&self) -> usize;
 
-        // init config
-        let supported_config;
-        let mut supported_configs_range = device.supported_output_configs()?;
-        // find a config for device have suitable sample rate
-        let support_config_range = supported_configs_range.find(|config| {
-            config.min_sample_rate() <= target_sample_rate
-                && target_sample_rate <= config.max_sample_rate()
-        });
-        if let Some(config) = support_config_range {
-            // have suitable sample rate
-            supported_config = config.with_sample_rate(target_sample_rate).config();
-        } else {
-            // do not have suitable sample rate, use max sample rate
-            let mut supported_configs_range = device.supported_output_configs()?;
-            supported_config = supported_configs_range
-                .next()
-                .ok_or(anyhow!("no supported config"))?
-                .with_max_sample_rate()
-                .config();
-        }
-
-        // build output stream
-        let stream = device
-            .build_output_stream(
-                &supported_config,
-                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                    if clear_buf.load(Ordering::Relaxed) {
-                        consumer.clear();
-                        clear_buf.store(false, Ordering::Relaxed);
-                    }
+    /// Start stream output
+    fn play(&self);
 
-                    let g = gain.load(Ordering::Relaxed);
-                    let r_lenth = consumer.pop_slice(data);
-                    for sample in &mut data[..r_lenth] {
-                        *sample = Stream::apply_gain(*sample, g)
-                    }
-                    for sample in &mut data[r_lenth..] {
-                        *sample = 0.0;
-                    }
-                    buf_occupied.store(consumer.occupied_len(), Ordering::Relaxed);
-                },
-                move |err| {
-                    eprintln!("error: {}", err);
-                },
-                None,
-            )
-            .unwrap();
+    /// Pause stream output
+    fn pause(&self);
 
-        Ok(Self {
-            host: host,
-            device: device,
-            supported_config: supported_config,
-            stream: stream,
-        })
+    /// Whether chosen device is missing and default one is used
+    fn fallback(&self) -> bool {
+        false
     }
+}
 
-    /// Start stream output
-    pub fn play(&self) {
-        self.stream.play().unwrap();
+/// Read side of ringbuf, shared by all sinks
+pub struct OutputBuffer {
+    consumer: HeapCons<f32>,
+    // samples length consumed by output
+    consumed_len: Arc<AtomicU64>,
+    // samples before this position will be skipped
+    skip_to: Arc<AtomicU64>,
+}
+
+impl OutputBuffer {
+    pub fn new(
+        consumer: HeapCons<f32>,
+        consumed_len: Arc<AtomicU64>,
+        skip_to: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            consumer,
+            consumed_len,
+            skip_to,
+        }
     }
 
-    /// Pause stream output
-    pub fn pause(&self) {
-        self.stream.pause().unwrap();
+    /// Read samples into data, return length read
+    pub fn read(&mut self, data: &mut [f32]) -> usize {
+        let mut consumed = self.consumed_len.load(Ordering::Relaxed);
+        // skip samples which are discarded
+        let skip_to = self.skip_to.load(Ordering::Relaxed);
+        if consumed < skip_to {
+            consumed += self.consumer.skip((skip_to - consumed) as usize) as u64;
+        }
+
+        let r_lenth = self.consumer.pop_slice(data);
+        self.consumed_len
+            .store(consumed + r_lenth as u64, Ordering::Relaxed);
+        r_lenth
     }
 }
+
+/// Open the sink of kind, prefer stream format of the music
+pub fn open_output(
+    kind: &OutputKind,
+    buffer: OutputBuffer,
+    sample_rate: u32,
+    channels: u16,
+    sender: Sender<Events>,
+) -> Result<Box<dyn Output>, anyhow::Error> {
+    Ok(match kind {
+        OutputKind::Device(device) => Box::new(CpalOutput::new(
+            buffer,
+            device.as_ref(),
+            SampleRate(sample_rate),
+            channels,
+            sender,
+        )?),
+        OutputKind::Null { realtime } => Box::new(NullOutput::new(
+            buffer,
+            sample_rate,
+            channels as usize,
+            *realtime,
+        )),
+        OutputKind::Wav(path) => Box::new(WavOutput::new(
+            buffer,
+            path,
+            sample_rate,
+            channels as usize,
+        )?),
+    })
+}
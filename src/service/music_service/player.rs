@@ -1,88 +1,101 @@
 use atomic_float::AtomicF32;
-use cpal::SampleRate;
 use ringbuf::{storage::Heap, traits::Split};
-use smol::channel::Receiver;
-use std::path::PathBuf;
+use smol::channel::Sender;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::atomic::AtomicU64;
 use symphonia::core::units::Time;
 
-use crate::service::music_service::controller::Controller;
+use crate::service::music_service::controller::{Controller, QueuedMusic, ServiceState};
 use crate::service::music_service::decoder::Decoder;
-use crate::service::music_service::models::{self, Events};
-use crate::service::music_service::output::Output;
+use crate::service::music_service::dsp::DspLayout;
+use crate::service::music_service::models::{self, Events, OutputKind, PlayItem};
+use crate::service::music_service::output::{self, Output, OutputBuffer};
 use crate::service::music_service::service::Service;
 use crate::service::music_service::time::PlayTime;
 
-// reInit for every new song
+// keep output stream for songs played continuously
 pub struct Player {
-    // output device
-    output: Output,
+    // output sink
+    output: Box<dyn Output>,
     // decode controller
     controller: Arc<Controller>,
-    // current music track info
-    // track: Track,
     // current playtime
     play_time: Arc<PlayTime>,
-    // event receiver
-    receiver: Arc<Receiver<Events>>,
-
-    need_clear_buf: Arc<AtomicBool>,
 }
 
 impl Player {
     /// Create a new player
     /// Used to play a file, will create a decode thread and output thread.
-    pub fn new(file_path: PathBuf, gain: Arc<AtomicF32>) -> Result<Self, anyhow::Error> {
+    /// Events of the decode thread will be sent by sender.
+    /// If output device is missing, default one is used and a fallback event is sent.
+    pub fn new(
+        item: PlayItem,
+        output_kind: &OutputKind,
+        crossfade_secs: Arc<AtomicF32>,
+        dsp: Arc<DspLayout>,
+        sender: Sender<Events>,
+    ) -> Result<Self, anyhow::Error> {
         // setup ringbuf
         let rb = ringbuf::SharedRb::<Heap<f32>>::new(models::RINGBUF_SIZE);
         let (producer, consumer) = rb.split();
 
-        // create channel
-        let (tx, rx) = smol::channel::unbounded::<Events>();
         // create atomic counter
-        let decoded_len = Arc::new(AtomicU64::new(0));
-        let buf_occupied = Arc::new(AtomicUsize::new(0));
-        let need_clear_buf = Arc::new(AtomicBool::new(false));
+        let written_len = Arc::new(AtomicU64::new(0));
+        let consumed_len = Arc::new(AtomicU64::new(0));
+        let skip_to = Arc::new(AtomicU64::new(0));
         // decode file
-        let decoded = Decoder::decode_from_path(file_path)?;
+        let music = Self::queue_music(item)?;
+        let decoded = &music.decoder;
+        let channels = decoded
+            .format
+            .default_track()
+            .and_then(|t| t.codec_params.channels)
+            .map(|c| c.count())
+            .unwrap_or(2);
         // setup output
-        let output = Output::new(
-            consumer,
-            SampleRate(decoded.sample_rate),
-            gain.clone(),
-            buf_occupied.clone(),
-            need_clear_buf.clone(),
+        let buffer = OutputBuffer::new(consumer, consumed_len.clone(), skip_to.clone());
+        let output = output::open_output(
+            output_kind,
+            buffer,
+            decoded.sample_rate,
+            channels as u16,
+            sender.clone(),
         )?;
+        if output.fallback()
+            && let OutputKind::Device(Some(d)) = output_kind
+            && let Err(e) = sender.try_send(Events::DeviceFallback(d.name.clone()))
+        {
+            eprintln!("error when send event: {}", e);
+        }
         // create decoder controller
         let controller = Arc::new(Controller::new());
-        // clone track data
-        let track = decoded.format.default_track().unwrap().clone();
 
         // init play time
-        let play_time = Arc::new(
-            PlayTime::from_track(
-                track,
-                output.supported_config.sample_rate.0,
-                decoded_len.clone(),
-                buf_occupied.clone(),
-            )
-            .unwrap(),
-        );
+        let play_time = Arc::new(PlayTime::new(
+            output.sample_rate(),
+            output.channels(),
+            written_len,
+            consumed_len,
+        ));
 
         // create and run service thread
-        Service::new(decoded, producer, controller.clone(), play_time.clone())
-            .subscribe(tx)
-            .start_service()
-            .unwrap();
+        Service::new(
+            music,
+            producer,
+            controller.clone(),
+            play_time.clone(),
+            skip_to,
+            crossfade_secs,
+            dsp,
+        )?
+        .subscribe(sender)
+        .start_service()?;
 
         // return self
         Ok(Self {
             output,
             controller,
             play_time,
-            receiver: Arc::new(rx),
-            need_clear_buf,
         })
     }
 
@@ -91,14 +104,41 @@ impl Player {
         &self.play_time
     }
 
-    /// Get event receiver
-    pub fn receiver(&self) -> Arc<Receiver<Events>> {
-        self.receiver.clone()
+    /// Whether decode thread finished or stopped
+    pub fn is_stopped(&self) -> bool {
+        self.controller.state() == ServiceState::Stopped
+    }
+
+    /// Replace current song immediately, keep the output stream.
+    /// Fails if decode thread has exited, then a new player is required
+    pub fn load(&self, item: PlayItem) -> Result<(), anyhow::Error> {
+        self.controller.load(Self::queue_music(item)?)
+    }
+
+    /// Set the song to be played after current one without gap
+    pub fn set_next(&self, item: Option<PlayItem>) -> Result<(), anyhow::Error> {
+        let next = match item {
+            Some(i) => Some(Self::queue_music(i)?),
+            None => None,
+        };
+        self.controller.set_next(next);
+        Ok(())
+    }
+
+    /// Decode the song for decode thread
+    fn queue_music(item: PlayItem) -> Result<QueuedMusic, anyhow::Error> {
+        Ok(QueuedMusic {
+            decoder: Decoder::decode_from_path(item.path)?,
+            id: item.id,
+            crossfade: item.crossfade,
+            gain: item.gain,
+            start: item.start,
+            range: item.range,
+        })
     }
 
     pub fn seek_to(&self, seek_to: Time) {
         self.controller.seek_to(seek_to);
-        self.need_clear_buf.store(true, Ordering::Relaxed);
     }
 
     /// Start decode and output.
@@ -125,3 +165,141 @@ impl Drop for Player {
         self.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use smol::channel::{self, Receiver};
+
+    use super::*;
+    use crate::{service::music_service::equalizer::Equalizer, utils::temp_dir::TempDir};
+
+    static SAMPLE_RATE: u32 = 44100;
+    static CHANNELS: u16 = 2;
+    // one second
+    static FRAMES: u32 = 44100;
+
+    /// Write a 16-bit wav of a sine wave
+    fn write_wav(path: &Path) {
+        let block_align = CHANNELS * 2;
+        let data_len = FRAMES * block_align as u32;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&CHANNELS.to_le_bytes());
+        bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        bytes.extend_from_slice(&(SAMPLE_RATE * block_align as u32).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for frame in 0..FRAMES {
+            let t = frame as f32 / SAMPLE_RATE as f32;
+            let sample = ((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16;
+            for _ in 0..CHANNELS {
+                bytes.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn player(path: PathBuf, output: &OutputKind) -> (Player, Receiver<Events>) {
+        let item = PlayItem {
+            path,
+            id: 1,
+            crossfade: false,
+            gain: 1.0,
+            start: None,
+            range: None,
+        };
+        let dsp = Arc::new(DspLayout::new(
+            Vec::new(),
+            Arc::new(Equalizer::new()),
+            Arc::new(AtomicF32::new(0.0)),
+            Arc::new(AtomicF32::new(1.0)),
+        ));
+        let (sender, receiver) = channel::unbounded();
+        let player = Player::new(item, output, Arc::new(AtomicF32::new(0.0)), dsp, sender).unwrap();
+        (player, receiver)
+    }
+
+    fn secs(time: Time) -> f64 {
+        time.seconds as f64 + time.frac
+    }
+
+    /// Wait until condition holds, fail after a few seconds
+    fn wait_until(mut condition: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn wait_finished(events: &Receiver<Events>) {
+        wait_until(|| {
+            let mut finished = false;
+            while let Ok(event) = events.try_recv() {
+                finished |= matches!(event, Events::PlayFinished);
+            }
+            finished
+        });
+    }
+
+    #[test]
+    fn null_output_plays_to_the_end() {
+        let dir = TempDir::new("player_null");
+        let path = dir.join("sine.wav");
+        write_wav(&path);
+        let (player, events) = player(path, &OutputKind::Null { realtime: false });
+        player.play();
+        wait_finished(&events);
+        assert!(player.is_stopped());
+        assert_eq!(player.play_time().duration_sec(), 1);
+        assert!((secs(player.play_time().played_time()) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn play_time_follows_seek() {
+        let dir = TempDir::new("player_seek");
+        let path = dir.join("sine.wav");
+        write_wav(&path);
+        let (player, events) = player(path, &OutputKind::Null { realtime: false });
+        // output is paused, so play time stays where seek moved it
+        player.seek_to(Time::from(0.5));
+        wait_until(|| secs(player.play_time().played_time()) > 0.0);
+        assert!((secs(player.play_time().played_time()) - 0.5).abs() < 1e-3);
+
+        player.play();
+        wait_finished(&events);
+        assert!((secs(player.play_time().played_time()) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn wav_output_writes_every_sample() {
+        let dir = TempDir::new("player_wav");
+        let path = dir.join("sine.wav");
+        let written = dir.join("out.wav");
+        write_wav(&path);
+        let (player, events) = player(path, &OutputKind::Wav(written.clone()));
+        player.play();
+        wait_finished(&events);
+        // header is finished when output is dropped
+        drop(player);
+
+        let bytes = fs::read(&written).unwrap();
+        let data_len = u32::from_le_bytes(bytes[40..44].try_into().unwrap());
+        assert_eq!(bytes.len(), 44 + data_len as usize);
+        // 32-bit float samples
+        assert_eq!(data_len / 4, FRAMES * CHANNELS as u32);
+    }
+}
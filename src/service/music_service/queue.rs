@@ -0,0 +1,300 @@
+use crate::service::music_service::music::Music;
+
+/// The play queue, storage musics and the index of current playing one
+pub struct Queue {
+    list: Vec<Music>,
+    // ids and positions of musics, in the same order as list
+    slots: Vec<Slot>,
+    current: Option<usize>,
+    // increased when musics of queue changed
+    version: u64,
+    // id given to the next added music
+    next_id: u64,
+}
+
+/// Id of a music in queue and when it came to its position
+#[derive(Clone, Copy)]
+struct Slot {
+    // stays while music is in queue, never given again
+    id: u64,
+    // version of queue when music was added or moved
+    changed: u64,
+}
+
+impl Queue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            slots: Vec::new(),
+            current: None,
+            version: 0,
+            next_id: 0,
+        }
+    }
+
+    /// Get version of queue, changes when musics are added, removed or moved
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Get all musics in queue
+    pub fn list(&self) -> &[Music] {
+        &self.list
+    }
+
+    /// Get id of music at index, it stays the same while the music is in queue
+    pub fn id(&self, index: usize) -> Option<u64> {
+        self.slots.get(index).map(|s| s.id)
+    }
+
+    /// Get version of queue when music at index was added or moved to it
+    pub fn changed(&self, index: usize) -> Option<u64> {
+        self.slots.get(index).map(|s| s.changed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Get mutable music at index
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Music> {
+        self.list.get_mut(index)
+    }
+
+    /// Get index of current music
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Get current music if exists
+    pub fn current(&self) -> Option<&Music> {
+        self.list.get(self.current?)
+    }
+
+    /// Get the music after current one if exists
+    pub fn peek_next(&self) -> Option<&Music> {
+        match self.current {
+            Some(i) => self.list.get(i + 1),
+            None => self.list.first(),
+        }
+    }
+
+    /// Append a music to the end of queue
+    pub fn append(&mut self, music: Music) {
+        self.list.push(music);
+        self.version += 1;
+        let slot = self.new_slot();
+        self.slots.push(slot);
+    }
+
+    /// Insert a music at index, index larger than length will append it
+    pub fn insert(&mut self, index: usize, music: Music) {
+        let index = index.min(self.list.len());
+        self.list.insert(index, music);
+        self.version += 1;
+        let slot = self.new_slot();
+        self.slots.insert(index, slot);
+        self.touch(index, self.slots.len());
+        // keep current pointing to the same music
+        if let Some(c) = self.current
+            && index <= c
+        {
+            self.current = Some(c + 1);
+        }
+    }
+
+    /// Remove a music from queue, return the removed one
+    /// If current music is removed, current will be unset.
+    pub fn remove(&mut self, index: usize) -> Option<Music> {
+        if index >= self.list.len() {
+            return None;
+        }
+        let music = self.list.remove(index);
+        self.version += 1;
+        self.slots.remove(index);
+        self.touch(index, self.slots.len());
+        if let Some(c) = self.current {
+            if index == c {
+                self.current = None;
+            } else if index < c {
+                self.current = Some(c - 1);
+            }
+        }
+        Some(music)
+    }
+
+    /// Move a music from one index to another
+    pub fn move_item(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.list.len() || to >= self.list.len() {
+            return false;
+        }
+        let music = self.list.remove(from);
+        self.list.insert(to, music);
+        self.version += 1;
+        let slot = self.slots.remove(from);
+        self.slots.insert(to, slot);
+        self.touch(from.min(to), from.max(to) + 1);
+        // keep current pointing to the same music
+        if let Some(c) = self.current {
+            if c == from {
+                self.current = Some(to);
+            } else if from < c && c <= to {
+                self.current = Some(c - 1);
+            } else if to <= c && c < from {
+                self.current = Some(c + 1);
+            }
+        }
+        true
+    }
+
+    /// Remove all musics
+    pub fn clear(&mut self) {
+        self.list.clear();
+        self.slots.clear();
+        self.current = None;
+        self.version += 1;
+    }
+
+    /// Set current music by index, return the music if index is valid
+    pub fn set_current(&mut self, index: usize) -> Option<&Music> {
+        if index >= self.list.len() {
+            return None;
+        }
+        self.current = Some(index);
+        self.list.get(index)
+    }
+
+    /// Unset current music
+    pub fn reset_current(&mut self) {
+        self.current = None;
+    }
+
+    /// Move current to the next music, return it if exists
+    pub fn next(&mut self) -> Option<&Music> {
+        let index = match self.current {
+            Some(i) => i + 1,
+            None => 0,
+        };
+        self.set_current(index)
+    }
+
+    /// Slot of a music added in this version
+    fn new_slot(&mut self) -> Slot {
+        self.next_id += 1;
+        Slot {
+            id: self.next_id,
+            changed: self.version,
+        }
+    }
+
+    /// Mark musics from start to end as moved in this version
+    fn touch(&mut self, start: usize, end: usize) {
+        for slot in &mut self.slots[start..end] {
+            slot.changed = self.version;
+        }
+    }
+
+    /// Move current to the previous music, return it if exists
+    pub fn previous(&mut self) -> Option<&Music> {
+        let index = self.current?.checked_sub(1)?;
+        self.set_current(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(paths: &[&str]) -> Queue {
+        let mut queue = Queue::new();
+        for path in paths {
+            queue.append(Music::untagged(path));
+        }
+        queue
+    }
+
+    fn current_path(queue: &Queue) -> Option<&str> {
+        queue.current().and_then(|m| m.get_path().to_str())
+    }
+
+    #[test]
+    fn next_and_previous_stop_at_ends() {
+        let mut queue = queue(&["a", "b"]);
+        assert!(queue.previous().is_none());
+        assert_eq!(
+            queue.peek_next().and_then(|m| m.get_path().to_str()),
+            Some("a")
+        );
+        assert!(queue.next().is_some());
+        assert_eq!(current_path(&queue), Some("a"));
+        assert!(queue.previous().is_none());
+        assert_eq!(current_path(&queue), Some("a"));
+        assert!(queue.next().is_some());
+        assert!(queue.next().is_none());
+        // current stays on the last music, a stopped queue is not reset by next
+        assert_eq!(queue.current_index(), Some(1));
+        assert_eq!(
+            queue.previous().and_then(|m| m.get_path().to_str()),
+            Some("a")
+        );
+    }
+
+    #[test]
+    fn insert_and_remove_keep_current_music() {
+        let mut queue = queue(&["a", "b", "c"]);
+        queue.set_current(1);
+        queue.insert(0, Music::untagged("z"));
+        assert_eq!(current_path(&queue), Some("b"));
+        queue.insert(10, Music::untagged("end"));
+        assert_eq!(
+            queue.list().last().and_then(|m| m.get_path().to_str()),
+            Some("end")
+        );
+
+        queue.remove(0);
+        assert_eq!(current_path(&queue), Some("b"));
+        queue.remove(2);
+        assert_eq!(current_path(&queue), Some("b"));
+        queue.remove(1);
+        assert_eq!(queue.current_index(), None);
+        assert!(queue.remove(10).is_none());
+    }
+
+    #[test]
+    fn move_keeps_current_music() {
+        let mut queue = queue(&["a", "b", "c", "d"]);
+        queue.set_current(1);
+        assert!(queue.move_item(1, 3));
+        assert_eq!(queue.current_index(), Some(3));
+        assert!(queue.move_item(0, 3));
+        assert_eq!(current_path(&queue), Some("b"));
+        assert_eq!(queue.current_index(), Some(2));
+        assert!(queue.move_item(3, 0));
+        assert_eq!(current_path(&queue), Some("b"));
+        assert_eq!(queue.current_index(), Some(3));
+        assert!(!queue.move_item(0, 4));
+    }
+
+    #[test]
+    fn ids_stay_and_changes_are_versioned() {
+        let mut queue = queue(&["a", "b", "c"]);
+        let ids: Vec<_> = (0..3).filter_map(|ix| queue.id(ix)).collect();
+        let version = queue.version();
+        queue.remove(1);
+        assert_eq!(queue.id(0), Some(ids[0]));
+        assert_eq!(queue.id(1), Some(ids[2]));
+        assert!(queue.changed(0).unwrap() <= version);
+        assert!(queue.changed(1).unwrap() > version);
+
+        queue.insert(0, Music::untagged("z"));
+        assert!(!ids.contains(&queue.id(0).unwrap()));
+        queue.clear();
+        assert!(queue.is_empty() && queue.id(0).is_none());
+        assert!(queue.version() > version);
+    }
+}
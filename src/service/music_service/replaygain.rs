@@ -0,0 +1,84 @@
+use lofty::tag::{ItemKey, Tag};
+use serde::{Deserialize, Serialize};
+
+/// Loudness normalization mode
+#[derive(PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+impl ReplayGainMode {
+    pub const ALL: [ReplayGainMode; 3] = [
+        ReplayGainMode::Off,
+        ReplayGainMode::Track,
+        ReplayGainMode::Album,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReplayGainMode::Off => "Off",
+            ReplayGainMode::Track => "Track",
+            ReplayGainMode::Album => "Album",
+        }
+    }
+}
+
+/// ReplayGain info read from tags, gain in dB
+#[derive(Clone, Copy, Default)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Read ReplayGain tags
+    pub fn from_tag(tag: &Tag) -> Self {
+        Self {
+            track_gain: Self::read(tag, ItemKey::ReplayGainTrackGain),
+            track_peak: Self::read(tag, ItemKey::ReplayGainTrackPeak),
+            album_gain: Self::read(tag, ItemKey::ReplayGainAlbumGain),
+            album_peak: Self::read(tag, ItemKey::ReplayGainAlbumPeak),
+        }
+    }
+
+    /// Get linear gain factor of mode with preamp (dB).
+    /// The factor is limited by peak so that it never clips.
+    pub fn factor(&self, mode: ReplayGainMode, preamp: f32) -> f32 {
+        let (gain, peak) = match mode {
+            ReplayGainMode::Off => return 1.0,
+            ReplayGainMode::Track => (
+                self.track_gain.or(self.album_gain),
+                self.track_peak.or(self.album_peak),
+            ),
+            ReplayGainMode::Album => (
+                self.album_gain.or(self.track_gain),
+                self.album_peak.or(self.track_peak),
+            ),
+        };
+        let Some(gain) = gain else {
+            return 1.0;
+        };
+        let factor = 10f32.powf((gain + preamp) / 20.0);
+        match peak {
+            Some(p) if p > 0.0 => factor.min(1.0 / p),
+            _ => factor,
+        }
+    }
+
+    /// Read a number like "-6.54 dB" or "0.988"
+    fn read(tag: &Tag, key: ItemKey) -> Option<f32> {
+        let value = tag.get_string(&key)?;
+        value
+            .trim()
+            .trim_end_matches(|c: char| c.is_alphabetic())
+            .trim()
+            .parse()
+            .ok()
+    }
+}
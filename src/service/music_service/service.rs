@@ -1,42 +1,63 @@
 use std::{
     collections::VecDeque,
-    sync::{Arc, atomic::Ordering},
+    f32::consts::FRAC_PI_2,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
     thread,
     time::Duration,
 };
 
+use atomic_float::AtomicF32;
 use ringbuf::{
     HeapProd,
     traits::{Observer, Producer},
 };
 use smol::channel::Sender;
-use symphonia::core::formats::{Packet, SeekMode, SeekTo};
 
 use crate::service::music_service::{
-    controller::{Controller, ServiceState},
-    decoder::Decoder,
+    controller::{Controller, QueuedMusic, ServiceState},
+    dsp::{DspChain, DspLayout},
     models::Events,
-    stream::Stream,
+    source::Source,
     time::PlayTime,
 };
 
+/// The music fading in while current one fading out
+struct Crossfade {
+    // music fading in
+    source: Source,
+    // decoded samples not mixed yet
+    fade_out: VecDeque<f32>,
+    fade_in: VecDeque<f32>,
+    // mixed frames
+    mixed: u64,
+    // total frames of fading
+    length: u64,
+    // music fading out ended before the fade is finished
+    out_ended: bool,
+}
+
 pub struct Service {
-    // decoder with music
-    music_decoder: Decoder,
+    // music being decoded
+    source: Source,
+    // crossfade with next music if fading
+    fading: Option<Crossfade>,
+    // crossfade seconds, 0 means disabled
+    crossfade_secs: Arc<AtomicF32>,
+    // processors applied to decoded samples, owned by this thread
+    dsp: DspChain,
     // ringbuf peoducer
     producer: HeapProd<f32>,
     // state controller
     controller: Arc<Controller>,
     // play time
     play_time: Arc<PlayTime>,
+    // samples before this position will be skipped by output
+    skip_to: Arc<AtomicU64>,
     // sample write overflow zone
     leftover_samples: VecDeque<f32>,
-    // whether need resample
-    need_resample: bool,
-    // resampler. if need resample, that will be init
-    resampler: Option<Stream>,
-    // sample pack expected length (for resampler)
-    expected_sample_len: usize,
     // channel sender
     sender: Option<Sender<Events>>,
 }
@@ -44,24 +65,28 @@ pub struct Service {
 impl Service {
     // Create new service
     pub fn new(
-        music_decoder: Decoder,
+        music: QueuedMusic,
         producer: HeapProd<f32>,
         controller: Arc<Controller>,
         play_time: Arc<PlayTime>,
-    ) -> Self {
-        let leftover_samples = VecDeque::new();
-        let need_resample = !(play_time.device_sample_rate == music_decoder.sample_rate);
-        Self {
-            music_decoder,
+        skip_to: Arc<AtomicU64>,
+        crossfade_secs: Arc<AtomicF32>,
+        dsp: Arc<DspLayout>,
+    ) -> Result<Self, anyhow::Error> {
+        let source = Source::new(music, play_time.device_sample_rate, play_time.channels)?;
+        play_time.start_track(source.track_time(0));
+        Ok(Self {
+            source,
+            fading: None,
+            crossfade_secs,
+            dsp: DspChain::new(dsp),
             producer,
             controller,
             play_time,
-            leftover_samples,
-            need_resample,
-            resampler: None,
-            expected_sample_len: 0,
+            skip_to,
+            leftover_samples: VecDeque::new(),
             sender: None,
-        }
+        })
     }
 
     /// subscribe play events
@@ -77,35 +102,46 @@ impl Service {
         // run decode thread
         thread::spawn(move || {
             loop {
+                // replace current music if required
+                if let Some(music) = self.controller.take_load() {
+                    match self.new_source(music) {
+                        Ok(source) => {
+                            self.source = source;
+                            self.fading = None;
+                            self.dsp.reset();
+                            let pos = self.discard_buffered();
+                            self.play_time.start_track(self.source.track_time(pos));
+                            is_finished = false;
+                            last_sent_time = 0.0;
+                        }
+                        Err(e) => eprintln!("error: {}", e),
+                    }
+                }
+
                 // state check
                 match self.controller.state() {
                     ServiceState::Playing => {
-                        // if subscribed
-                        if let Some(tx) = self.sender.as_ref() {
-                            // check whether play finished
-                            if is_finished {
-                                let buf_occupied =
-                                    self.play_time.occupied_len.load(Ordering::Relaxed);
-                                // play finished
-                                if buf_occupied == 0 {
-                                    // send finish event
-                                    if let Err(e) = tx.try_send(Events::PlayFinished) {
-                                        eprintln!("error when send event: {}", e);
-                                    }
-                                    // set state
-                                    self.controller.stop();
-                                    break;
-                                }
-                            }
-                            // send current play time
-                            let time = self.play_time.played_time();
-                            let current_time = time.seconds as f64 + time.frac;
-                            if current_time >= (last_sent_time + 0.1) {
-                                last_sent_time = last_sent_time.max(current_time);
-                                if let Err(e) = tx.try_send(Events::PlaytimeRefresh) {
-                                    eprintln!("error when send event: {}", e);
-                                }
+                        // output reached the next music
+                        if self.play_time.switch_if_reached() {
+                            last_sent_time = 0.0;
+                            self.send_track_changed();
+                        }
+                        // check whether play finished
+                        if is_finished && self.buffered_len() == 0 {
+                            // a music loaded meanwhile is taken in next loop
+                            if !self.controller.finish() {
+                                continue;
                             }
+                            // send finish event
+                            self.send(Events::PlayFinished);
+                            break;
+                        }
+                        // send current play time
+                        let time = self.play_time.played_time();
+                        let current_time = time.seconds as f64 + time.frac;
+                        if current_time >= (last_sent_time + 0.1) {
+                            last_sent_time = last_sent_time.max(current_time);
+                            self.send(Events::PlaytimeRefresh);
                         }
                     }
                     ServiceState::Paused => {
@@ -113,19 +149,16 @@ impl Service {
                     }
                     ServiceState::Stopped => break,
                     ServiceState::Seek(t) => {
-                        let r = self.music_decoder.format.seek(
-                            SeekMode::Accurate,
-                            SeekTo::Time {
-                                time: t,
-                                track_id: None,
-                            },
-                        );
-                        match r {
-                            Ok(s) => {
-                                self.leftover_samples.clear();
-                                self.play_time
-                                    .decoded_len
-                                    .store(s.actual_ts, Ordering::Relaxed);
+                        self.cancel_crossfade();
+                        match self.source.seek(t) {
+                            Ok(ts) => {
+                                self.dsp.reset();
+                                let pos = self.discard_buffered();
+                                if self.play_time.seek_track(pos, ts) {
+                                    self.send_track_changed();
+                                }
+                                last_sent_time = 0.0;
+                                is_finished = false;
                             }
                             Err(e) => eprintln!("error: {}", e),
                         }
@@ -140,6 +173,9 @@ impl Service {
                         .producer
                         .push_slice(self.leftover_samples.make_contiguous());
                     self.leftover_samples.drain(..written);
+                    self.play_time
+                        .written_len
+                        .fetch_add(written as u64, Ordering::Relaxed);
                 }
 
                 // if ringbuff is full, wait
@@ -148,25 +184,50 @@ impl Service {
                     continue;
                 }
 
-                // read and decode package data
-                let package = match self.music_decoder.format.next_packet() {
-                    Ok(p) => p,
-                    Err(_) => {
-                        is_finished = true;
-                        continue;
-                    } // play finished
-                };
+                // start crossfade when current music is going to end
+                if self.fading.is_none() {
+                    self.try_start_crossfade();
+                }
 
                 // decode & transfer & resample
-                let (sample, frames) = self.process_stream(&package);
+                let mut sample = if self.fading.is_some() {
+                    self.mix_crossfade()
+                } else if let Some(s) = self.source.next_samples() {
+                    s
+                } else {
+                    // continue with next music without gap
+                    match self.controller.take_next().map(|n| self.new_source(n)) {
+                        Some(Ok(source)) => {
+                            self.source = source;
+                            let pos = self.pending_pos();
+                            self.play_time.push_track(self.source.track_time(pos));
+                            is_finished = false;
+                        }
+                        Some(Err(e)) => {
+                            eprintln!("error: {}", e);
+                            is_finished = true;
+                        }
+                        None => {
+                            // wait for next music until buffer played
+                            is_finished = true;
+                            thread::sleep(Duration::from_millis(10));
+                        }
+                    }
+                    continue;
+                };
 
-                // append counter
-                self.play_time
-                    .decoded_len
-                    .fetch_add(frames as u64, Ordering::Relaxed);
+                // apply dsp chain
+                self.dsp.process(
+                    &mut sample,
+                    self.play_time.channels,
+                    self.play_time.device_sample_rate,
+                );
 
                 // push sample into buffer
                 let written = self.producer.push_slice(&sample);
+                self.play_time
+                    .written_len
+                    .fetch_add(written as u64, Ordering::Relaxed);
                 // buffer is full, put data into overflow
                 if written < sample.len() {
                     let remaining_slice = &sample[written..];
@@ -178,34 +239,158 @@ impl Service {
         Ok(())
     }
 
-    /// decode and process stream
-    fn process_stream(&mut self, package: &Packet) -> (Vec<f32>, usize) {
-        let buff = self.music_decoder.decoder.decode(package).unwrap();
-        // transfer data to f32
-        let (mut sample, _, channels, frames) = Stream::transfer_to_f32(buff);
-
-        // if need resample
-        if self.need_resample {
-            // init resampler if not
-            if self.resampler.is_none() {
-                self.expected_sample_len = sample.len();
-                self.resampler = Some(
-                    Stream::new(
-                        self.music_decoder.sample_rate as usize,
-                        self.play_time.device_sample_rate as usize,
-                        self.expected_sample_len / channels,
-                        channels,
-                    )
-                    .unwrap(),
-                );
+    /// Create a source fit current output
+    fn new_source(&self, music: QueuedMusic) -> Result<Source, anyhow::Error> {
+        Source::new(
+            music,
+            self.play_time.device_sample_rate,
+            self.play_time.channels,
+        )
+    }
+
+    /// Start crossfade if current music is going to end and next one can be crossfaded
+    fn try_start_crossfade(&mut self) {
+        let secs = self.crossfade_secs.load(Ordering::Relaxed) as f64;
+        if secs <= 0.0 {
+            return;
+        }
+        let Some(remaining) = self.source.remaining_secs() else {
+            return;
+        };
+        if remaining > secs {
+            return;
+        }
+        let Some(next) = self.controller.take_next_crossfade() else {
+            return;
+        };
+        match self.new_source(next) {
+            Ok(source) => {
+                // the next music is heard from now
+                let pos = self.pending_pos();
+                self.play_time.push_track(source.track_time(pos));
+                self.fading = Some(Crossfade {
+                    source,
+                    fade_out: VecDeque::new(),
+                    fade_in: VecDeque::new(),
+                    mixed: 0,
+                    length: (remaining * self.play_time.device_sample_rate as f64) as u64,
+                    out_ended: false,
+                });
             }
-            // if short than expected length
-            if sample.len() < self.expected_sample_len {
-                sample.resize(self.expected_sample_len, 0.0);
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+
+    /// Stop fading before seeking.
+    /// If output has not reached the music fading in, it is handed back to be played later
+    /// and the music fading out is seeked, otherwise the music fading in is.
+    fn cancel_crossfade(&mut self) {
+        let Some(fade) = self.fading.take() else {
+            return;
+        };
+        if !self.play_time.drop_unreached() {
+            self.source = fade.source;
+            return;
+        }
+        match fade.source.into_music() {
+            Ok(music) => self.controller.restore_next(music),
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+
+    /// Decode both musics and mix them with equal power curves.
+    /// The music fading in becomes current one when the fade finished and the other ended.
+    fn mix_crossfade(&mut self) -> Vec<f32> {
+        let channels = self.play_time.channels;
+        let Some(fade) = self.fading.as_mut() else {
+            return Vec::new();
+        };
+
+        if !fade.out_ended {
+            match self.source.next_samples() {
+                Some(s) => fade.fade_out.extend(s),
+                None => fade.out_ended = true,
+            }
+        }
+        // keep music fading in no shorter than the other one,
+        // and keep fading it in alone if the other one ended early
+        let wanted = if fade.out_ended {
+            channels
+        } else {
+            fade.fade_out.len()
+        };
+        let mut in_ended = false;
+        while fade.fade_in.len() < wanted {
+            match fade.source.next_samples() {
+                Some(s) => fade.fade_in.extend(s),
+                None => {
+                    in_ended = true;
+                    break;
+                }
+            }
+        }
+
+        let frames = if fade.out_ended {
+            fade.fade_in.len().max(fade.fade_out.len())
+        } else {
+            fade.fade_out.len()
+        } / channels;
+        let mut mixed = Vec::with_capacity(frames * channels);
+        for _ in 0..frames {
+            let t = (fade.mixed as f32 / fade.length.max(1) as f32).min(1.0);
+            let gain_out = (t * FRAC_PI_2).cos();
+            let gain_in = (t * FRAC_PI_2).sin();
+            for _ in 0..channels {
+                let a = fade.fade_out.pop_front().unwrap_or(0.0);
+                let b = fade.fade_in.pop_front().unwrap_or(0.0);
+                mixed.push(a * gain_out + b * gain_in);
             }
-            // resample
-            sample = self.resampler.as_mut().unwrap().process(&sample);
+            fade.mixed += 1;
+        }
+
+        if fade.out_ended
+            && (fade.mixed >= fade.length || in_ended)
+            && let Some(fade) = self.fading.take()
+        {
+            mixed.extend(fade.fade_in);
+            self.source = fade.source;
+        }
+        mixed
+    }
+
+    /// Position of the next sample to be produced
+    fn pending_pos(&self) -> u64 {
+        self.play_time.written_len.load(Ordering::Relaxed) + self.leftover_samples.len() as u64
+    }
+
+    /// Drop all samples not played yet, return the position of next sample
+    fn discard_buffered(&mut self) -> u64 {
+        self.leftover_samples.clear();
+        let pos = self.play_time.written_len.load(Ordering::Relaxed);
+        self.skip_to.store(pos, Ordering::Relaxed);
+        pos
+    }
+
+    /// Length of samples not played yet
+    fn buffered_len(&self) -> u64 {
+        let written = self.play_time.written_len.load(Ordering::Relaxed);
+        let consumed = self.play_time.consumed_len.load(Ordering::Relaxed);
+        written.saturating_sub(consumed) + self.leftover_samples.len() as u64
+    }
+
+    /// Send event if subscribed
+    fn send(&self, event: Events) {
+        if let Some(tx) = self.sender.as_ref()
+            && let Err(e) = tx.try_send(event)
+        {
+            eprintln!("error when send event: {}", e);
+        }
+    }
+
+    /// Send event of the song output switched to
+    fn send_track_changed(&self) {
+        if let Some(id) = self.play_time.playing_id() {
+            self.send(Events::TrackChanged(id));
         }
-        (sample, frames)
     }
 }
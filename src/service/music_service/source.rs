@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use symphonia::core::{
+    errors::Error,
+    formats::{SeekMode, SeekTo, Track},
+    units::{Time, TimeBase},
+};
+
+use crate::service::music_service::{
+    controller::QueuedMusic, decoder::Decoder, models::TrackRange, stream::Stream, time::TrackTime,
+};
+
+/// A music being decoded, produce samples fit the output stream
+pub struct Source {
+    // decoder with music
+    music_decoder: Decoder,
+    // id of song in queue
+    id: u64,
+    // default track of music
+    track: Track,
+    // loudness normalization gain factor
+    gain: f32,
+    // output device sample rate
+    device_sample_rate: u32,
+    // output device channels
+    device_channels: usize,
+    // resampler. if need resample, that will be init
+    resampler: Option<Stream>,
+    // timestamp of decoded position
+    position: u64,
+    // time base of track timestamps
+    time_base: TimeBase,
+    // part of file played, the whole file if None
+    range: Option<TrackRange>,
+    // timestamps of the part played
+    range_start: u64,
+    range_end: Option<u64>,
+    // decoded frames before this timestamp are dropped, set by seeking
+    skip_until: u64,
+}
+
+impl Source {
+    /// Create a source from queued music
+    pub fn new(
+        music: QueuedMusic,
+        device_sample_rate: u32,
+        device_channels: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let music_decoder = music.decoder;
+        let track = music_decoder
+            .format
+            .default_track()
+            .ok_or_else(|| anyhow::anyhow!("no track found"))?
+            .clone();
+        let time_base = track
+            .codec_params
+            .time_base
+            .unwrap_or_else(|| TimeBase::new(1, music_decoder.sample_rate));
+        let to_ts = |d: Duration| time_base.calc_timestamp(Time::from(d.as_secs_f64()));
+        let range_start = music.range.map_or(0, |r| to_ts(r.start));
+        let range_end = music.range.and_then(|r| r.end).map(to_ts);
+        let mut source = Self {
+            music_decoder,
+            id: music.id,
+            track,
+            gain: music.gain,
+            device_sample_rate,
+            device_channels,
+            resampler: None,
+            position: 0,
+            time_base,
+            range: music.range,
+            range_start,
+            range_end,
+            skip_until: 0,
+        };
+        if music.start.is_some() || range_start > 0 {
+            source.seek(music.start.unwrap_or(Time::from(0.0)))?;
+        }
+        Ok(source)
+    }
+
+    /// Get time info of source starting at stream position.
+    /// Time of a range is counted from its start, not the start of file
+    pub fn track_time(&self, start_pos: u64) -> TrackTime {
+        let mut track_time = TrackTime::from_track(&self.track, start_pos);
+        track_time.id = self.id;
+        track_time.n_frames = self
+            .range_end
+            .unwrap_or(track_time.n_frames)
+            .saturating_sub(self.range_start);
+        track_time.start_ts = self
+            .position
+            .max(self.skip_until)
+            .saturating_sub(self.range_start);
+        track_time
+    }
+
+    /// Seek source to time of the range, return the timestamp reached in range
+    pub fn seek(&mut self, time: Time) -> Result<u64, anyhow::Error> {
+        let start = self.time_base.calc_time(self.range_start);
+        let time = Time::from(start.seconds as f64 + start.frac + time.seconds as f64 + time.frac);
+        let s = self.music_decoder.format.seek(
+            SeekMode::Accurate,
+            SeekTo::Time {
+                time,
+                track_id: None,
+            },
+        )?;
+        self.music_decoder.decoder.reset();
+        if let Some(resampler) = self.resampler.as_mut() {
+            resampler.reset();
+        }
+        self.position = s.actual_ts;
+        // frames between the actual and the required position are decoded but not played
+        self.skip_until = s.required_ts;
+        Ok(s.required_ts.saturating_sub(self.range_start))
+    }
+
+    /// Rewind source into queued music, so that it can be played again from start
+    pub fn into_music(mut self) -> Result<QueuedMusic, anyhow::Error> {
+        self.seek(Time::from(0.0))?;
+        Ok(QueuedMusic {
+            decoder: self.music_decoder,
+            id: self.id,
+            crossfade: true,
+            gain: self.gain,
+            start: None,
+            range: self.range,
+        })
+    }
+
+    /// Get seconds of source not decoded yet, None if duration unknown
+    pub fn remaining_secs(&self) -> Option<f64> {
+        let end = self.range_end.or(self.track.codec_params.n_frames)?;
+        let time = self.time_base.calc_time(end.saturating_sub(self.position));
+        Some(time.seconds as f64 + time.frac)
+    }
+
+    /// Decode next package of source, return None if reached the end
+    pub fn next_samples(&mut self) -> Option<Vec<f32>> {
+        loop {
+            // read and decode package data
+            let package = match self.music_decoder.format.next_packet() {
+                Ok(p) => p,
+                Err(_) => return self.flush(), // play finished
+            };
+            // skip packages of other tracks
+            if package.track_id() != self.track.id {
+                continue;
+            }
+            let ts = package.ts();
+            // the rest of file belongs to the next range
+            if self.range_end.is_some_and(|end| ts >= end) {
+                return self.flush();
+            }
+            self.position = ts + package.dur();
+            match self.music_decoder.decoder.decode(&package) {
+                Ok(buff) => {
+                    // transfer data to f32
+                    let (mut sample, _, channels, _) = Stream::transfer_to_f32(buff);
+                    let len = sample.len();
+                    // drop frames before seeked position and after the range
+                    let head = self.ts_frames(self.skip_until.saturating_sub(ts)) * channels;
+                    let tail = match self.range_end {
+                        Some(end) if end < self.position => self.ts_frames(end - ts) * channels,
+                        _ => len,
+                    };
+                    sample.truncate(tail);
+                    sample.drain(..head.min(sample.len()));
+                    if sample.is_empty() {
+                        continue;
+                    }
+                    match self.process_stream(sample, channels, len) {
+                        // resampler is waiting for more frames
+                        Ok(s) if s.is_empty() => continue,
+                        Ok(s) => return Some(s),
+                        Err(e) => {
+                            eprintln!("error when resample: {}", e);
+                            return None;
+                        }
+                    }
+                }
+                // broken package, skip it
+                Err(Error::DecodeError(e)) => eprintln!("error when decode: {}", e),
+                Err(_) => return self.flush(),
+            }
+        }
+    }
+
+    /// Samples kept by resampler at the end of source, None if nothing left
+    fn flush(&mut self) -> Option<Vec<f32>> {
+        let resampler = self.resampler.as_mut()?;
+        let channels = resampler.channels();
+        let sample = match resampler.flush() {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("error when resample: {}", e);
+                return None;
+            }
+        };
+        if sample.is_empty() {
+            return None;
+        }
+        Some(self.fit_output(sample, channels))
+    }
+
+    /// Count of decoded frames in timestamp duration
+    fn ts_frames(&self, ts: u64) -> usize {
+        let time = self.time_base.calc_time(ts);
+        ((time.seconds as f64 + time.frac) * self.music_decoder.sample_rate as f64).round() as usize
+    }
+
+    /// resample, remix and normalize stream to fit output.
+    /// Package length is the length before dropping frames, resampler works on blocks of it
+    fn process_stream(
+        &mut self,
+        mut sample: Vec<f32>,
+        channels: usize,
+        package_len: usize,
+    ) -> Result<Vec<f32>, anyhow::Error> {
+        // if need resample
+        if self.music_decoder.sample_rate != self.device_sample_rate {
+            // init resampler if not
+            if self.resampler.is_none() {
+                self.resampler = Some(Stream::new(
+                    self.music_decoder.sample_rate as usize,
+                    self.device_sample_rate as usize,
+                    package_len / channels,
+                    channels,
+                )?);
+            }
+            // resample, short packages are kept until a whole block is decoded
+            if let Some(resampler) = self.resampler.as_mut() {
+                sample = resampler.process(&sample)?;
+            }
+        }
+        Ok(self.fit_output(sample, channels))
+    }
+
+    /// Remix and normalize stream to fit output
+    fn fit_output(&self, sample: Vec<f32>, channels: usize) -> Vec<f32> {
+        let mut sample = Stream::remix(sample, channels, self.device_channels);
+        if self.gain != 1.0 {
+            for s in &mut sample {
+                *s = Stream::apply_gain(*s, self.gain);
+            }
+        }
+        sample
+    }
+}
@@ -6,6 +6,8 @@ pub struct Stream {
     input_rate: usize,
     output_rate: usize,
     channels: usize,
+    // input frames waiting for a whole chunk, split by channel
+    pending: Vec<Vec<f32>>,
 }
 
 impl Stream {
@@ -22,40 +24,97 @@ impl Stream {
             input_rate,
             output_rate,
             channels,
+            pending: vec![Vec::new(); channels],
         })
     }
 
-    /// Do resample for a stream block
-    pub fn process(&mut self, input: &Vec<f32>) -> Vec<f32> {
+    /// Resample a stream block.
+    /// Frames not filling a whole chunk are kept until more input or `flush`
+    pub fn process(&mut self, input: &[f32]) -> Result<Vec<f32>, anyhow::Error> {
         // return directly if no need resample
         if self.input_rate == self.output_rate {
-            return input.to_vec();
+            return Ok(input.to_vec());
         }
 
-        let frames = input.len() / self.channels;
-
         // split channels
-        let mut channels_data = vec![Vec::with_capacity(frames); self.channels];
         for (i, &sample) in input.iter().enumerate() {
-            channels_data[i % self.channels].push(sample);
+            self.pending[i % self.channels].push(sample);
         }
 
-        // resample
-        let outputs = self
-            .resampler
-            .process(&channels_data, None)
-            .expect("Resampling failed");
+        // resample every whole chunk
+        let mut interleaved = Vec::new();
+        while self.pending[0].len() >= self.resampler.input_frames_next() {
+            let frames = self.resampler.input_frames_next();
+            let outputs = self.resampler.process(&self.pending, None)?;
+            for channel in &mut self.pending {
+                channel.drain(..frames);
+            }
+            Self::interleave(&outputs, outputs[0].len(), &mut interleaved);
+        }
+        Ok(interleaved)
+    }
+
+    /// Resample frames kept from the end of stream, without the padding silence
+    pub fn flush(&mut self) -> Result<Vec<f32>, anyhow::Error> {
+        let frames = self.pending[0].len();
+        if frames == 0 {
+            return Ok(Vec::new());
+        }
+        let outputs = self.resampler.process_partial(Some(&self.pending), None)?;
+        for channel in &mut self.pending {
+            channel.clear();
+        }
+        let frames_out = (frames * self.output_rate).div_ceil(self.input_rate);
+        let mut interleaved = Vec::new();
+        Self::interleave(&outputs, frames_out.min(outputs[0].len()), &mut interleaved);
+        Ok(interleaved)
+    }
+
+    /// Count of channels of the stream
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
 
-        // mix two channels
-        let frames_out = outputs[0].len();
-        let mut interleaved = Vec::with_capacity(frames_out * self.channels);
-        for i in 0..frames_out {
-            for ch in 0..self.channels {
-                interleaved.push(outputs[ch][i]);
+    /// Drop kept frames and resampler state, used when stream jumps
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+        for channel in &mut self.pending {
+            channel.clear();
+        }
+    }
+
+    /// Append first frames of channels to interleaved samples
+    fn interleave(channels: &[Vec<f32>], frames: usize, interleaved: &mut Vec<f32>) {
+        interleaved.reserve(frames * channels.len());
+        for i in 0..frames {
+            for channel in channels {
+                interleaved.push(channel[i]);
             }
         }
+    }
 
-        interleaved
+    /// Remix interleaved samples from one channel count to another
+    pub fn remix(input: Vec<f32>, from: usize, to: usize) -> Vec<f32> {
+        if from == to || from == 0 || to == 0 {
+            return input;
+        }
+        let mut output = Vec::with_capacity(input.len() / from * to);
+        for frame in input.chunks_exact(from) {
+            if to == 1 {
+                // downmix to mono
+                output.push(frame.iter().sum::<f32>() / from as f32);
+                continue;
+            }
+            for ch in 0..to {
+                let sample = match from {
+                    // upmix mono to every channel
+                    1 => frame[0],
+                    _ => frame.get(ch).copied().unwrap_or(0.0),
+                };
+                output.push(sample);
+            }
+        }
+        output
     }
 
     /// function to process gain of stream
@@ -190,6 +249,6 @@ impl Stream {
                 }
             }
         }
-        return (sample_packet, sample_rate, channels, frames);
+        (sample_packet, sample_rate, channels, frames)
     }
 }
@@ -1,6 +1,9 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicU64, AtomicUsize, Ordering},
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
 };
 
 use symphonia::core::{
@@ -8,89 +11,165 @@ use symphonia::core::{
     units::{Time, TimeBase},
 };
 
-pub struct PlayTime {
-    // current song time base
+/// Time info of a track inside the output stream
+#[derive(Clone, Copy)]
+pub struct TrackTime {
+    // id of song in queue
+    pub id: u64,
+    // track time base
     pub time_base: TimeBase,
-    // total frames of song
+    // total frames of track
     pub n_frames: u64,
-    // duration seconds of song
-    pub duration_sec: u64,
-    // count of song channels
-    pub channels: usize,
-    // song sample rate
-    pub sample_rate: u32,
+    // stream position (in samples) where the track starts
+    pub start_pos: u64,
+    // track timestamp at start position
+    pub start_ts: u64,
+}
+
+impl TrackTime {
+    /// Create track time from track, starts at stream position
+    pub fn from_track(track: &Track, start_pos: u64) -> Self {
+        let params = &track.codec_params;
+        let sample_rate = params.sample_rate.unwrap_or(44100);
+        Self {
+            id: 0,
+            time_base: params
+                .time_base
+                .unwrap_or_else(|| TimeBase::new(1, sample_rate)),
+            n_frames: params.n_frames.unwrap_or(0),
+            start_pos,
+            start_ts: 0,
+        }
+    }
+}
+
+pub struct PlayTime {
     // output device sample rate
     pub device_sample_rate: u32,
-    // decoded frames length
-    pub decoded_len: Arc<AtomicU64>,
-    // ringbuf occupied length
-    pub occupied_len: Arc<AtomicUsize>,
+    // count of output channels
+    pub channels: usize,
+    // samples length written into ringbuf
+    pub written_len: Arc<AtomicU64>,
+    // samples length consumed by output
+    pub consumed_len: Arc<AtomicU64>,
+    // tracks inside the stream, the front one is playing
+    tracks: Mutex<VecDeque<TrackTime>>,
 }
 
 impl PlayTime {
     /// Create a playtime
     pub fn new(
-        time_base: TimeBase,
-        n_frames: u64,
-        channels: usize,
-        sample_rate: u32,
         device_sample_rate: u32,
-        decoded_len: Arc<AtomicU64>,
-        occupied_len: Arc<AtomicUsize>,
+        channels: usize,
+        written_len: Arc<AtomicU64>,
+        consumed_len: Arc<AtomicU64>,
     ) -> Self {
-        let duration_sec = time_base.calc_time(n_frames).seconds;
         Self {
-            time_base,
-            n_frames,
-            duration_sec,
-            channels,
-            sample_rate,
             device_sample_rate,
-            decoded_len,
-            occupied_len,
+            channels,
+            written_len,
+            consumed_len,
+            tracks: Mutex::new(VecDeque::new()),
         }
     }
 
-    /// Create play time from track
-    pub fn from_track(
-        track: Track,
-        device_sample_rate: u32,
-        decoded_len: Arc<AtomicU64>,
-        occupied_len: Arc<AtomicUsize>,
-    ) -> Option<Self> {
-        Some(Self::new(
-            track.codec_params.time_base?,
-            track.codec_params.n_frames?,
-            track.codec_params.channels?.count(),
-            track.codec_params.sample_rate?,
-            device_sample_rate,
-            decoded_len,
-            occupied_len,
-        ))
+    /// Replace all tracks with a new one
+    pub fn start_track(&self, track: TrackTime) {
+        let mut tracks = self.tracks.lock().unwrap();
+        tracks.clear();
+        tracks.push_back(track);
+    }
+
+    /// Append a track which starts after the previous one
+    pub fn push_track(&self, track: TrackTime) {
+        self.tracks.lock().unwrap().push_back(track);
+    }
+
+    /// Move the decoding track to a new position, tracks before it are dropped.
+    /// Return true if the playing track is changed.
+    pub fn seek_track(&self, start_pos: u64, start_ts: u64) -> bool {
+        let mut tracks = self.tracks.lock().unwrap();
+        let switched = tracks.len() > 1;
+        while tracks.len() > 1 {
+            tracks.pop_front();
+        }
+        if let Some(t) = tracks.front_mut() {
+            t.start_pos = start_pos;
+            t.start_ts = start_ts;
+        }
+        switched
+    }
+
+    /// Drop the tracks output has not reached, the playing one is kept.
+    /// Return true if any track is dropped.
+    pub fn drop_unreached(&self) -> bool {
+        let consumed = self.consumed_len.load(Ordering::Relaxed);
+        let mut tracks = self.tracks.lock().unwrap();
+        let len = tracks.len();
+        while tracks.len() > 1 && tracks.back().is_some_and(|t| t.start_pos > consumed) {
+            tracks.pop_back();
+        }
+        tracks.len() < len
+    }
+
+    /// Drop the playing track if output reached next one.
+    /// Return true if the playing track is changed.
+    pub fn switch_if_reached(&self) -> bool {
+        let consumed = self.consumed_len.load(Ordering::Relaxed);
+        let mut tracks = self.tracks.lock().unwrap();
+        let mut switched = false;
+        while tracks.len() > 1 && tracks[1].start_pos <= consumed {
+            tracks.pop_front();
+            switched = true;
+        }
+        switched
+    }
+
+    /// Get queue id of the playing track
+    pub fn playing_id(&self) -> Option<u64> {
+        self.tracks.lock().unwrap().front().map(|t| t.id)
+    }
+
+    /// Get the playing track
+    fn current(&self) -> Option<TrackTime> {
+        let consumed = self.consumed_len.load(Ordering::Relaxed);
+        let tracks = self.tracks.lock().unwrap();
+        // the next track may be reached before switched
+        tracks
+            .iter()
+            .rev()
+            .find(|t| t.start_pos <= consumed)
+            .or(tracks.front())
+            .copied()
     }
 
     /// Get duration of song, return Time
     pub fn duration(&self) -> Time {
-        self.time_base.calc_time(self.n_frames)
+        match self.current() {
+            Some(t) => t.time_base.calc_time(t.n_frames),
+            None => Time::from(0.0),
+        }
     }
 
     /// Get duration of song, return second u64
     pub fn duration_sec(&self) -> u64 {
-        self.duration_sec
+        self.duration().seconds
     }
 
     /// Get music played time, return Time
     pub fn played_time(&self) -> Time {
-        let occupied = self.occupied_len.load(Ordering::Relaxed);
-        let latency_samples = (((occupied as u32 / self.channels as u32) as f32
-            / self.device_sample_rate as f32)
-            * self.sample_rate as f32) as u64;
-
-        let played_len = self
-            .decoded_len
-            .load(Ordering::Relaxed)
-            .saturating_sub(latency_samples);
-        self.time_base.calc_time(played_len)
+        let Some(track) = self.current() else {
+            return Time::from(0.0);
+        };
+        let consumed = self.consumed_len.load(Ordering::Relaxed);
+        let played_samples = consumed.saturating_sub(track.start_pos);
+        let played_frames = played_samples / self.channels as u64;
+        let start = track.time_base.calc_time(track.start_ts);
+        Time::from(
+            start.seconds as f64
+                + start.frac
+                + played_frames as f64 / self.device_sample_rate as f64,
+        )
     }
 
     /// Get music played time, return second u64
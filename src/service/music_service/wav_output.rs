@@ -0,0 +1,133 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::service::music_service::output::{Output, OutputBuffer};
+
+// frames written at once
+static BLOCK_FRAMES: usize = 4096;
+// length of wav header
+static HEADER_LEN: u32 = 44;
+
+/// A sink writing samples into a 32-bit float wav file as fast as possible
+pub struct WavOutput {
+    sample_rate: u32,
+    channels: usize,
+    playing: Arc<AtomicBool>,
+    closed: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WavOutput {
+    /// Create the file and start writing thread, the file is finished when dropped
+    pub fn new(
+        mut buffer: OutputBuffer,
+        path: &Path,
+        sample_rate: u32,
+        channels: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        Self::write_header(&mut writer, sample_rate, channels, 0)?;
+
+        let playing = Arc::new(AtomicBool::new(false));
+        let closed = Arc::new(AtomicBool::new(false));
+        let (p, c) = (playing.clone(), closed.clone());
+        let handle = thread::spawn(move || {
+            let mut data = vec![0.0; BLOCK_FRAMES * channels];
+            let mut data_len: u64 = 0;
+            while !c.load(Ordering::Relaxed) {
+                if !p.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+                let len = buffer.read(&mut data);
+                if len == 0 {
+                    thread::sleep(Duration::from_millis(1));
+                    continue;
+                }
+                let bytes: Vec<u8> = data[..len].iter().flat_map(|s| s.to_le_bytes()).collect();
+                if let Err(e) = writer.write_all(&bytes) {
+                    eprintln!("error when write wav: {}", e);
+                    break;
+                }
+                data_len += bytes.len() as u64;
+            }
+            // fill sizes of header
+            let data_len = data_len.min((u32::MAX - HEADER_LEN) as u64) as u32;
+            let result = writer
+                .seek(SeekFrom::Start(0))
+                .map_err(anyhow::Error::from)
+                .and_then(|_| Self::write_header(&mut writer, sample_rate, channels, data_len))
+                .and_then(|_| writer.flush().map_err(anyhow::Error::from));
+            if let Err(e) = result {
+                eprintln!("error when finish wav: {}", e);
+            }
+        });
+        Ok(Self {
+            sample_rate,
+            channels,
+            playing,
+            closed,
+            handle: Some(handle),
+        })
+    }
+
+    /// Write RIFF header of IEEE float samples
+    fn write_header(
+        writer: &mut impl Write,
+        sample_rate: u32,
+        channels: usize,
+        data_len: u32,
+    ) -> Result<(), anyhow::Error> {
+        let block_align = channels as u16 * 4;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_LEN - 8 + data_len).to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // format 3: IEEE float
+        writer.write_all(&3u16.to_le_bytes())?;
+        writer.write_all(&(channels as u16).to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&32u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&data_len.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl Output for WavOutput {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn play(&self) {
+        self.playing.store(true, Ordering::Relaxed);
+    }
+
+    fn pause(&self) {
+        self.playing.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Drop for WavOutput {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Some(h) = self.handle.take() {
+            let _ = h.join();
+        }
+    }
+}
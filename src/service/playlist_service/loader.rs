@@ -0,0 +1,127 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::service::{
+    music_service::cue,
+    playlist_service::{
+        m3u,
+        models::{LoadedPlaylist, Playlist, PlaylistFormat},
+        pls, uri, xspf,
+    },
+};
+
+/// Whether file is a playlist of known format
+pub fn is_playlist<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    PlaylistFormat::from_path(path).is_some()
+}
+
+/// Read playlist file, relative entries are resolved against its folder.
+/// Entries whose files do not exist are reported in missing instead of failing the playlist
+pub fn load(path: &Path) -> Result<LoadedPlaylist, anyhow::Error> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a playlist", path.display()))?;
+    let text = decode(&fs::read(path)?);
+    let playlist = match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => m3u::parse(&text),
+        PlaylistFormat::Pls => pls::parse(&text),
+        PlaylistFormat::Xspf => xspf::parse(&text),
+    };
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut entries = Vec::new();
+    let mut missing = Vec::new();
+    for mut entry in playlist.entries {
+        entry.path = resolve(base, &entry.path);
+        // single tracks of CUE sheets are kept like `album.cue#3`
+        let track_file = cue::split_track_path(&entry.path).map(|(file, _)| file);
+        if entry.path.is_file() || track_file.is_some_and(|f| f.is_file()) {
+            entries.push(entry);
+        } else {
+            missing.push(entry.path);
+        }
+    }
+    Ok(LoadedPlaylist {
+        playlist: Playlist {
+            title: playlist.title,
+            entries,
+        },
+        missing,
+    })
+}
+
+/// Write playlist in format of file extension.
+/// Songs under the playlist folder are written relative to it, so folder can be moved
+pub fn save(path: &Path, playlist: &Playlist) -> Result<(), anyhow::Error> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a playlist", path.display()))?;
+    let mut playlist = playlist.clone();
+    if let Some(base) = path.parent().filter(|p| p.is_absolute()) {
+        for entry in &mut playlist.entries {
+            if let Ok(relative) = entry.path.strip_prefix(base) {
+                entry.path = relative.to_path_buf();
+            }
+        }
+    }
+    let text = match format {
+        PlaylistFormat::M3u | PlaylistFormat::M3u8 => m3u::write(&playlist),
+        PlaylistFormat::Pls => pls::write(&playlist),
+        PlaylistFormat::Xspf => xspf::write(&playlist),
+    };
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// Text of playlist, UTF-8 with or without BOM, otherwise Latin-1 like old M3U files
+fn decode(data: &[u8]) -> String {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    match std::str::from_utf8(data) {
+        Ok(text) => text.to_string(),
+        Err(_) => data.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Absolute path of entry, relative ones are under base
+fn resolve(base: &Path, path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    let is_uri = text
+        .get(..7)
+        .is_some_and(|head| head.eq_ignore_ascii_case("file://"));
+    let path = if is_uri {
+        uri::to_path(&text)
+    } else if text.contains("://") {
+        // streams are not played, kept as written to be reported
+        return path.to_path_buf();
+    } else if cfg!(windows) {
+        path.to_path_buf()
+    } else {
+        // playlists written on windows use backslash
+        PathBuf::from(text.replace('\\', "/"))
+    };
+    if path.is_absolute() {
+        normalize(&path)
+    } else {
+        normalize(&base.join(path))
+    }
+}
+
+/// Remove `.` and `..` of path, so paths of same file are equal
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+use crate::service::playlist_service::models::{Playlist, PlaylistEntry};
+
+/// Parse M3U or M3U8 text, `#EXTINF` gives duration and title of the next entry.
+/// Paths are kept as written, loader resolves them.
+pub fn parse(text: &str) -> Playlist {
+    let mut playlist = Playlist::default();
+    // info of EXTINF line waiting for its path
+    let mut info: Option<(Option<f64>, Option<String>)> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("#EXTINF:") {
+            // duration, optional attributes, then title after the first comma outside quotes
+            let (head, title) = split_extinf(rest);
+            let duration = head
+                .split_whitespace()
+                .next()
+                .and_then(|d| d.parse::<f64>().ok())
+                .filter(|d| *d >= 0.0);
+            let title = Some(title.trim().to_string()).filter(|t| !t.is_empty());
+            info = Some((duration, title));
+            continue;
+        }
+        if let Some(title) = line.strip_prefix("#PLAYLIST:") {
+            playlist.title = Some(title.trim().to_string()).filter(|t| !t.is_empty());
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let (duration, title) = info.take().unwrap_or_default();
+        playlist.entries.push(PlaylistEntry {
+            path: PathBuf::from(line),
+            title,
+            artist: None,
+            duration,
+        });
+    }
+    playlist
+}
+
+/// Write playlist as extended M3U, paths are written as given.
+/// Both M3U and M3U8 are written in UTF-8
+pub fn write(playlist: &Playlist) -> String {
+    let mut text = String::from("#EXTM3U\n");
+    if let Some(title) = &playlist.title {
+        text.push_str(&format!("#PLAYLIST:{}\n", one_line(title)));
+    }
+    for entry in &playlist.entries {
+        let duration = entry.duration.map_or(-1, |d| d.round() as i64);
+        let title = entry.display_title().unwrap_or_else(|| {
+            entry
+                .path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+        text.push_str(&format!("#EXTINF:{},{}\n", duration, one_line(&title)));
+        text.push_str(&entry.path.to_string_lossy());
+        text.push('\n');
+    }
+    text
+}
+
+/// Split `-1 tvg-name="a,b",Title` into head and title
+fn split_extinf(s: &str) -> (&str, &str) {
+    let mut quoted = false;
+    for (ix, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => return (&s[..ix], &s[ix + 1..]),
+            _ => (),
+        }
+    }
+    (s, "")
+}
+
+fn one_line(s: &str) -> String {
+    s.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_entries() {
+        let playlist = Playlist {
+            title: Some("Road trip".to_string()),
+            entries: vec![
+                PlaylistEntry {
+                    path: PathBuf::from("/music/Björk - Joga.flac"),
+                    title: Some("Björk - Jóga".to_string()),
+                    artist: None,
+                    duration: Some(305.0),
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("relative/b.mp3"),
+                    title: Some("b".to_string()),
+                    artist: None,
+                    duration: None,
+                },
+            ],
+        };
+        assert_eq!(parse(&write(&playlist)), playlist);
+    }
+
+    #[test]
+    fn parses_extinf_attributes() {
+        let playlist =
+            parse("#EXTM3U\n#EXTINF:-1 tvg-name=\"a,b\",Radio\nhttp://x/stream\nplain.mp3\n");
+        assert_eq!(playlist.entries.len(), 2);
+        assert_eq!(playlist.entries[0].title.as_deref(), Some("Radio"));
+        assert_eq!(playlist.entries[0].duration, None);
+        assert_eq!(
+            playlist.entries[1],
+            PlaylistEntry::new(PathBuf::from("plain.mp3"))
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub mod loader;
+pub mod m3u;
+pub mod models;
+pub mod pls;
+pub mod uri;
+pub mod xspf;
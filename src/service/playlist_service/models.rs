@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+
+/// Formats of playlist files, known by extension
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlaylistFormat {
+    M3u,
+    M3u8,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_path<P>(path: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        let format = match ext.as_str() {
+            "m3u" => PlaylistFormat::M3u,
+            "m3u8" => PlaylistFormat::M3u8,
+            "pls" => PlaylistFormat::Pls,
+            "xspf" => PlaylistFormat::Xspf,
+            _ => return None,
+        };
+        Some(format)
+    }
+}
+
+/// A song of playlist, extra info is what the file gives
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlaylistEntry {
+    // absolute after loading, may be relative to playlist folder before saving
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    // seconds
+    pub duration: Option<f64>,
+}
+
+impl PlaylistEntry {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// Text shown for entry, like "Artist - Title"
+    pub fn display_title(&self) -> Option<String> {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => Some(format!("{} - {}", artist, title)),
+            (None, Some(title)) => Some(title.clone()),
+            (Some(artist), None) => Some(artist.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Songs of a playlist file in order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Playlist {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// Playlist loaded from file, with entries whose files do not exist
+pub struct LoadedPlaylist {
+    pub playlist: Playlist,
+    pub missing: Vec<PathBuf>,
+}
@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::service::playlist_service::models::{Playlist, PlaylistEntry};
+
+/// Parse PLS text, entries are ordered by their number.
+/// Paths are kept as written, loader resolves them.
+pub fn parse(text: &str) -> Playlist {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+    let mut playlist = Playlist::default();
+    for line in text.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        if key == "x-gnome-title" || key == "title" {
+            playlist.title = Some(value.to_string()).filter(|t| !t.is_empty());
+            continue;
+        }
+        // key like File1, Title1 or Length1
+        let split = key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len());
+        let Ok(number) = key[split..].parse::<u32>() else {
+            continue;
+        };
+        let entry = entries.entry(number).or_default();
+        match &key[..split] {
+            "file" => entry.path = PathBuf::from(value),
+            "title" => entry.title = Some(value.to_string()).filter(|t| !t.is_empty()),
+            // -1 for unknown length
+            "length" => entry.duration = value.parse::<f64>().ok().filter(|d| *d >= 0.0),
+            _ => (),
+        }
+    }
+    playlist.entries = entries
+        .into_values()
+        .filter(|e| !e.path.as_os_str().is_empty())
+        .collect();
+    playlist
+}
+
+/// Write playlist as PLS version 2, paths are written as given
+pub fn write(playlist: &Playlist) -> String {
+    let mut text = String::from("[playlist]\n");
+    for (ix, entry) in playlist.entries.iter().enumerate() {
+        let number = ix + 1;
+        text.push_str(&format!(
+            "File{}={}\n",
+            number,
+            entry.path.to_string_lossy()
+        ));
+        if let Some(title) = entry.display_title() {
+            text.push_str(&format!(
+                "Title{}={}\n",
+                number,
+                title.replace(['\r', '\n'], " ")
+            ));
+        }
+        let duration = entry.duration.map_or(-1, |d| d.round() as i64);
+        text.push_str(&format!("Length{}={}\n", number, duration));
+    }
+    text.push_str(&format!("NumberOfEntries={}\n", playlist.entries.len()));
+    text.push_str("Version=2\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_entries() {
+        let playlist = Playlist {
+            title: None,
+            entries: vec![
+                PlaylistEntry {
+                    path: PathBuf::from("/music/a.flac"),
+                    title: Some("Artist - A".to_string()),
+                    artist: None,
+                    duration: Some(200.0),
+                },
+                PlaylistEntry::new(PathBuf::from("b.mp3")),
+            ],
+        };
+        assert_eq!(parse(&write(&playlist)), playlist);
+    }
+
+    #[test]
+    fn orders_entries_by_number() {
+        let playlist = parse("[playlist]\nFile2=b.mp3\nfile1=a.mp3\nTitle3=no file\nLength1=-1\n");
+        let paths: Vec<_> = playlist.entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, [PathBuf::from("a.mp3"), PathBuf::from("b.mp3")]);
+        assert_eq!(playlist.entries[0].duration, None);
+    }
+}
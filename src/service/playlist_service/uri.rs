@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+/// Path of a location written as URI, like `file:///music/a%20b.mp3` or `a%20b.mp3`.
+/// Locations of other schemes are kept as they are
+pub fn to_path(location: &str) -> PathBuf {
+    if let Some(rest) = strip_prefix_ignore_case(location, "file://") {
+        // file://localhost/path is same as file:///path
+        let rest = strip_prefix_ignore_case(rest, "localhost").unwrap_or(rest);
+        let path = decode(rest);
+        // windows paths are written like file:///C:/music
+        let bytes = path.as_bytes();
+        if bytes.len() >= 3
+            && bytes[0] == b'/'
+            && bytes[1].is_ascii_alphabetic()
+            && bytes[2] == b':'
+        {
+            return PathBuf::from(&path[1..]);
+        }
+        return PathBuf::from(path);
+    }
+    if has_scheme(location) {
+        return PathBuf::from(location);
+    }
+    PathBuf::from(decode(location))
+}
+
+/// URI of path, `file://` for absolute paths and a relative reference otherwise
+pub fn from_path(path: &Path) -> String {
+    let text = path.to_string_lossy().replace('\\', "/");
+    if path.is_absolute() {
+        // windows paths start with drive, like C:/music
+        let slash = if text.starts_with('/') { "" } else { "/" };
+        format!("file://{}{}", slash, encode(&text, true))
+    } else {
+        encode(&text, false)
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = s.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &s[prefix.len()..])
+}
+
+/// Whether location starts with a scheme like `http:`, drive letters are not schemes
+fn has_scheme(s: &str) -> bool {
+    match s.split_once(':') {
+        Some((scheme, _)) => {
+            scheme.len() > 1
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// Decode %XX escapes, broken escapes are kept as they are
+pub fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut ix = 0;
+    while ix < bytes.len() {
+        if bytes[ix] == b'%'
+            && let Some(hex) = s.get(ix + 1..ix + 3)
+            && let Ok(b) = u8::from_str_radix(hex, 16)
+        {
+            out.push(b);
+            ix += 3;
+            continue;
+        }
+        out.push(bytes[ix]);
+        ix += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Escape bytes not allowed in URI path, colon is kept for drive of absolute path
+fn encode(s: &str, keep_colon: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                out.push(b as char)
+            }
+            b':' if keep_colon => out.push(':'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_drive_paths() {
+        assert_eq!(
+            to_path("file:///C:/Music/a%20b.mp3"),
+            PathBuf::from("C:/Music/a b.mp3")
+        );
+        assert_eq!(
+            to_path("FILE://localhost/d:/x.flac"),
+            PathBuf::from("d:/x.flac")
+        );
+        // a drive letter is not a scheme
+        assert_eq!(
+            to_path("C:%5Cmusic%5Cx.mp3"),
+            PathBuf::from("C:\\music\\x.mp3")
+        );
+    }
+
+    #[test]
+    fn keeps_other_schemes_and_broken_escapes() {
+        assert_eq!(
+            to_path("http://host/a%20b"),
+            PathBuf::from("http://host/a%20b")
+        );
+        assert_eq!(to_path("a%2"), PathBuf::from("a%2"));
+        assert_eq!(
+            to_path("file:///music/%C3%BC.ogg"),
+            PathBuf::from("/music/ü.ogg")
+        );
+    }
+
+    #[test]
+    fn round_trips_paths() {
+        for path in ["/music/a b#1?.mp3", "relative/ü.ogg"] {
+            let path = Path::new(path);
+            assert_eq!(to_path(&from_path(path)), path);
+        }
+    }
+}
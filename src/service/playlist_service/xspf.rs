@@ -0,0 +1,215 @@
+use crate::service::playlist_service::{
+    models::{Playlist, PlaylistEntry},
+    uri,
+};
+
+/// Parse XSPF document, locations are turned into paths but not resolved.
+/// Only elements used by players are read, unknown ones are skipped
+pub fn parse(text: &str) -> Playlist {
+    let text = strip_comments(text);
+    let (head, tracks) = match find_tag(&text, "trackList", 0) {
+        Some((start, _)) => text.split_at(start),
+        None => (text.as_str(), ""),
+    };
+
+    let mut entries = Vec::new();
+    let mut from = 0;
+    while let Some((start, end)) = find_tag(tracks, "track", from) {
+        // empty track like <track/>
+        if tracks[start..end].ends_with("/>") {
+            from = end;
+            continue;
+        }
+        let Some(close) = tracks[end..].find("</track>").map(|p| p + end) else {
+            break;
+        };
+        let track = &tracks[end..close];
+        from = close + "</track>".len();
+        // one location is used, the first that is given
+        let Some(location) = element_text(track, "location") else {
+            continue;
+        };
+        entries.push(PlaylistEntry {
+            path: uri::to_path(&location),
+            title: element_text(track, "title"),
+            artist: element_text(track, "creator"),
+            // milliseconds
+            duration: element_text(track, "duration")
+                .and_then(|d| d.parse::<f64>().ok())
+                .map(|ms| ms / 1000.0),
+        });
+    }
+    Playlist {
+        title: element_text(head, "title"),
+        entries,
+    }
+}
+
+/// Write playlist as XSPF version 1, paths are written as URIs
+pub fn write(playlist: &Playlist) -> String {
+    let mut text = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    text.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+    if let Some(title) = &playlist.title {
+        text.push_str(&format!("  <title>{}</title>\n", escape(title)));
+    }
+    text.push_str("  <trackList>\n");
+    for entry in &playlist.entries {
+        text.push_str("    <track>\n");
+        text.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape(&uri::from_path(&entry.path))
+        ));
+        if let Some(title) = &entry.title {
+            text.push_str(&format!("      <title>{}</title>\n", escape(title)));
+        }
+        if let Some(artist) = &entry.artist {
+            text.push_str(&format!("      <creator>{}</creator>\n", escape(artist)));
+        }
+        if let Some(duration) = entry.duration {
+            text.push_str(&format!(
+                "      <duration>{}</duration>\n",
+                (duration * 1000.0).round() as u64
+            ));
+        }
+        text.push_str("    </track>\n");
+    }
+    text.push_str("  </trackList>\n</playlist>\n");
+    text
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("<!--") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + 3..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Find start tag of element from position, return start of tag and end of tag
+fn find_tag(text: &str, name: &str, from: usize) -> Option<(usize, usize)> {
+    let open = format!("<{}", name);
+    let mut pos = from;
+    while let Some(found) = text[pos..].find(&open) {
+        let start = pos + found;
+        let after = start + open.len();
+        // skip elements whose name only starts with name, like <trackList> for <track>
+        match text[after..].chars().next() {
+            Some('>') => return Some((start, after + 1)),
+            Some(c) if c.is_whitespace() || c == '/' => {
+                let end = text[after..].find('>')? + after + 1;
+                return Some((start, end));
+            }
+            _ => pos = after,
+        }
+    }
+    None
+}
+
+/// Unescaped text of the first element with name, None if missing or empty
+fn element_text(text: &str, name: &str) -> Option<String> {
+    let (start, end) = find_tag(text, name, 0)?;
+    // empty element like <title/>
+    if text[start..end].ends_with("/>") {
+        return None;
+    }
+    let close = text[end..].find(&format!("</{}>", name))? + end;
+    let inner = &text[end..close];
+    let value = match inner
+        .trim()
+        .strip_prefix("<![CDATA[")
+        .and_then(|s| s.strip_suffix("]]>"))
+    {
+        Some(data) => data.to_string(),
+        None => unescape(inner),
+    };
+    let value = value.trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Replace entities like `&amp;` and `&#233;`, unknown ones are kept
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn round_trips_entries() {
+        let playlist = Playlist {
+            title: Some("Rock & <Roll>".to_string()),
+            entries: vec![
+                PlaylistEntry {
+                    path: PathBuf::from("/music/AC DC/Back in Black #1.mp3"),
+                    title: Some("Back in Black".to_string()),
+                    artist: Some("AC/DC".to_string()),
+                    duration: Some(255.5),
+                },
+                PlaylistEntry::new(PathBuf::from("relative/ü.ogg")),
+            ],
+        };
+        assert_eq!(parse(&write(&playlist)), playlist);
+    }
+
+    #[test]
+    fn skips_comments_and_reads_cdata() {
+        let text = r#"<playlist><!-- <title>no</title> --><title><![CDATA[A & B]]></title>
+            <trackList><track/><track><location>http://x/s.mp3</location></track></trackList></playlist>"#;
+        let playlist = parse(text);
+        assert_eq!(playlist.title.as_deref(), Some("A & B"));
+        assert_eq!(
+            playlist.entries,
+            [PlaylistEntry::new(PathBuf::from("http://x/s.mp3"))]
+        );
+    }
+}
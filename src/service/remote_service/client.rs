@@ -0,0 +1,54 @@
+use smol::channel::Sender;
+
+use crate::service::remote_service::models::{
+    LibrarySnapshot, PlayerStatus, QueueSong, RemoteCommand,
+};
+
+/// Handle for servers of remote controls to send commands and ask app for data
+#[derive(Clone)]
+pub struct RemoteClient {
+    commands: Sender<RemoteCommand>,
+}
+
+impl RemoteClient {
+    pub fn new(commands: Sender<RemoteCommand>) -> Self {
+        Self { commands }
+    }
+
+    /// Send command to app
+    pub fn send(&self, command: RemoteCommand) -> Result<(), anyhow::Error> {
+        self.commands
+            .try_send(command)
+            .map_err(|_| anyhow::anyhow!("player is closed"))
+    }
+
+    /// Get songs of queue from app
+    pub async fn queue(&self) -> Result<Vec<QueueSong>, anyhow::Error> {
+        let (sender, receiver) = smol::channel::bounded(1);
+        self.send(RemoteCommand::GetQueue(sender))?;
+        receiver
+            .recv()
+            .await
+            .map_err(|_| anyhow::anyhow!("player is closed"))
+    }
+
+    /// Get current status from app
+    pub async fn status(&self) -> Result<PlayerStatus, anyhow::Error> {
+        let (sender, receiver) = smol::channel::bounded(1);
+        self.send(RemoteCommand::GetStatus(sender))?;
+        receiver
+            .recv()
+            .await
+            .map_err(|_| anyhow::anyhow!("player is closed"))
+    }
+
+    /// Get library from app
+    pub async fn library(&self) -> Result<LibrarySnapshot, anyhow::Error> {
+        let (sender, receiver) = smol::channel::bounded(1);
+        self.send(RemoteCommand::GetLibrary(sender))?;
+        receiver
+            .recv()
+            .await
+            .map_err(|_| anyhow::anyhow!("player is closed"))
+    }
+}
@@ -0,0 +1,38 @@
+use std::{
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use lofty::{
+    file::TaggedFileExt,
+    picture::{MimeType, Picture},
+};
+
+/// Read cover picture of music file
+pub fn read(path: &Path) -> Option<Picture> {
+    let tagged = lofty::read_from_path(path).ok()?;
+    let tag = tagged.primary_tag().or(tagged.first_tag())?;
+    tag.pictures().first().cloned()
+}
+
+/// Write cover of music into temp folder, for clients which read covers by path.
+/// Return None if music has no cover
+pub fn export(path: &Path) -> Option<PathBuf> {
+    let picture = read(path)?;
+    let ext = match picture.mime_type() {
+        Some(MimeType::Png) => "png",
+        Some(MimeType::Gif) => "gif",
+        Some(MimeType::Bmp) => "bmp",
+        Some(MimeType::Tiff) => "tiff",
+        _ => "jpg",
+    };
+    let dir = env::temp_dir().join("the_player");
+    fs::create_dir_all(&dir).ok()?;
+    // a name for each song, so clients caching by path reload it
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    let file = dir.join(format!("cover-{:016x}.{}", hasher.finish(), ext));
+    fs::write(&file, picture.data()).ok()?;
+    Some(file)
+}
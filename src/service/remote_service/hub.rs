@@ -0,0 +1,56 @@
+use smol::channel::{Receiver, Sender};
+
+use crate::service::remote_service::models::{PlayerStatus, RemoteCommand};
+
+/// Connects remote controls with app, commands flow in and status flows out
+pub struct RemoteHub {
+    sender: Sender<RemoteCommand>,
+    receiver: Receiver<RemoteCommand>,
+    // status channels of remote controls
+    listeners: Vec<Sender<PlayerStatus>>,
+    // last published status
+    last: Option<PlayerStatus>,
+}
+
+impl RemoteHub {
+    /// Create a hub without remote controls
+    pub fn new() -> Self {
+        let (sender, receiver) = smol::channel::unbounded();
+        Self {
+            sender,
+            receiver,
+            listeners: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// Get sender for remote controls to send commands
+    pub fn command_sender(&self) -> Sender<RemoteCommand> {
+        self.sender.clone()
+    }
+
+    /// Get receiver of commands from all remote controls
+    pub fn commands(&self) -> Receiver<RemoteCommand> {
+        self.receiver.clone()
+    }
+
+    /// Register a remote control, it receives the last status and every change after it
+    pub fn subscribe(&mut self) -> Receiver<PlayerStatus> {
+        let (sender, receiver) = smol::channel::unbounded();
+        if let Some(status) = &self.last {
+            let _ = sender.try_send(status.clone());
+        }
+        self.listeners.push(sender);
+        receiver
+    }
+
+    /// Publish status if it changed, listeners closed are dropped
+    pub fn publish(&mut self, status: PlayerStatus) {
+        if self.last.as_ref() == Some(&status) {
+            return;
+        }
+        self.listeners
+            .retain(|l| l.try_send(status.clone()).is_ok());
+        self.last = Some(status);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod client;
+pub mod cover;
+pub mod hub;
+pub mod models;
@@ -0,0 +1,143 @@
+use std::{path::PathBuf, sync::Arc};
+
+use lofty::tag::{Accessor, ItemKey};
+use serde::{Deserialize, Serialize};
+use smol::channel::Sender;
+
+use crate::service::{
+    library_service::{library::Library, models::Track},
+    music_service::{
+        dsp::{DspEdit, DspStage},
+        models::PlayState,
+        music::Music,
+    },
+};
+
+/// A command sent by remote controls, applied to core by app
+#[derive(Clone, Debug)]
+pub enum RemoteCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    // seek by seconds from current position, negative goes back
+    SeekBy(f64),
+    // seek to seconds of current song
+    SeekTo(f64),
+    // volume from 0.0 to 1.0
+    SetVolume(f32),
+    // stereo balance from -1.0 (left) to 1.0 (right)
+    SetBalance(f32),
+    // add, remove, reorder or bypass a processor of chain
+    EditDsp(DspEdit),
+    // play on output device of name and save it, None for default device
+    SetOutputDevice(Option<String>),
+    // append songs to queue
+    Enqueue(Vec<PathBuf>),
+    // open files like launched with them, playlists are loaded
+    Open(Vec<PathBuf>),
+    // play song at index of queue
+    PlayAt(usize),
+    // remove song at index of queue
+    Remove(usize),
+    // move song of queue from index to another
+    Move(usize, usize),
+    Clear,
+    // reply songs of queue
+    GetQueue(Sender<Vec<QueueSong>>),
+    // reply library with its folders
+    GetLibrary(Sender<LibrarySnapshot>),
+    // reply current status
+    GetStatus(Sender<PlayerStatus>),
+}
+
+/// Library shared with remote controls, copied on write by core
+#[derive(Clone)]
+pub struct LibrarySnapshot {
+    pub library: Arc<Library>,
+    pub folders: Vec<PathBuf>,
+}
+
+/// Info of a song shown by remote controls
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SongInfo {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub number: Option<u32>,
+    pub year: Option<u32>,
+    // seconds
+    pub duration: f64,
+}
+
+impl SongInfo {
+    /// Read info from tags of music
+    pub fn from_music(music: &Music) -> Self {
+        let tags = music.get_tags();
+        let text = |key: ItemKey| tags.and_then(|t| t.get_string(&key)).map(|s| s.to_string());
+        Self {
+            path: music.get_path().clone(),
+            title: tags.and_then(|t| t.title()).map(|s| s.to_string()),
+            artist: tags.and_then(|t| t.artist()).map(|s| s.to_string()),
+            album: tags.and_then(|t| t.album()).map(|s| s.to_string()),
+            album_artist: text(ItemKey::AlbumArtist),
+            genre: tags.and_then(|t| t.genre()).map(|s| s.to_string()),
+            number: tags.and_then(|t| t.track()),
+            year: tags.and_then(|t| t.year()),
+            duration: music.get_duration().as_secs_f64(),
+        }
+    }
+
+    /// Info of library track
+    pub fn from_track(track: &Track) -> Self {
+        Self {
+            path: track.path.clone(),
+            title: Some(track.title.clone()),
+            artist: track.artist.clone(),
+            album: track.album.clone(),
+            album_artist: track.album_artist.clone(),
+            genre: track.genre.clone(),
+            number: track.track_number,
+            year: track.year,
+            duration: track.duration,
+        }
+    }
+}
+
+/// Song of queue with its id, which stays while the song is in queue
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueueSong {
+    pub id: u64,
+    // queue version when song was added or moved to its position
+    pub changed: u64,
+    #[serde(flatten)]
+    pub song: SongInfo,
+}
+
+/// Snapshot of player published to remote controls
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStatus {
+    pub state: PlayState,
+    // seconds played of current song
+    pub position: f64,
+    pub volume: f32,
+    // index of current song in queue
+    pub index: Option<usize>,
+    // queue ids of current song and the one after it
+    #[serde(default)]
+    pub song_id: Option<u64>,
+    #[serde(default)]
+    pub next_song_id: Option<u64>,
+    pub queue_len: usize,
+    // changes when songs of queue changed
+    pub queue_version: u64,
+    pub song: Option<SongInfo>,
+    // processors of chain in order
+    #[serde(default)]
+    pub dsp: Vec<DspStage>,
+}
@@ -1,35 +1,130 @@
-use std::time::Duration;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::{
     assets::icons,
-    service::music_service::{self, core::Core, models::PlayState},
-    ui::modules::button::Button,
-    utils::utils,
+    service::{
+        library_service::models::LibraryEvents,
+        loudness_service::models::ScanEvents,
+        lyrics_service::{loader, models::Lyrics},
+        music_service::{
+            self, core::Core, equalizer::Preset, models::PlayState, replaygain::ReplayGainMode,
+        },
+        playlist_service,
+        remote_service::{hub::RemoteHub, models::RemoteCommand},
+    },
+    ui::modules::{
+        button::Button,
+        library_view::{LibraryAction, LibraryView},
+        lyrics_view::LyricsView,
+    },
+    utils::{palette::Palette, utils},
 };
 use gpui::{
-    AsyncApp, ClickEvent, Context, ExternalPaths, ImageSource, MouseDownEvent, SharedString, Task,
-    WeakEntity, Window, div, img, prelude::*, px, relative, rgb, rgba, svg,
+    AsyncApp, ClickEvent, Context, Entity, ExternalPaths, ImageSource, MouseDownEvent,
+    PathPromptOptions, ScrollHandle, SharedString, Task, WeakEntity, Window, div, img, point,
+    prelude::*, px, relative, rgb, rgba, svg,
 };
+use smol::channel::Receiver;
 use symphonia::core::units::Time;
 
 pub struct MyApp {
     music_core: music_service::core::Core,
     refresh_task: Option<Task<()>>,
     volume: f32,
+    eq_preset: usize,
     message: String,
     msg_timer: Option<Task<()>>,
+    // lyrics of current song
+    show_lyrics: bool,
+    lyrics: Option<Rc<Lyrics>>,
+    lyrics_path: Option<PathBuf>,
+    lyrics_line: Option<usize>,
+    lyrics_scroll: ScrollHandle,
+    // colors from cover, animated from old palette to new one
+    palette_from: Palette,
+    palette_to: Palette,
+    palette_start: Option<Instant>,
+    palette_path: Option<PathBuf>,
+    palette_task: Option<Task<()>>,
+    palette_anim: Option<Task<()>>,
+    // running library scan and watcher
+    library_task: Option<Task<()>>,
+    watch_task: Option<Task<()>>,
+    // running loudness scan
+    loudness_task: Option<Task<()>>,
+    // play stats are waiting to be saved
+    stats_scheduled: bool,
+    // last library save, the next one waits for it so that saves land in order
+    library_save: Option<Task<()>>,
+    library_view: Entity<LibraryView>,
+    // remote controls like MPRIS, their commands are applied to core
+    remote: RemoteHub,
+    // applies remote commands while held
+    _remote_task: Task<()>,
 }
 
+// width of player panel beside library
+static PLAYER_WIDTH: f32 = 500.0;
+
+// duration of palette transition
+static PALETTE_TRANSITION: Duration = Duration::from_millis(600);
+
+// delay of saving play stats, plays during it are saved together
+static STATS_SAVE_DELAY: Duration = Duration::from_secs(10);
+
+// crossfade seconds switched by button, 0 disables it
+static CROSSFADE_STEPS: [f32; 4] = [0.0, 3.0, 6.0, 10.0];
+
 impl MyApp {
     /// Init app struct
-    pub fn init() -> Self {
-        Self {
-            music_core: Core::new(),
+    pub fn init(cx: &mut Context<Self>) -> Self {
+        let music_core = Core::new();
+        let library_view = cx.new(|cx| {
+            let mut view = LibraryView::new(music_core.library().clone(), cx);
+            view.set_playlists(music_core.smart_playlists().to_vec());
+            view
+        });
+        cx.subscribe(&library_view, Self::handle_library_action)
+            .detach();
+        let remote = RemoteHub::new();
+        let remote_task = Self::spawn_remote_commands(remote.commands(), cx);
+        let mut app = Self {
+            music_core,
             refresh_task: None,
             volume: 1.0,
+            eq_preset: 0,
             message: "".into(),
             msg_timer: None,
+            show_lyrics: false,
+            lyrics: None,
+            lyrics_path: None,
+            lyrics_line: None,
+            lyrics_scroll: ScrollHandle::new(),
+            palette_from: Palette::default(),
+            palette_to: Palette::default(),
+            palette_start: None,
+            palette_path: None,
+            palette_task: None,
+            palette_anim: None,
+            library_task: None,
+            watch_task: None,
+            loudness_task: None,
+            stats_scheduled: false,
+            library_save: None,
+            library_view,
+            remote,
+            _remote_task: remote_task,
+        };
+        if let Some(e) = app.music_core.smart_playlists_error() {
+            let msg = format!("Smart playlists not loaded: {}", e);
+            app.show_msg(cx, msg, Duration::from_secs(10));
         }
+        app
     }
 
     fn show_msg(&mut self, cx: &mut Context<Self>, msg: String, duration: Duration) {
@@ -61,41 +156,465 @@ impl MyApp {
 
     /// Get name of current song
     fn current_name(&self) -> SharedString {
-        if let Some(music) = &self.music_core.current() {
-            if let Some(tags) = music.get_tags() {
-                if let Some(title) = tags.get_string(&lofty::tag::ItemKey::TrackTitle) {
-                    return SharedString::new(title);
-                }
-            }
+        if let Some(music) = &self.music_core.current()
+            && let Some(tags) = music.get_tags()
+            && let Some(title) = tags.get_string(&lofty::tag::ItemKey::TrackTitle)
+        {
+            return SharedString::new(title);
         }
         SharedString::new("-")
     }
 
     /// Get cover picture of current song
     fn current_picture(&self) -> Option<ImageSource> {
-        let Some(music) = self.music_core.current() else {
-            return None;
-        };
-        let Some(tags) = music.get_tags() else {
-            return None;
-        };
-        let Some(pic) = tags.pictures().first() else {
-            return None;
-        };
-        let Some(src) = utils::convert_picture(pic) else {
-            return None;
-        };
-        Some(src)
+        let music = self.music_core.current()?;
+        let tags = music.get_tags()?;
+        let pic = tags.pictures().first()?;
+        utils::convert_picture(pic)
     }
 
     /// Get current play progress
     fn current_progress(&self) -> f32 {
-        if let Some(p) = self.music_core.player() {
+        if let Some(p) = self.music_core.player()
+            && p.play_time().duration_sec() > 0
+        {
             return p.play_time().played_sec() as f32 / p.play_time().duration_sec() as f32;
         }
         0.
     }
 
+    /// Load lyrics if song changed and follow the line being sung
+    fn sync_lyrics(&mut self) {
+        let path = self.music_core.current().map(|m| m.get_path().clone());
+        if path != self.lyrics_path {
+            self.lyrics = self
+                .music_core
+                .current()
+                .and_then(loader::load)
+                .map(Rc::new);
+            self.lyrics_path = path;
+            self.lyrics_line = None;
+            self.lyrics_scroll.set_offset(point(px(0.0), px(0.0)));
+        }
+
+        let line = match (&self.lyrics, self.music_core.player()) {
+            (Some(lyrics), Some(p)) => {
+                let time = p.play_time().played_time();
+                lyrics.index_at(time.seconds as f64 + time.frac)
+            }
+            _ => None,
+        };
+        if line != self.lyrics_line {
+            self.lyrics_line = line;
+            if let (Some(ix), Some(lyrics)) = (line, &self.lyrics) {
+                LyricsView::scroll_to_line(&self.lyrics_scroll, lyrics, ix);
+            }
+        }
+    }
+
+    /// Scan library folders in background and apply changes when finished
+    pub fn scan_library(&mut self, cx: &mut Context<Self>) {
+        let receiver = self.music_core.scan_library();
+        self.library_task = Some(Self::spawn_library_events(receiver, cx));
+    }
+
+    /// Watch library folders and apply changes as they happen
+    pub fn watch_library(&mut self, cx: &mut Context<Self>) {
+        match self.music_core.watch_library() {
+            Ok(receiver) => self.watch_task = Some(Self::spawn_library_events(receiver, cx)),
+            Err(e) => self.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6)),
+        }
+    }
+
+    fn spawn_library_events(receiver: Receiver<LibraryEvents>, cx: &mut Context<Self>) -> Task<()> {
+        cx.spawn(async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
+            while let Ok(event) = receiver.recv().await {
+                if weak
+                    .update(cx, |app, cx| app.handle_library_event(event, cx))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        })
+    }
+
+    fn handle_library_event(&mut self, event: LibraryEvents, cx: &mut Context<Self>) {
+        match event {
+            LibraryEvents::Started(total) if total > 0 => {
+                let msg = format!("Scanning library: {} files", total);
+                self.show_msg(cx, msg, Duration::from_secs(3));
+            }
+            LibraryEvents::Progress(done, total, path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let msg = format!("Scanning library: {}/{} {}", done + 1, total, name);
+                self.show_msg(cx, msg, Duration::from_secs(3));
+            }
+            LibraryEvents::Failed(path, e) => {
+                eprintln!("failed to read {}: {}", path.display(), e)
+            }
+            LibraryEvents::Finished(changes) | LibraryEvents::Changed(changes) => {
+                self.music_core.apply_library_changes(changes);
+                self.save_library(cx);
+                cx.notify();
+            }
+            _ => (),
+        }
+    }
+
+    /// Measure loudness of musics in background and write their ReplayGain tags
+    fn scan_loudness(&mut self, paths: Vec<PathBuf>, cx: &mut Context<Self>) {
+        let receiver = self.music_core.scan_loudness(paths);
+        self.loudness_task = Some(cx.spawn(
+            async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
+                while let Ok(event) = receiver.recv().await {
+                    if weak
+                        .update(cx, |app, cx| app.handle_loudness_event(event, cx))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            },
+        ));
+    }
+
+    fn handle_loudness_event(&mut self, event: ScanEvents, cx: &mut Context<Self>) {
+        match event {
+            ScanEvents::Started(0) => {
+                let msg = "Loudness is analyzed already".to_string();
+                self.show_msg(cx, msg, Duration::from_secs(3));
+            }
+            ScanEvents::Progress(done, total, path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let msg = format!("Analyzing loudness: {}/{} {}", done + 1, total, name);
+                self.show_msg(cx, msg, Duration::from_secs(60));
+            }
+            ScanEvents::Scanned(result) => {
+                let name = result
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                let msg = format!(
+                    "{}: {:.1} LUFS, gain {:+.1} dB",
+                    name, result.loudness, result.track_gain
+                );
+                self.show_msg(cx, msg, Duration::from_secs(60));
+            }
+            ScanEvents::Failed(path, e) => {
+                eprintln!("failed to analyze {}: {}", path.display(), e)
+            }
+            ScanEvents::Finished => {
+                self.show_msg(cx, "Loudness analyzed".into(), Duration::from_secs(3));
+            }
+            _ => (),
+        }
+    }
+
+    /// Pass library to view if it changed, like play counts after song changed
+    fn sync_library(&mut self, cx: &mut Context<Self>) {
+        self.schedule_stats_save(cx);
+        let library = self.music_core.library();
+        if Arc::ptr_eq(library, self.library_view.read(cx).library()) {
+            return;
+        }
+        let library = library.clone();
+        self.library_view
+            .update(cx, |view, _| view.set_library(library));
+    }
+
+    /// Count plays into library a while after they happened and save it in background
+    fn schedule_stats_save(&mut self, cx: &mut Context<Self>) {
+        if self.stats_scheduled || !self.music_core.has_pending_stats() {
+            return;
+        }
+        self.stats_scheduled = true;
+        cx.spawn(async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
+            cx.background_executor().timer(STATS_SAVE_DELAY).await;
+            let _ = weak.update(cx, |app, cx| {
+                app.stats_scheduled = false;
+                app.save_library(cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Save library with batched stats in background if it changed
+    fn save_library(&mut self, cx: &mut Context<Self>) {
+        let Some(library) = self.music_core.flush_stats() else {
+            return;
+        };
+        let previous = self.library_save.take();
+        self.library_save = Some(cx.background_executor().spawn(async move {
+            if let Some(p) = previous {
+                p.await;
+            }
+            if let Err(e) = library.save() {
+                eprintln!("failed to save library: {}", e);
+            }
+        }));
+    }
+
+    fn handle_library_action(
+        &mut self,
+        _: Entity<LibraryView>,
+        action: &LibraryAction,
+        cx: &mut Context<Self>,
+    ) {
+        let result = match action {
+            LibraryAction::Play(paths, index) => self.music_core.play_paths(paths.clone(), *index),
+            LibraryAction::PlayNext(paths) => self.music_core.play_next(paths.clone()),
+            LibraryAction::Enqueue(paths) => paths
+                .iter()
+                .try_for_each(|p| self.music_core.append(p.clone())),
+            LibraryAction::ScanLoudness(paths) => {
+                self.scan_loudness(paths.clone(), cx);
+                Ok(())
+            }
+            LibraryAction::Rate(paths, rating) => paths
+                .iter()
+                .try_for_each(|p| self.music_core.set_rating(p, *rating)),
+            LibraryAction::SavePlaylist(index, playlist) => self
+                .music_core
+                .save_smart_playlist(*index, playlist.clone())
+                .map(|_| ()),
+            LibraryAction::RemovePlaylist(index) => self.music_core.remove_smart_playlist(*index),
+            LibraryAction::OpenPlaylistFiles => {
+                self.open_playlist_files(cx);
+                Ok(())
+            }
+            LibraryAction::SaveQueue => {
+                self.save_queue(cx);
+                Ok(())
+            }
+        };
+        if let LibraryAction::SavePlaylist(..) | LibraryAction::RemovePlaylist(_) = action {
+            let playlists = self.music_core.smart_playlists().to_vec();
+            self.library_view
+                .update(cx, |view, _| view.set_playlists(playlists));
+        }
+        if let LibraryAction::Rate(..) = action {
+            self.sync_library(cx);
+        }
+        if let Err(e) = result {
+            self.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6));
+        }
+        self.spawn_refresh(cx);
+        cx.notify();
+    }
+
+    /// Serve MPRIS on session bus, for media keys and desktop widgets
+    #[cfg(target_os = "linux")]
+    pub fn start_mpris(&mut self) {
+        crate::service::mpris_service::server::start(
+            self.remote.command_sender(),
+            self.remote.subscribe(),
+        );
+    }
+
+    /// Serve socket of this user, so later launches open their files in this window
+    #[cfg(unix)]
+    pub fn start_ipc(&mut self) {
+        crate::service::ipc_service::server::start(self.remote.command_sender());
+    }
+
+    /// Serve MPD protocol if an address is configured
+    pub fn start_mpd(&mut self) {
+        if let Some(address) = self.music_core.mpd_address() {
+            crate::service::mpd_service::server::start(
+                address.to_string(),
+                self.remote.command_sender(),
+                self.remote.subscribe(),
+            );
+        }
+    }
+
+    /// Serve HTTP remote API if a port is configured
+    pub fn start_http(&mut self) {
+        if let Some((address, token)) = self.music_core.http_config() {
+            crate::service::http_service::server::start(
+                address,
+                token,
+                self.remote.command_sender(),
+                self.remote.subscribe(),
+            );
+        }
+    }
+
+    fn spawn_remote_commands(
+        receiver: Receiver<RemoteCommand>,
+        cx: &mut Context<Self>,
+    ) -> Task<()> {
+        cx.spawn(async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
+            while let Ok(command) = receiver.recv().await {
+                if weak
+                    .update(cx, |app, cx| app.handle_remote_command(command, cx))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Apply command of remote controls to core
+    fn handle_remote_command(&mut self, command: RemoteCommand, cx: &mut Context<Self>) {
+        // files opened by another launch are opened as given to this window
+        if let RemoteCommand::Open(paths) = command {
+            self.open_paths(&paths, cx);
+            self.sync_remote();
+            cx.activate(true);
+            return;
+        }
+        if let Err(e) = self.music_core.apply_command(command) {
+            self.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6));
+        }
+        self.volume = self.music_core.get_gain();
+        self.sync_remote();
+        self.spawn_refresh(cx);
+        cx.notify();
+    }
+
+    /// Publish status to remote controls if it changed
+    fn sync_remote(&mut self) {
+        self.remote.publish(self.music_core.status());
+    }
+
+    /// Append songs of playlist file to queue, entries not found are reported
+    fn load_playlist(&mut self, path: &Path, cx: &mut Context<Self>) {
+        match self.music_core.load_playlist(path) {
+            Ok(missing) if missing.is_empty() => (),
+            Ok(missing) => {
+                for entry in &missing {
+                    eprintln!("playlist entry not found: {}", entry.display());
+                }
+                let msg = format!("{} songs of playlist not found", missing.len());
+                self.show_msg(cx, msg, Duration::from_secs(6));
+            }
+            Err(e) => self.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6)),
+        }
+    }
+
+    /// Ask for playlist files and load them into queue
+    fn open_playlist_files(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: true,
+            prompt: Some("Open playlist".into()),
+        });
+        cx.spawn(async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let _ = weak.update(cx, |app, cx| {
+                for path in paths {
+                    app.load_playlist(&path, cx);
+                }
+                app.spawn_refresh(cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Ask for a file and save queue into it, M3U8 if extension is not a playlist
+    fn save_queue(&mut self, cx: &mut Context<Self>) {
+        let dir = self
+            .music_core
+            .library_folders()
+            .first()
+            .cloned()
+            .or_else(|| std::env::var_os("HOME").map(PathBuf::from))
+            .unwrap_or_default();
+        let path = cx.prompt_for_new_path(&dir, Some("queue.m3u8"));
+        cx.spawn(async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
+            let Ok(Ok(Some(mut path))) = path.await else {
+                return;
+            };
+            if !playlist_service::loader::is_playlist(&path) {
+                path.set_extension("m3u8");
+            }
+            let _ = weak.update(cx, |app, cx| {
+                let msg = match app.music_core.save_queue_as_playlist(&path) {
+                    Ok(()) => format!("Queue saved to {}", path.display()),
+                    Err(e) => format!("Error: {}", e),
+                };
+                app.show_msg(cx, msg, Duration::from_secs(4));
+            });
+        })
+        .detach();
+    }
+
+    /// Extract palette from cover in background if song changed
+    fn sync_palette(&mut self, cx: &mut Context<Self>) {
+        let path = self.music_core.current().map(|m| m.get_path().clone());
+        if path == self.palette_path {
+            return;
+        }
+        self.palette_path = path;
+
+        let data = self
+            .music_core
+            .current()
+            .and_then(|m| m.get_tags())
+            .and_then(|t| t.pictures().first())
+            .map(|p| p.data().to_vec());
+        let Some(data) = data else {
+            self.palette_task = None;
+            self.start_palette_transition(Palette::default(), cx);
+            return;
+        };
+        self.palette_task = Some(cx.spawn(
+            async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
+                let palette = cx
+                    .background_executor()
+                    .spawn(async move { Palette::from_image(&data) })
+                    .await;
+                let _ = weak.update(cx, |app, cx| {
+                    app.start_palette_transition(palette.unwrap_or_default(), cx)
+                });
+            },
+        ));
+    }
+
+    /// Animate from current colors to palette
+    fn start_palette_transition(&mut self, palette: Palette, cx: &mut Context<Self>) {
+        self.palette_from = self.current_palette();
+        self.palette_to = palette;
+        self.palette_start = Some(Instant::now());
+        self.palette_anim = Some(cx.spawn(
+            async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
+                loop {
+                    cx.background_executor()
+                        .timer(Duration::from_millis(16))
+                        .await;
+                    let r = weak.update(cx, |app, cx| {
+                        cx.notify();
+                        app.palette_start
+                            .is_none_or(|s| s.elapsed() >= PALETTE_TRANSITION)
+                    });
+                    if r.unwrap_or(true) {
+                        break;
+                    }
+                }
+            },
+        ));
+    }
+
+    /// Get colors of this frame
+    fn current_palette(&self) -> Palette {
+        let Some(start) = self.palette_start else {
+            return self.palette_to;
+        };
+        let t = (start.elapsed().as_secs_f32() / PALETTE_TRANSITION.as_secs_f32()).min(1.0);
+        // ease in out
+        let t = t * t * (3.0 - 2.0 * t);
+        self.palette_from.mix(&self.palette_to, t)
+    }
+
     /// File deop event
     fn handle_file_drop(
         &mut self,
@@ -103,21 +622,43 @@ impl MyApp {
         _window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        // try get path
-        if let Some(path) = event.paths().first() {
+        self.open_paths(event.paths(), cx);
+    }
+
+    /// Open files given by drop or command line
+    pub fn open_paths(&mut self, paths: &[PathBuf], cx: &mut Context<Self>) {
+        // append every file to queue, folders are added into library
+        // and songs of playlist files are appended
+        let mut folder_added = false;
+        for path in paths {
+            if path.is_dir() {
+                match self.music_core.add_library_folder(path.clone()) {
+                    Ok(()) => folder_added = true,
+                    Err(e) => self.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6)),
+                }
+                continue;
+            }
             // check whether file
             if !path.is_file() {
-                return;
+                continue;
+            }
+            if playlist_service::loader::is_playlist(path) {
+                self.load_playlist(path, cx);
+                continue;
             }
             // append to player
             if let Err(e) = self.music_core.append(path.clone()) {
                 self.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6));
             }
-            // start refresh page
-            self.spawn_refresh(cx);
-            // update view
-            cx.notify();
         }
+        if folder_added {
+            self.scan_library(cx);
+            self.watch_library(cx);
+        }
+        // start refresh page
+        self.spawn_refresh(cx);
+        // update view
+        cx.notify();
     }
 
     /// Switch player state
@@ -141,23 +682,58 @@ impl MyApp {
         cx.notify();
     }
 
+    /// Play next song of queue
+    fn handle_next(&mut self, _: &ClickEvent, _: &mut Window, cx: &mut Context<Self>) {
+        if let Err(e) = self.music_core.next() {
+            self.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6));
+        }
+        cx.notify();
+    }
+
+    /// Play previous song of queue
+    fn handle_previous(&mut self, _: &ClickEvent, _: &mut Window, cx: &mut Context<Self>) {
+        if let Err(e) = self.music_core.previous() {
+            self.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6));
+        }
+        cx.notify();
+    }
+
     /// spawn a refresh task to refresh indicater during playing
     fn spawn_refresh(&mut self, _cx: &mut Context<Self>) {
-        let Some(p) = self.music_core.player() else {
+        if self.music_core.player().is_none() {
             return;
-        };
-        let rx = p.receiver();
+        }
+        let rx = self.music_core.receiver();
 
         let t = _cx.spawn(async move |weak: WeakEntity<MyApp>, cx: &mut AsyncApp| {
             while let Ok(e) = rx.recv().await {
                 let r = weak.update(cx, |app, cx| {
                     match e {
                         music_service::models::Events::PlaytimeRefresh => (),
-                        music_service::models::Events::PlayFinished => app.music_core.stop(),
+                        music_service::models::Events::TrackChanged(id) => {
+                            app.music_core.handle_track_changed(id)
+                        }
+                        music_service::models::Events::PlayFinished => {
+                            if let Err(e) = app.music_core.handle_finished() {
+                                app.show_msg(cx, format!("Error: {}", e), Duration::from_secs(6));
+                            }
+                        }
+                        music_service::models::Events::DeviceFallback(name) => app.show_msg(
+                            cx,
+                            format!("Device {} not found, using default", name),
+                            Duration::from_secs(6),
+                        ),
+                        music_service::models::Events::DeviceLost(name) => {
+                            let msg = match app.music_core.handle_device_lost() {
+                                Ok(()) => format!("Device {} lost, switched output", name),
+                                Err(e) => format!("Device {} lost: {}", name, e),
+                            };
+                            app.show_msg(cx, msg, Duration::from_secs(6));
+                        }
                     };
                     cx.notify();
                 });
-                if let Err(_) = r {
+                if r.is_err() {
                     break;
                 }
             }
@@ -189,14 +765,71 @@ impl MyApp {
         self.music_core.set_gain(self.volume);
     }
 
+    fn handle_switch_eq(&mut self, _: &ClickEvent, _: &mut Window, cx: &mut Context<Self>) {
+        self.eq_preset = (self.eq_preset + 1) % Preset::ALL.len();
+        let preset = Preset::ALL[self.eq_preset];
+        let msg = match self.music_core.apply_eq_preset(preset) {
+            Ok(()) => format!("EQ {}", preset.name()),
+            Err(e) => format!("Error: {}", e),
+        };
+        self.show_msg(cx, msg, Duration::from_secs(2));
+    }
+
+    fn handle_switch_crossfade(&mut self, _: &ClickEvent, _: &mut Window, cx: &mut Context<Self>) {
+        let current = self.music_core.get_crossfade();
+        let secs = CROSSFADE_STEPS
+            .iter()
+            .copied()
+            .find(|s| *s > current)
+            .unwrap_or(0.0);
+        let msg = match self.music_core.set_crossfade(secs) {
+            Ok(()) if secs > 0.0 => format!("Crossfade {}s", secs),
+            Ok(()) => "Crossfade off".to_string(),
+            Err(e) => format!("Error: {}", e),
+        };
+        self.show_msg(cx, msg, Duration::from_secs(2));
+    }
+
+    fn handle_switch_replay_gain(
+        &mut self,
+        _: &ClickEvent,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let (mode, preamp) = self.music_core.get_replay_gain();
+        let ix = ReplayGainMode::ALL.iter().position(|m| *m == mode);
+        let mode = ReplayGainMode::ALL[ix.map_or(0, |i| (i + 1) % ReplayGainMode::ALL.len())];
+        let msg = match self.music_core.set_replay_gain(mode, preamp) {
+            Ok(()) => format!("ReplayGain {}", mode.name()),
+            Err(e) => format!("Error: {}", e),
+        };
+        self.show_msg(cx, msg, Duration::from_secs(2));
+    }
+
+    fn handle_switch_lyrics(&mut self, _: &ClickEvent, _: &mut Window, cx: &mut Context<Self>) {
+        self.show_lyrics = !self.show_lyrics;
+        // scroll to current line when shown
+        self.lyrics_line = None;
+        cx.notify();
+    }
+
+    /// Seek to time (seconds) of a lyric line
+    fn seek_to_lyric(&mut self, time: f64, cx: &mut Context<Self>) {
+        if let Some(p) = self.music_core.player() {
+            p.seek_to(Time::from(time));
+            cx.notify();
+        }
+    }
+
     fn handle_process_click(
         &mut self,
         event: &MouseDownEvent,
-        window: &mut Window,
+        _: &mut Window,
         cx: &mut Context<Self>,
     ) {
         if let Some(p) = self.music_core.player() {
-            let per = event.position.x.to_f64() / window.viewport_size().width.to_f64();
+            // progress bar spans the player panel, which is the left one of window
+            let per = (event.position.x.to_f64() / PLAYER_WIDTH as f64).clamp(0.0, 1.0);
             let time_point = (p.play_time().duration_sec() as f64 * per + 0.5).floor();
             p.seek_to(Time::from(time_point));
             cx.notify();
@@ -206,41 +839,109 @@ impl MyApp {
 
 impl Render for MyApp {
     fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
-        div()
-            .w_full()
+        self.sync_lyrics();
+        self.sync_palette(_cx);
+        self.sync_library(_cx);
+        self.sync_remote();
+        let palette = self.current_palette();
+        let time = self.music_core.player().map_or(0.0, |p| {
+            let t = p.play_time().played_time();
+            t.seconds as f64 + t.frac
+        });
+        let weak = _cx.entity().downgrade();
+        let lyrics_view = LyricsView::new("lyrics", self.lyrics_scroll.clone())
+            .lyrics(self.lyrics.clone(), self.lyrics_line, time)
+            .on_seek(move |time, _, cx| {
+                let _ = weak.update(cx, |app, cx| app.seek_to_lyric(time, cx));
+            });
+
+        let player = div()
+            .w(px(PLAYER_WIDTH))
+            .flex_none()
             .h_full()
-            .on_drop(_cx.listener(Self::handle_file_drop))
             .child(
                 div()
                     .relative()
                     .w_full()
                     .h_2_3()
-                    .bg(rgb(0x398ad7))
-                    .text_color(gpui::white())
+                    .bg(rgb(palette.background))
+                    .text_color(rgb(palette.text))
                     .flex()
                     .flex_col()
                     .justify_center()
                     .items_center()
                     .text_align(gpui::TextAlign::Center)
-                    .child(div().text_xl().child(self.current_status()))
-                    .child(if self.current_picture().is_none() {
+                    .child(
                         div()
-                    } else {
-                        div().child(
-                            img(self.current_picture().unwrap())
-                                .size(px(150.0))
-                                .rounded_md(),
-                        )
+                            .absolute()
+                            .top_2()
+                            .right_2()
+                            .flex()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .id("crossfade_switch")
+                                    .cursor_pointer()
+                                    .child(
+                                        svg()
+                                            .path(icons::CROSSFADE)
+                                            .w(px(22.0))
+                                            .h(px(22.0))
+                                            .text_color(gpui::white()),
+                                    )
+                                    .on_click(_cx.listener(Self::handle_switch_crossfade)),
+                            )
+                            .child(
+                                div()
+                                    .id("replay_gain_switch")
+                                    .cursor_pointer()
+                                    .child(
+                                        svg()
+                                            .path(icons::REPLAY_GAIN)
+                                            .w(px(22.0))
+                                            .h(px(22.0))
+                                            .text_color(gpui::white()),
+                                    )
+                                    .on_click(_cx.listener(Self::handle_switch_replay_gain)),
+                            )
+                            .child(
+                                div()
+                                    .id("lyrics_switch")
+                                    .cursor_pointer()
+                                    .child(
+                                        svg()
+                                            .path(icons::LYRICS)
+                                            .w(px(22.0))
+                                            .h(px(22.0))
+                                            .text_color(gpui::white()),
+                                    )
+                                    .on_click(_cx.listener(Self::handle_switch_lyrics)),
+                            ),
+                    )
+                    .when(self.show_lyrics, |this| {
+                        this.child(div().w_full().h_full().py_8().child(lyrics_view))
                     })
-                    .child(div().text_3xl().child(self.current_name()))
-                    .child(if let Some(p) = self.music_core.player() {
-                        format!(
-                            "{} / {}",
-                            utils::format_time(p.play_time().played_sec()),
-                            utils::format_time(p.play_time().duration_sec()),
-                        )
-                    } else {
-                        "".to_string()
+                    .when(!self.show_lyrics, |this| {
+                        this.child(div().text_xl().child(self.current_status()))
+                            .child(if self.current_picture().is_none() {
+                                div()
+                            } else {
+                                div().child(
+                                    img(self.current_picture().unwrap())
+                                        .size(px(150.0))
+                                        .rounded_md(),
+                                )
+                            })
+                            .child(div().text_3xl().child(self.current_name()))
+                            .child(if let Some(p) = self.music_core.player() {
+                                format!(
+                                    "{} / {}",
+                                    utils::format_time(p.play_time().played_sec()),
+                                    utils::format_time(p.play_time().duration_sec()),
+                                )
+                            } else {
+                                "".to_string()
+                            })
                     })
                     .child(
                         div()
@@ -283,6 +984,7 @@ impl Render for MyApp {
                     )
                     .child(
                         Button::new("volume")
+                            .color(rgb(palette.accent))
                             .child(
                                 svg()
                                     .path(match self.volume {
@@ -296,8 +998,33 @@ impl Render for MyApp {
                             )
                             .on_click(_cx.listener(Self::handle_switch_volume)),
                     )
+                    .child(
+                        Button::new("equalizer")
+                            .color(rgb(palette.accent))
+                            .child(
+                                svg()
+                                    .path(icons::EQUALIZER)
+                                    .w(px(26.0))
+                                    .h(px(26.0))
+                                    .text_color(gpui::white()),
+                            )
+                            .on_click(_cx.listener(Self::handle_switch_eq)),
+                    )
+                    .child(
+                        Button::new("button_previous")
+                            .color(rgb(palette.accent))
+                            .child(
+                                svg()
+                                    .path(icons::SKIP_PREVIOUS)
+                                    .w(px(26.0))
+                                    .h(px(26.0))
+                                    .text_color(gpui::white()),
+                            )
+                            .on_click(_cx.listener(Self::handle_previous)),
+                    )
                     .child(
                         Button::new("button_play_pause")
+                            .color(rgb(palette.accent))
                             .on_click(_cx.listener(Self::handle_switch_player))
                             .child(
                                 svg()
@@ -307,8 +1034,21 @@ impl Render for MyApp {
                                     .text_color(gpui::white()),
                             ),
                     )
+                    .child(
+                        Button::new("button_next")
+                            .color(rgb(palette.accent))
+                            .child(
+                                svg()
+                                    .path(icons::SKIP_NEXT)
+                                    .w(px(26.0))
+                                    .h(px(26.0))
+                                    .text_color(gpui::white()),
+                            )
+                            .on_click(_cx.listener(Self::handle_next)),
+                    )
                     .child(
                         Button::new("button_stop")
+                            .color(rgb(palette.accent))
                             .child(
                                 svg()
                                     .path(icons::STOP_FILLED)
@@ -318,6 +1058,21 @@ impl Render for MyApp {
                             )
                             .on_click(_cx.listener(Self::handle_drop_core)),
                     ),
+            );
+
+        div()
+            .size_full()
+            .flex()
+            .flex_row()
+            .on_drop(_cx.listener(Self::handle_file_drop))
+            .child(player)
+            .child(
+                div()
+                    .flex_1()
+                    .h_full()
+                    .border_l_1()
+                    .border_color(rgb(0xe0e0e0))
+                    .child(self.library_view.clone()),
             )
     }
 }
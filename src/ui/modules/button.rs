@@ -2,14 +2,17 @@ use std::rc::Rc;
 
 use gpui::{
     AnyElement, App, ClickEvent, ElementId, InteractiveElement, IntoElement, ParentElement,
-    RenderOnce, StatefulInteractiveElement, Styled, Window, div, prelude::FluentBuilder, rgb,
+    RenderOnce, Rgba, StatefulInteractiveElement, Styled, Window, div, prelude::FluentBuilder, rgb,
 };
 
+type ClickHandler = Rc<dyn Fn(&ClickEvent, &mut Window, &mut App) + 'static>;
+
 #[derive(IntoElement)]
 pub struct Button {
     id: ElementId,
-    on_click: Option<Rc<dyn Fn(&ClickEvent, &mut Window, &mut App) + 'static>>,
+    on_click: Option<ClickHandler>,
     child: Option<AnyElement>,
+    color: Rgba,
 }
 
 impl Button {
@@ -19,6 +22,7 @@ impl Button {
             id,
             on_click: None,
             child: None,
+            color: rgb(0x88b7e7),
         }
     }
 
@@ -30,6 +34,12 @@ impl Button {
         self
     }
 
+    /// Set background color, hover color is a darker one so that white icons keep contrast
+    pub fn color(mut self, color: Rgba) -> Self {
+        self.color = color;
+        self
+    }
+
     pub fn child(mut self, child: impl IntoElement) -> Self {
         self.child = Some(child.into_any_element());
         self
@@ -40,20 +50,27 @@ impl RenderOnce for Button {
     fn render(self, _: &mut Window, _: &mut gpui::App) -> impl IntoElement {
         let on_click = self.on_click.clone();
         let child = self.child;
+        let color = self.color;
+        let hover = Rgba {
+            r: color.r * 0.85,
+            g: color.g * 0.85,
+            b: color.b * 0.85,
+            a: color.a,
+        };
 
         div()
             .id(self.id.clone())
             // .border_1()
             // .border_color(gpui::black())
             .rounded_3xl()
-            .bg(rgb(0x88b7e7))
+            .bg(color)
             .w_16()
             .h_16()
             .flex()
             .justify_center()
             .items_center()
             .text_color(gpui::white())
-            .hover(|style| style.bg(rgb(0x98acc1)))
+            .hover(move |style| style.bg(hover))
             .when_some(child, |this, element| this.child(element))
             // .child(
             //     svg()
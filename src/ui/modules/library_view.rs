@@ -0,0 +1,1277 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use gpui::{
+    AnyElement, AsyncApp, ClickEvent, Context, Div, EventEmitter, FocusHandle, ImageSource,
+    InteractiveElement, IntoElement, KeyDownEvent, Keystroke, MouseButton, MouseDownEvent,
+    ParentElement, Pixels, Point, Render, Stateful, StatefulInteractiveElement, Styled,
+    UniformListScrollHandle, WeakEntity, Window, anchored, deferred, div, img,
+    prelude::FluentBuilder, px, rgb, uniform_list,
+};
+use lofty::{
+    file::TaggedFileExt,
+    picture::{MimeType, Picture, PictureType},
+};
+
+use crate::{
+    service::library_service::{
+        library::Library,
+        models::Track,
+        smart_playlist::{PlaylistSort, SmartPlaylist},
+    },
+    utils::utils,
+};
+
+// size of album card in grid
+static CARD_WIDTH: f32 = 150.0;
+static CARD_HEIGHT: f32 = 190.0;
+// columns of grid before its width is known
+static DEFAULT_COLUMNS: usize = 4;
+// size (px) covers are shrunk to
+static THUMBNAIL_SIZE: u32 = 128;
+static ROW_HEIGHT: f32 = 28.0;
+// most tracks shown for a search
+static SEARCH_LIMIT: usize = 500;
+
+/// Requests of library view, handled by app
+pub enum LibraryAction {
+    // replace queue with tracks and play the one at index
+    Play(Vec<PathBuf>, usize),
+    // insert tracks after current song
+    PlayNext(Vec<PathBuf>),
+    // append tracks to queue
+    Enqueue(Vec<PathBuf>),
+    // measure loudness of tracks and write ReplayGain tags
+    ScanLoudness(Vec<PathBuf>),
+    // set rating of tracks from 1 to 5, None clears it
+    Rate(Vec<PathBuf>, Option<u8>),
+    // replace smart playlist at index, or add it if None
+    SavePlaylist(Option<usize>, SmartPlaylist),
+    RemovePlaylist(usize),
+    // ask for M3U, PLS or XSPF files and append their songs to queue
+    OpenPlaylistFiles,
+    // ask for a file and save queue into it
+    SaveQueue,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Page {
+    Artists,
+    Albums,
+    Tracks,
+    Playlists,
+}
+
+/// Columns of track table
+#[derive(Clone, Copy, PartialEq)]
+enum Column {
+    Number,
+    Title,
+    Artist,
+    Album,
+    Year,
+    Duration,
+    Plays,
+}
+
+impl Column {
+    const ALL: [Column; 7] = [
+        Column::Number,
+        Column::Title,
+        Column::Artist,
+        Column::Album,
+        Column::Year,
+        Column::Duration,
+        Column::Plays,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Column::Number => "#",
+            Column::Title => "Title",
+            Column::Artist => "Artist",
+            Column::Album => "Album",
+            Column::Year => "Year",
+            Column::Duration => "Length",
+            Column::Plays => "Plays",
+        }
+    }
+
+    /// Fixed width, None to share the rest
+    fn width(&self) -> Option<f32> {
+        match self {
+            Column::Number => Some(36.0),
+            Column::Year => Some(48.0),
+            Column::Duration => Some(56.0),
+            Column::Plays => Some(48.0),
+            _ => None,
+        }
+    }
+
+    fn cell(&self, track: &Track) -> String {
+        let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+        match self {
+            Column::Number => number(track.track_number),
+            Column::Title => track.title.clone(),
+            Column::Artist => track.artist.clone().unwrap_or_default(),
+            Column::Album => track.album.clone().unwrap_or_default(),
+            Column::Year => number(track.year),
+            Column::Duration => utils::format_time(track.duration as u64),
+            Column::Plays => track.play_count.to_string(),
+        }
+    }
+}
+
+enum Thumbnail {
+    Loading,
+    Loaded(Option<ImageSource>),
+}
+
+/// Context menu opened on an artist, album or track
+struct Menu {
+    paths: Vec<PathBuf>,
+    position: Point<Pixels>,
+}
+
+/// Smart playlist being edited
+struct PlaylistEditor {
+    // index of edited playlist, None for a new one
+    index: Option<usize>,
+    playlist: SmartPlaylist,
+    // text of limit field, empty for no limit
+    limit: String,
+    // count of tracks matching rules, shown while editing
+    matched: usize,
+}
+
+/// Text fields of playlist editor
+#[derive(Clone, Copy)]
+enum EditorField {
+    Name,
+    Rules,
+    Limit,
+}
+
+/// Browse library by artists, albums and tracks.
+/// Lists are virtualized, only visible rows are built.
+pub struct LibraryView {
+    library: Arc<Library>,
+    page: Page,
+    // filters, indices into library artists and albums
+    artist: Option<usize>,
+    album: Option<usize>,
+    // albums shown in grid and tracks shown in table
+    albums: Vec<usize>,
+    rows: Vec<usize>,
+    sort: Column,
+    ascending: bool,
+    // search query, table shows results if not empty
+    query: String,
+    // search results and playlists keep their own order until sorted by a column
+    ranked: bool,
+    search_focus: FocusHandle,
+    // smart playlists and the one shown in table
+    playlists: Vec<SmartPlaylist>,
+    playlist: Option<usize>,
+    editor: Option<PlaylistEditor>,
+    // focus of editor fields, by EditorField
+    editor_focus: [FocusHandle; 3],
+    // selected track index
+    selected: Option<usize>,
+    // album covers by path of the first track
+    thumbnails: HashMap<PathBuf, Thumbnail>,
+    menu: Option<Menu>,
+    artist_scroll: UniformListScrollHandle,
+    album_scroll: UniformListScrollHandle,
+    track_scroll: UniformListScrollHandle,
+}
+
+impl EventEmitter<LibraryAction> for LibraryView {}
+
+impl LibraryView {
+    pub fn new(library: Arc<Library>, cx: &mut Context<Self>) -> Self {
+        let mut view = Self {
+            library,
+            page: Page::Artists,
+            artist: None,
+            album: None,
+            albums: Vec::new(),
+            rows: Vec::new(),
+            sort: Column::Number,
+            ascending: true,
+            query: String::new(),
+            ranked: false,
+            search_focus: cx.focus_handle(),
+            playlists: Vec::new(),
+            playlist: None,
+            editor: None,
+            editor_focus: [cx.focus_handle(), cx.focus_handle(), cx.focus_handle()],
+            selected: None,
+            thumbnails: HashMap::new(),
+            menu: None,
+            artist_scroll: UniformListScrollHandle::new(),
+            album_scroll: UniformListScrollHandle::new(),
+            track_scroll: UniformListScrollHandle::new(),
+        };
+        view.refresh();
+        view
+    }
+
+    /// Show changed library, filters are kept if artist and album still exist
+    pub fn set_library(&mut self, library: Arc<Library>) {
+        let artist = self
+            .artist
+            .map(|ix| self.library.artists()[ix].name.to_lowercase());
+        let album = self.album.map(|ix| {
+            let a = &self.library.albums()[ix];
+            (a.title.to_lowercase(), a.artist.clone())
+        });
+        let selected = self
+            .selected
+            .map(|ix| self.library.tracks()[ix].path.clone());
+
+        self.library = library;
+        self.artist = artist.and_then(|name| {
+            self.library
+                .artists()
+                .iter()
+                .position(|a| a.name.to_lowercase() == name)
+        });
+        self.album = album.and_then(|(title, artist)| {
+            self.library
+                .albums()
+                .iter()
+                .position(|a| a.title.to_lowercase() == title && a.artist == artist)
+        });
+        self.selected =
+            selected.and_then(|path| self.library.tracks().iter().position(|t| t.path == path));
+        self.refresh();
+    }
+
+    pub fn library(&self) -> &Arc<Library> {
+        &self.library
+    }
+
+    /// Show saved smart playlists, the shown one is dropped if removed
+    pub fn set_playlists(&mut self, playlists: Vec<SmartPlaylist>) {
+        self.playlists = playlists;
+        self.playlist = self.playlist.filter(|&ix| ix < self.playlists.len());
+        self.refresh();
+    }
+
+    /// Rebuild shown albums and tracks from filters or search query
+    fn refresh(&mut self) {
+        let library = &self.library;
+        self.albums = match self.artist {
+            Some(ix) => library.artists()[ix].albums.clone(),
+            None => (0..library.albums().len()).collect(),
+        };
+        if !self.query.is_empty() {
+            self.rows = library
+                .search(&self.query, SEARCH_LIMIT)
+                .into_iter()
+                .map(|result| result.track)
+                .collect();
+            if !self.ranked {
+                self.sort_rows();
+            }
+            return;
+        }
+        if let Some(playlist) = self.playlist.and_then(|ix| self.playlists.get(ix)) {
+            self.rows = playlist.evaluate(library, utils::now_millis());
+            if !self.ranked {
+                self.sort_rows();
+            }
+            return;
+        }
+        self.rows = match (self.album, self.artist) {
+            (Some(ix), _) => library.albums()[ix].tracks.clone(),
+            (None, Some(ix)) => library.artists()[ix].tracks.clone(),
+            (None, None) => (0..library.tracks().len()).collect(),
+        };
+        self.sort_rows();
+    }
+
+    fn sort_rows(&mut self) {
+        let tracks = self.library.tracks();
+        let text = |s: &Option<String>| s.as_deref().unwrap_or_default().to_lowercase();
+        match self.sort {
+            // album order
+            Column::Number => self.rows.sort_by_cached_key(|&ix| {
+                let t = &tracks[ix];
+                let artist = t
+                    .album_artist_or_artist()
+                    .unwrap_or_default()
+                    .to_lowercase();
+                (artist, text(&t.album), t.disc_number, t.track_number)
+            }),
+            Column::Title => self
+                .rows
+                .sort_by_cached_key(|&ix| tracks[ix].title.to_lowercase()),
+            Column::Artist => self.rows.sort_by_cached_key(|&ix| text(&tracks[ix].artist)),
+            Column::Album => self.rows.sort_by_cached_key(|&ix| {
+                let t = &tracks[ix];
+                (text(&t.album), t.disc_number, t.track_number)
+            }),
+            Column::Year => self.rows.sort_by_key(|&ix| tracks[ix].year),
+            Column::Duration => self
+                .rows
+                .sort_by(|&a, &b| tracks[a].duration.total_cmp(&tracks[b].duration)),
+            Column::Plays => self.rows.sort_by_key(|&ix| tracks[ix].play_count),
+        }
+        if !self.ascending {
+            self.rows.reverse();
+        }
+    }
+
+    fn set_page(&mut self, page: Page, cx: &mut Context<Self>) {
+        self.page = page;
+        self.menu = None;
+        cx.notify();
+    }
+
+    fn select_artist(&mut self, artist: Option<usize>, cx: &mut Context<Self>) {
+        self.artist = artist;
+        self.album = None;
+        self.playlist = None;
+        self.query.clear();
+        self.ranked = false;
+        self.refresh();
+        self.album_scroll
+            .scroll_to_item(0, gpui::ScrollStrategy::Top);
+        self.set_page(Page::Albums, cx);
+    }
+
+    fn select_album(&mut self, album: Option<usize>, cx: &mut Context<Self>) {
+        self.album = album;
+        self.playlist = None;
+        self.query.clear();
+        self.ranked = false;
+        self.sort = Column::Number;
+        self.ascending = true;
+        self.refresh();
+        self.track_scroll
+            .scroll_to_item(0, gpui::ScrollStrategy::Top);
+        self.set_page(Page::Tracks, cx);
+    }
+
+    /// Sort table by column, toggle order if already sorted by it
+    fn sort_by(&mut self, column: Column, cx: &mut Context<Self>) {
+        if self.sort == column && !self.ranked {
+            self.ascending = !self.ascending;
+        } else {
+            self.sort = column;
+            self.ascending = true;
+        }
+        self.ranked = false;
+        self.refresh();
+        cx.notify();
+    }
+
+    /// Search library as query is typed, results are shown best first
+    fn set_query(&mut self, query: String, cx: &mut Context<Self>) {
+        self.query = query;
+        self.ranked = true;
+        self.refresh();
+        self.track_scroll
+            .scroll_to_item(0, gpui::ScrollStrategy::Top);
+        if !self.query.is_empty() {
+            self.page = Page::Tracks;
+        }
+        cx.notify();
+    }
+
+    fn handle_search_key(&mut self, e: &KeyDownEvent, cx: &mut Context<Self>) {
+        let mut query = self.query.clone();
+        match e.keystroke.key.as_str() {
+            "escape" => query.clear(),
+            "enter" => {
+                if !self.rows.is_empty() && !self.query.is_empty() {
+                    self.play_row(0, cx);
+                }
+                return;
+            }
+            _ => {
+                if !edit_text(&mut query, &e.keystroke) {
+                    return;
+                }
+            }
+        }
+        cx.stop_propagation();
+        if query != self.query {
+            self.set_query(query, cx);
+        }
+    }
+
+    /// Show tracks of smart playlist in table, in order of playlist
+    fn select_playlist(&mut self, playlist: usize, cx: &mut Context<Self>) {
+        self.playlist = Some(playlist);
+        self.artist = None;
+        self.album = None;
+        self.query.clear();
+        self.ranked = true;
+        self.refresh();
+        self.track_scroll
+            .scroll_to_item(0, gpui::ScrollStrategy::Top);
+        self.set_page(Page::Tracks, cx);
+    }
+
+    /// Edit smart playlist at index, or a new one if None
+    fn open_editor(&mut self, index: Option<usize>, window: &mut Window, cx: &mut Context<Self>) {
+        let playlist = match index.and_then(|ix| self.playlists.get(ix)) {
+            Some(playlist) => playlist.clone(),
+            None => SmartPlaylist::new("New playlist".into(), String::new()),
+        };
+        let mut editor = PlaylistEditor {
+            index,
+            limit: playlist.limit.map(|l| l.to_string()).unwrap_or_default(),
+            playlist,
+            matched: 0,
+        };
+        editor.matched = editor
+            .playlist
+            .evaluate(&self.library, utils::now_millis())
+            .len();
+        self.editor = Some(editor);
+        self.editor_focus[EditorField::Name as usize].focus(window);
+        cx.notify();
+    }
+
+    fn handle_editor_key(&mut self, field: EditorField, e: &KeyDownEvent, cx: &mut Context<Self>) {
+        match e.keystroke.key.as_str() {
+            "enter" => {
+                self.save_editor(cx);
+                return;
+            }
+            "escape" => {
+                self.editor = None;
+                cx.notify();
+                return;
+            }
+            _ => (),
+        }
+        let Some(editor) = self.editor.as_mut() else {
+            return;
+        };
+        let text = match field {
+            EditorField::Name => &mut editor.playlist.name,
+            EditorField::Rules => &mut editor.playlist.rules,
+            EditorField::Limit => &mut editor.limit,
+        };
+        let before = text.clone();
+        if !edit_text(text, &e.keystroke) {
+            return;
+        }
+        if let EditorField::Limit = field {
+            text.retain(|c| c.is_ascii_digit());
+        }
+        cx.stop_propagation();
+        if *text != before {
+            self.update_editor(cx);
+        }
+    }
+
+    /// Count matching tracks again after rules, order or limit changed
+    fn update_editor(&mut self, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.as_mut() else {
+            return;
+        };
+        editor.playlist.limit = editor.limit.parse().ok();
+        editor.matched = editor
+            .playlist
+            .evaluate(&self.library, utils::now_millis())
+            .len();
+        cx.notify();
+    }
+
+    /// Change order of edited playlist
+    fn change_editor(&mut self, f: impl FnOnce(&mut SmartPlaylist), cx: &mut Context<Self>) {
+        if let Some(editor) = self.editor.as_mut() {
+            f(&mut editor.playlist);
+        }
+        self.update_editor(cx);
+    }
+
+    fn save_editor(&mut self, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.take_if(|e| !e.playlist.name.trim().is_empty()) else {
+            return;
+        };
+        cx.emit(LibraryAction::SavePlaylist(editor.index, editor.playlist));
+        cx.notify();
+    }
+
+    fn remove_playlist(&mut self, index: usize, cx: &mut Context<Self>) {
+        self.editor = None;
+        // keep showing the same playlist after indices shift
+        self.playlist = match self.playlist {
+            Some(ix) if ix == index => None,
+            Some(ix) if ix > index => Some(ix - 1),
+            ix => ix,
+        };
+        cx.emit(LibraryAction::RemovePlaylist(index));
+        cx.notify();
+    }
+
+    fn paths(&self, tracks: &[usize]) -> Vec<PathBuf> {
+        tracks
+            .iter()
+            .map(|&ix| self.library.tracks()[ix].path.clone())
+            .collect()
+    }
+
+    /// Play track at row, with the rest of filtered table queued around it.
+    /// Unfiltered table plays the track alone, as queueing whole library is slow.
+    fn play_row(&mut self, row: usize, cx: &mut Context<Self>) {
+        if self.artist.is_some()
+            || self.album.is_some()
+            || self.playlist.is_some()
+            || !self.query.is_empty()
+        {
+            cx.emit(LibraryAction::Play(self.paths(&self.rows), row));
+        } else {
+            cx.emit(LibraryAction::Play(self.paths(&self.rows[row..=row]), 0));
+        }
+    }
+
+    fn open_menu(&mut self, paths: Vec<PathBuf>, position: Point<Pixels>, cx: &mut Context<Self>) {
+        self.menu = Some(Menu { paths, position });
+        cx.notify();
+    }
+
+    /// Cover of album, loaded in background when first asked
+    fn thumbnail(&mut self, path: &Path, cx: &mut Context<Self>) -> Option<ImageSource> {
+        match self.thumbnails.get(path) {
+            Some(Thumbnail::Loaded(src)) => return src.clone(),
+            Some(Thumbnail::Loading) => return None,
+            None => (),
+        }
+        self.thumbnails
+            .insert(path.to_path_buf(), Thumbnail::Loading);
+
+        let path = path.to_path_buf();
+        cx.spawn(
+            async move |weak: WeakEntity<LibraryView>, cx: &mut AsyncApp| {
+                let picture = {
+                    let path = path.clone();
+                    cx.background_executor()
+                        .spawn(async move { load_thumbnail(&path) })
+                        .await
+                };
+                let _ = weak.update(cx, |view, cx| {
+                    let src = picture.as_ref().and_then(utils::convert_picture);
+                    view.thumbnails.insert(path, Thumbnail::Loaded(src));
+                    cx.notify();
+                });
+            },
+        )
+        .detach();
+        None
+    }
+
+    /// Columns of album grid fitting current width
+    fn columns(&self) -> usize {
+        let width = self.album_scroll.0.borrow().base_handle.bounds().size.width;
+        if width <= px(0.0) {
+            return DEFAULT_COLUMNS;
+        }
+        ((width / px(CARD_WIDTH)) as usize).max(1)
+    }
+
+    fn render_search(&self, window: &Window, cx: &mut Context<Self>) -> impl IntoElement {
+        text_field("search", &self.query, "Search", &self.search_focus, window)
+            .ml_auto()
+            .w(px(200.0))
+            .on_key_down(cx.listener(|view, e: &KeyDownEvent, _, cx| view.handle_search_key(e, cx)))
+    }
+
+    fn render_tabs(&self, window: &Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let tab = |id: &'static str, label: String, page: Page| {
+            div()
+                .id(id)
+                .px_3()
+                .py_1()
+                .rounded_md()
+                .cursor_pointer()
+                .when(self.page == page, |this| this.bg(rgb(0xe8e8e8)))
+                .hover(|style| style.bg(rgb(0xf0f0f0)))
+                .child(label)
+                .on_click(cx.listener(move |view, _: &ClickEvent, _, cx| view.set_page(page, cx)))
+        };
+        let artist = self
+            .artist
+            .map(|ix| self.library.artists()[ix].name.clone());
+        let album = self.album.map(|ix| {
+            let title = &self.library.albums()[ix].title;
+            if title.is_empty() {
+                "Unknown album".to_string()
+            } else {
+                title.clone()
+            }
+        });
+        let playlist = self
+            .playlist
+            .and_then(|ix| self.playlists.get(ix))
+            .map(|p| p.name.clone());
+        let tracks = if !self.query.is_empty() {
+            format!("{} results", self.rows.len())
+        } else {
+            playlist.or(album).unwrap_or_else(|| "Tracks".into())
+        };
+
+        div()
+            .flex()
+            .flex_row()
+            .items_center()
+            .gap_1()
+            .p_2()
+            .border_b_1()
+            .border_color(rgb(0xe0e0e0))
+            .child(tab("tab_artists", "Artists".into(), Page::Artists))
+            .child(tab(
+                "tab_albums",
+                artist.unwrap_or_else(|| "Albums".into()),
+                Page::Albums,
+            ))
+            .child(tab("tab_tracks", tracks, Page::Tracks))
+            .child(tab("tab_playlists", "Playlists".into(), Page::Playlists))
+            .child(self.render_search(window, cx))
+            .when(
+                self.artist.is_some() || self.album.is_some() || self.playlist.is_some(),
+                |this| {
+                    this.child(
+                        div()
+                            .id("clear_filter")
+                            .px_2()
+                            .cursor_pointer()
+                            .text_color(rgb(0x999999))
+                            .hover(|style| style.text_color(rgb(0x323232)))
+                            .child("Show all")
+                            .on_click(cx.listener(|view, _: &ClickEvent, _, cx| {
+                                view.artist = None;
+                                view.album = None;
+                                view.playlist = None;
+                                view.ranked = false;
+                                view.refresh();
+                                cx.notify();
+                            })),
+                    )
+                },
+            )
+    }
+
+    fn render_playlists(&self, window: &Window, cx: &mut Context<Self>) -> AnyElement {
+        let rows = self.playlists.iter().enumerate().map(|(ix, playlist)| {
+            div()
+                .id(("playlist", ix))
+                .h(px(ROW_HEIGHT))
+                .px_3()
+                .flex()
+                .items_center()
+                .justify_between()
+                .cursor_pointer()
+                .map(|this| {
+                    if self.playlist == Some(ix) {
+                        this.bg(rgb(0xe0e0e0))
+                    } else {
+                        this.hover(|style| style.bg(rgb(0xf0f0f0)))
+                    }
+                })
+                .child(div().truncate().child(playlist.name.clone()))
+                .child(
+                    div()
+                        .id(("edit_playlist", ix))
+                        .flex_none()
+                        .text_color(rgb(0x999999))
+                        .hover(|style| style.text_color(rgb(0x323232)))
+                        .child("Edit")
+                        .on_click(cx.listener(move |view, _: &ClickEvent, window, cx| {
+                            cx.stop_propagation();
+                            view.open_editor(Some(ix), window, cx)
+                        })),
+                )
+                .on_click(
+                    cx.listener(move |view, _: &ClickEvent, _, cx| view.select_playlist(ix, cx)),
+                )
+                .on_mouse_down(
+                    MouseButton::Right,
+                    cx.listener(move |view, e: &MouseDownEvent, _, cx| {
+                        let rows = view.playlists[ix].evaluate(&view.library, utils::now_millis());
+                        view.open_menu(view.paths(&rows), e.position, cx)
+                    }),
+                )
+        });
+
+        let link = |id: &'static str, text: &'static str| {
+            div()
+                .id(id)
+                .h(px(ROW_HEIGHT))
+                .px_3()
+                .flex()
+                .items_center()
+                .cursor_pointer()
+                .text_color(rgb(0x999999))
+                .hover(|style| style.text_color(rgb(0x323232)))
+                .child(text)
+        };
+
+        div()
+            .id("playlists")
+            .size_full()
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .children(rows)
+            .child(link("new_playlist", "+ New smart playlist").on_click(
+                cx.listener(|view, _: &ClickEvent, window, cx| view.open_editor(None, window, cx)),
+            ))
+            .child(link("open_playlist", "Open playlist file").on_click(
+                cx.listener(|_, _: &ClickEvent, _, cx| cx.emit(LibraryAction::OpenPlaylistFiles)),
+            ))
+            .child(link("save_queue", "Save queue as playlist").on_click(
+                cx.listener(|_, _: &ClickEvent, _, cx| cx.emit(LibraryAction::SaveQueue)),
+            ))
+            .when_some(self.editor.as_ref(), |this, editor| {
+                this.child(self.render_editor(editor, window, cx))
+            })
+            .into_any_element()
+    }
+
+    fn render_editor(
+        &self,
+        editor: &PlaylistEditor,
+        window: &Window,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let row = || div().flex().flex_row().items_center().gap_2();
+        let label = |text: &'static str| {
+            div()
+                .w(px(56.0))
+                .flex_none()
+                .text_color(rgb(0x999999))
+                .child(text)
+        };
+        let input =
+            |field: EditorField, id: &'static str, text: &str, placeholder: &'static str| {
+                text_field(
+                    id,
+                    text,
+                    placeholder,
+                    &self.editor_focus[field as usize],
+                    window,
+                )
+                .flex_1()
+                .on_key_down(cx.listener(move |view, e: &KeyDownEvent, _, cx| {
+                    view.handle_editor_key(field, e, cx)
+                }))
+            };
+        let button = |id: &'static str, text: &'static str| {
+            div()
+                .id(id)
+                .px_3()
+                .py_1()
+                .rounded_md()
+                .cursor_pointer()
+                .bg(rgb(0xe8e8e8))
+                .hover(|style| style.bg(rgb(0xe0e0e0)))
+                .child(text)
+        };
+        let playlist = &editor.playlist;
+
+        div()
+            .m_3()
+            .p_3()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .border_1()
+            .border_color(rgb(0xe0e0e0))
+            .rounded_md()
+            .child(row().child(label("Name")).child(input(
+                EditorField::Name,
+                "editor_name",
+                &playlist.name,
+                "Name",
+            )))
+            .child(row().child(label("Rules")).child(input(
+                EditorField::Rules,
+                "editor_rules",
+                &playlist.rules,
+                "genre:rock rating:>=4 played:>30",
+            )))
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(rgb(0x999999))
+                    .child("Rules use search syntax and match whole words. played and added count days, like added:<7"),
+            )
+            .child(
+                row()
+                    .child(label("Order"))
+                    .child(button("editor_sort", playlist.sort.name()).on_click(cx.listener(
+                        |view, _: &ClickEvent, _, cx| {
+                            view.change_editor(
+                                |p| {
+                                    let all = PlaylistSort::ALL;
+                                    let pos = all.iter().position(|s| *s == p.sort).unwrap_or(0);
+                                    p.sort = all[(pos + 1) % all.len()];
+                                },
+                                cx,
+                            )
+                        },
+                    )))
+                    .child(
+                        button(
+                            "editor_order",
+                            if playlist.descending {
+                                "Descending"
+                            } else {
+                                "Ascending"
+                            },
+                        )
+                        .on_click(cx.listener(|view, _: &ClickEvent, _, cx| {
+                            view.change_editor(|p| p.descending = !p.descending, cx)
+                        })),
+                    )
+                    .when(playlist.sort == PlaylistSort::Random, |this| {
+                        this.child(button("editor_shuffle", "Shuffle").on_click(cx.listener(
+                            |view, _: &ClickEvent, _, cx| {
+                                view.change_editor(SmartPlaylist::reshuffle, cx)
+                            },
+                        )))
+                    }),
+            )
+            .child(
+                row().child(label("Limit")).child(
+                    input(EditorField::Limit, "editor_limit", &editor.limit, "No limit")
+                        .flex_none()
+                        .w(px(100.0)),
+                ),
+            )
+            .child(
+                row()
+                    .child(
+                        div()
+                            .text_color(rgb(0x999999))
+                            .child(format!("{} tracks", editor.matched)),
+                    )
+                    .child(div().flex_1())
+                    .when_some(editor.index, |this, ix| {
+                        this.child(button("editor_delete", "Delete").on_click(cx.listener(
+                            move |view, _: &ClickEvent, _, cx| view.remove_playlist(ix, cx),
+                        )))
+                    })
+                    .child(button("editor_cancel", "Cancel").on_click(cx.listener(
+                        |view, _: &ClickEvent, _, cx| {
+                            view.editor = None;
+                            cx.notify();
+                        },
+                    )))
+                    .child(button("editor_save", "Save").on_click(
+                        cx.listener(|view, _: &ClickEvent, _, cx| view.save_editor(cx)),
+                    )),
+            )
+    }
+
+    fn render_artists(&self, cx: &mut Context<Self>) -> AnyElement {
+        uniform_list(
+            "artists",
+            self.library.artists().len(),
+            cx.processor(|view, range: Range<usize>, _, cx| {
+                range
+                    .map(|ix| {
+                        let artist = &view.library.artists()[ix];
+                        div()
+                            .id(ix)
+                            .h(px(ROW_HEIGHT))
+                            .px_3()
+                            .flex()
+                            .items_center()
+                            .justify_between()
+                            .cursor_pointer()
+                            .hover(|style| style.bg(rgb(0xf0f0f0)))
+                            .child(div().truncate().child(artist.name.clone()))
+                            .child(
+                                div()
+                                    .flex_none()
+                                    .text_color(rgb(0x999999))
+                                    .child(format!("{} albums", artist.albums.len())),
+                            )
+                            .on_click(cx.listener(move |view, _: &ClickEvent, _, cx| {
+                                view.select_artist(Some(ix), cx)
+                            }))
+                            .on_mouse_down(
+                                MouseButton::Right,
+                                cx.listener(move |view, e: &MouseDownEvent, _, cx| {
+                                    let tracks = &view.library.artists()[ix].tracks;
+                                    view.open_menu(view.paths(tracks), e.position, cx)
+                                }),
+                            )
+                    })
+                    .collect()
+            }),
+        )
+        .track_scroll(self.artist_scroll.clone())
+        .size_full()
+        .into_any_element()
+    }
+
+    fn render_albums(&self, cx: &mut Context<Self>) -> AnyElement {
+        let columns = self.columns();
+        uniform_list(
+            "albums",
+            self.albums.len().div_ceil(columns),
+            cx.processor(move |view, range: Range<usize>, _, cx| {
+                range
+                    .map(|row| {
+                        let start = row * columns;
+                        let end = (start + columns).min(view.albums.len());
+                        div()
+                            .h(px(CARD_HEIGHT))
+                            .flex()
+                            .flex_row()
+                            .children((start..end).map(|pos| view.album_card(pos, cx)))
+                    })
+                    .collect()
+            }),
+        )
+        .track_scroll(self.album_scroll.clone())
+        .size_full()
+        .into_any_element()
+    }
+
+    fn album_card(&mut self, pos: usize, cx: &mut Context<Self>) -> AnyElement {
+        let library = self.library.clone();
+        let ix = self.albums[pos];
+        let album = &library.albums()[ix];
+        let cover = album
+            .tracks
+            .first()
+            .and_then(|&t| self.thumbnail(&library.tracks()[t].path, cx));
+        let size = px(CARD_WIDTH - 16.0);
+
+        div()
+            .id(("album", ix))
+            .w(px(CARD_WIDTH))
+            .h(px(CARD_HEIGHT))
+            .p_2()
+            .flex()
+            .flex_col()
+            .rounded_md()
+            .cursor_pointer()
+            .hover(|style| style.bg(rgb(0xf0f0f0)))
+            .child(match cover {
+                Some(src) => img(src).size(size).rounded_md().into_any_element(),
+                None => div()
+                    .size(size)
+                    .rounded_md()
+                    .bg(rgb(0xe0e0e0))
+                    .into_any_element(),
+            })
+            .child(div().mt_1().truncate().child(if album.title.is_empty() {
+                "Unknown album".to_string()
+            } else {
+                album.title.clone()
+            }))
+            .child(
+                div()
+                    .truncate()
+                    .text_xs()
+                    .text_color(rgb(0x999999))
+                    .child(album.artist.clone().unwrap_or_default()),
+            )
+            .on_click(
+                cx.listener(move |view, _: &ClickEvent, _, cx| view.select_album(Some(ix), cx)),
+            )
+            .on_mouse_down(
+                MouseButton::Right,
+                cx.listener(move |view, e: &MouseDownEvent, _, cx| {
+                    let tracks = &view.library.albums()[ix].tracks;
+                    view.open_menu(view.paths(tracks), e.position, cx)
+                }),
+            )
+            .into_any_element()
+    }
+
+    fn render_tracks(&self, cx: &mut Context<Self>) -> AnyElement {
+        let cell = |column: Column| {
+            div().px_1().truncate().map(|this| match column.width() {
+                Some(w) => this.w(px(w)).flex_none(),
+                None => this.flex_1(),
+            })
+        };
+        let header = div()
+            .h(px(ROW_HEIGHT))
+            .px_3()
+            .flex()
+            .flex_row()
+            .items_center()
+            .border_b_1()
+            .border_color(rgb(0xe0e0e0))
+            .text_color(rgb(0x999999))
+            .children(Column::ALL.into_iter().map(|column| {
+                let arrow = match (self.sort == column && !self.ranked, self.ascending) {
+                    (false, _) => "",
+                    (true, true) => " ▲",
+                    (true, false) => " ▼",
+                };
+                cell(column)
+                    .id(column.name())
+                    .cursor_pointer()
+                    .hover(|style| style.text_color(rgb(0x323232)))
+                    .child(format!("{}{}", column.name(), arrow))
+                    .on_click(
+                        cx.listener(move |view, _: &ClickEvent, _, cx| view.sort_by(column, cx)),
+                    )
+            }));
+
+        let list = uniform_list(
+            "tracks",
+            self.rows.len(),
+            cx.processor(move |view, range: Range<usize>, _, cx| {
+                range
+                    .map(|row| {
+                        let ix = view.rows[row];
+                        let track = &view.library.tracks()[ix];
+                        div()
+                            .id(row)
+                            .h(px(ROW_HEIGHT))
+                            .px_3()
+                            .flex()
+                            .flex_row()
+                            .items_center()
+                            .cursor_pointer()
+                            .map(|this| {
+                                if view.selected == Some(ix) {
+                                    this.bg(rgb(0xe0e0e0))
+                                } else {
+                                    this.hover(|style| style.bg(rgb(0xf0f0f0)))
+                                }
+                            })
+                            .children(
+                                Column::ALL
+                                    .into_iter()
+                                    .map(|column| cell(column).child(column.cell(track))),
+                            )
+                            .on_click(cx.listener(move |view, e: &ClickEvent, _, cx| {
+                                view.selected = Some(ix);
+                                if e.click_count() >= 2 {
+                                    view.play_row(row, cx);
+                                }
+                                cx.notify();
+                            }))
+                            .on_mouse_down(
+                                MouseButton::Right,
+                                cx.listener(move |view, e: &MouseDownEvent, _, cx| {
+                                    view.selected = Some(ix);
+                                    view.open_menu(view.paths(&[ix]), e.position, cx)
+                                }),
+                            )
+                    })
+                    .collect()
+            }),
+        )
+        .track_scroll(self.track_scroll.clone())
+        .flex_1();
+
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .child(header)
+            .child(list)
+            .into_any_element()
+    }
+
+    fn render_menu(&self, menu: &Menu, cx: &mut Context<Self>) -> impl IntoElement {
+        let close = cx.listener(|view, _: &MouseDownEvent, _, cx| {
+            view.menu = None;
+            cx.notify();
+        });
+        let item =
+            |id: &'static str, label: &'static str, action: fn(Vec<PathBuf>) -> LibraryAction| {
+                div()
+                    .id(id)
+                    .px_3()
+                    .py_1()
+                    .cursor_pointer()
+                    .hover(|style| style.bg(rgb(0xf0f0f0)))
+                    .child(label)
+                    .on_click(cx.listener(move |view, _: &ClickEvent, _, cx| {
+                        if let Some(menu) = view.menu.take() {
+                            cx.emit(action(menu.paths));
+                        }
+                        cx.notify();
+                    }))
+            };
+        // stars show rating of first track, clicking its own rating clears it
+        let rating = menu
+            .paths
+            .first()
+            .and_then(|p| self.library.track(p))
+            .and_then(|t| t.rating)
+            .unwrap_or(0);
+        let star = |r: u8| {
+            div()
+                .id(("menu_rate", r as usize))
+                .px_0p5()
+                .cursor_pointer()
+                .child(if r <= rating { "★" } else { "☆" })
+                .on_click(cx.listener(move |view, _: &ClickEvent, _, cx| {
+                    if let Some(menu) = view.menu.take() {
+                        cx.emit(LibraryAction::Rate(menu.paths, (r != rating).then_some(r)));
+                    }
+                    cx.notify();
+                }))
+        };
+
+        deferred(
+            anchored().position(menu.position).snap_to_window().child(
+                div()
+                    .id("library_menu")
+                    .w(px(160.0))
+                    .py_1()
+                    .bg(gpui::white())
+                    .border_1()
+                    .border_color(rgb(0xdddddd))
+                    .rounded_md()
+                    .shadow_md()
+                    .on_mouse_down_out(close)
+                    .child(item("menu_play", "Play", |paths| {
+                        LibraryAction::Play(paths, 0)
+                    }))
+                    .child(item("menu_play_next", "Play next", LibraryAction::PlayNext))
+                    .child(item("menu_enqueue", "Add to queue", LibraryAction::Enqueue))
+                    .child(item(
+                        "menu_loudness",
+                        "Analyze loudness",
+                        LibraryAction::ScanLoudness,
+                    ))
+                    .child(
+                        div()
+                            .px_3()
+                            .py_1()
+                            .flex()
+                            .flex_row()
+                            .items_center()
+                            .child(div().flex_1().child("Rate"))
+                            .children((1..=5).map(star)),
+                    ),
+            ),
+        )
+    }
+}
+
+impl Render for LibraryView {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let content = if self.library.tracks().is_empty() {
+            div()
+                .size_full()
+                .flex()
+                .justify_center()
+                .items_center()
+                .text_color(rgb(0x999999))
+                .child("Drop a music folder here to build library")
+                .into_any_element()
+        } else {
+            match self.page {
+                Page::Artists => self.render_artists(cx),
+                Page::Albums => self.render_albums(cx),
+                Page::Tracks => self.render_tracks(cx),
+                Page::Playlists => self.render_playlists(window, cx),
+            }
+        };
+
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .bg(gpui::white())
+            .text_color(rgb(0x323232))
+            .text_sm()
+            .child(self.render_tabs(window, cx))
+            .child(div().flex_1().overflow_hidden().child(content))
+            .when_some(self.menu.as_ref(), |this, menu| {
+                this.child(self.render_menu(menu, cx))
+            })
+    }
+}
+
+/// Edit text by a typed key, return false if key does not edit text
+fn edit_text(text: &mut String, keystroke: &Keystroke) -> bool {
+    if keystroke.key == "backspace" {
+        text.pop();
+        return true;
+    }
+    if keystroke.modifiers.control || keystroke.modifiers.platform {
+        return false;
+    }
+    match &keystroke.key_char {
+        Some(c) if !c.chars().any(char::is_control) => {
+            text.push_str(c);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Single line text field focused by click, keys are handled by caller
+fn text_field(
+    id: &'static str,
+    text: &str,
+    placeholder: &'static str,
+    focus: &FocusHandle,
+    window: &Window,
+) -> Stateful<Div> {
+    let focused = focus.is_focused(window);
+    let focus = focus.clone();
+    div()
+        .id(id)
+        .track_focus(&focus)
+        .px_2()
+        .py_1()
+        .flex()
+        .flex_row()
+        .items_center()
+        .border_1()
+        .rounded_md()
+        .cursor_text()
+        .border_color(if focused {
+            rgb(0x999999)
+        } else {
+            rgb(0xe0e0e0)
+        })
+        .child(if text.is_empty() && !focused {
+            div().text_color(rgb(0xbbbbbb)).child(placeholder)
+        } else {
+            div().truncate().child(text.to_string())
+        })
+        .when(focused, |this| {
+            this.child(div().w(px(1.0)).h_4().bg(rgb(0x323232)))
+        })
+        .on_click(move |_, window, _| focus.focus(window))
+}
+
+/// Read cover of music and shrink it, runs on background thread
+fn load_thumbnail(path: &Path) -> Option<Picture> {
+    let tagged = lofty::read_from_path(path).ok()?;
+    let tag = tagged.primary_tag().or(tagged.first_tag())?;
+    let data = tag.pictures().first()?.data();
+    let image = image::load_from_memory(data)
+        .ok()?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .ok()?;
+    Some(Picture::new_unchecked(
+        PictureType::CoverFront,
+        Some(MimeType::Png),
+        None,
+        png,
+    ))
+}
@@ -0,0 +1,172 @@
+use std::rc::Rc;
+
+use gpui::{
+    AnyElement, App, ClickEvent, ElementId, InteractiveElement, IntoElement, ParentElement,
+    RenderOnce, ScrollHandle, StatefulInteractiveElement, Styled, Window, div, point,
+    prelude::FluentBuilder, px, relative, rgba,
+};
+
+use crate::service::lyrics_service::models::{LyricLine, Lyrics};
+
+// height of a lyric line
+static LINE_HEIGHT: f32 = 32.0;
+// height of a translation under lyric line
+static TRANSLATION_HEIGHT: f32 = 20.0;
+
+type SeekHandler = Rc<dyn Fn(f64, &mut Window, &mut App) + 'static>;
+
+/// Scrolling lyrics panel, the current line is highlighted word by word
+#[derive(IntoElement)]
+pub struct LyricsView {
+    id: ElementId,
+    lyrics: Option<Rc<Lyrics>>,
+    current: Option<usize>,
+    // play time in seconds
+    time: f64,
+    scroll_handle: ScrollHandle,
+    on_seek: Option<SeekHandler>,
+}
+
+impl LyricsView {
+    pub fn new(id: impl Into<ElementId>, scroll_handle: ScrollHandle) -> Self {
+        Self {
+            id: id.into(),
+            lyrics: None,
+            current: None,
+            time: 0.0,
+            scroll_handle,
+            on_seek: None,
+        }
+    }
+
+    pub fn lyrics(mut self, lyrics: Option<Rc<Lyrics>>, current: Option<usize>, time: f64) -> Self {
+        self.lyrics = lyrics;
+        self.current = current;
+        self.time = time;
+        self
+    }
+
+    /// Called with time (seconds) of clicked line
+    pub fn on_seek(mut self, handler: impl Fn(f64, &mut Window, &mut App) + 'static) -> Self {
+        self.on_seek = Some(Rc::new(handler));
+        self
+    }
+
+    /// Scroll so that line is at the middle of panel
+    pub fn scroll_to_line(scroll_handle: &ScrollHandle, lyrics: &Lyrics, index: usize) {
+        let height = scroll_handle.bounds().size.height.to_f64() as f32;
+        let top: f32 = lyrics.lines[..index].iter().map(Self::line_height).sum();
+        let center = top + Self::line_height(&lyrics.lines[index]) / 2.0;
+        let y = (center - height / 2.0).max(0.0);
+        scroll_handle.set_offset(point(px(0.0), px(-y)));
+    }
+
+    fn line_height(line: &LyricLine) -> f32 {
+        match line.translation {
+            Some(_) => LINE_HEIGHT + TRANSLATION_HEIGHT,
+            None => LINE_HEIGHT,
+        }
+    }
+
+    /// Words of current line, the sung part of every word is filled
+    fn karaoke(line: &LyricLine, progress: Vec<f32>) -> AnyElement {
+        div()
+            .flex()
+            .flex_row()
+            .whitespace_nowrap()
+            .children(line.words.iter().zip(progress).map(|(word, p)| {
+                div()
+                    .relative()
+                    .text_color(rgba(0xffffff99))
+                    .child(word.text.clone())
+                    .child(
+                        div()
+                            .absolute()
+                            .top_0()
+                            .left_0()
+                            .h_full()
+                            .w(relative(p))
+                            .overflow_hidden()
+                            .text_color(gpui::white())
+                            .child(word.text.clone()),
+                    )
+            }))
+            .into_any_element()
+    }
+}
+
+impl RenderOnce for LyricsView {
+    fn render(self, _: &mut Window, _: &mut App) -> impl IntoElement {
+        let Some(lyrics) = self.lyrics else {
+            return div()
+                .id(self.id)
+                .size_full()
+                .flex()
+                .justify_center()
+                .items_center()
+                .child("No lyrics");
+        };
+
+        let current = self.current;
+        let time = self.time;
+        let on_seek = self.on_seek;
+        div()
+            .id(self.id)
+            .size_full()
+            .overflow_y_scroll()
+            .track_scroll(&self.scroll_handle)
+            .children(lyrics.lines.iter().enumerate().map(|(ix, line)| {
+                let on_seek = on_seek.clone();
+                let seek_time = line.time;
+                let is_current = current == Some(ix);
+                let text = if is_current && !line.words.is_empty() {
+                    Self::karaoke(line, line.word_progress(time, lyrics.line_end(ix)))
+                } else {
+                    line.text.clone().into_any_element()
+                };
+                div()
+                    .id(ix)
+                    .w_full()
+                    .h(px(Self::line_height(line)))
+                    .px_4()
+                    .flex()
+                    .flex_col()
+                    .justify_center()
+                    .items_center()
+                    .map(|this| {
+                        if is_current {
+                            this.text_color(gpui::white())
+                        } else {
+                            this.text_color(rgba(0xffffff99))
+                        }
+                    })
+                    .child(
+                        div()
+                            .h(px(LINE_HEIGHT))
+                            .flex()
+                            .items_center()
+                            .truncate()
+                            .when(is_current, |this| this.text_xl())
+                            .child(text),
+                    )
+                    .when_some(line.translation.clone(), |this, translation| {
+                        this.child(
+                            div()
+                                .h(px(TRANSLATION_HEIGHT))
+                                .text_sm()
+                                .truncate()
+                                .child(translation),
+                        )
+                    })
+                    .when(lyrics.synced, |this| {
+                        this.cursor_pointer()
+                            .hover(|style| style.text_color(gpui::white()))
+                            .when_some(on_seek, |this, on_seek| {
+                                this.on_click(move |_: &ClickEvent, window, cx| {
+                                    on_seek(seek_time, window, cx)
+                                })
+                            })
+                    })
+            }))
+    }
+}
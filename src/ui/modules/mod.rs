@@ -1 +1,3 @@
 pub mod button;
+pub mod library_view;
+pub mod lyrics_view;
@@ -0,0 +1,78 @@
+use std::{env, fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::service::music_service::{
+    dsp::DspStage, models::OutputDevice, replaygain::ReplayGainMode,
+};
+
+static CONFIG_FILE: &str = "config.json";
+
+/// User settings saved between runs
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // chosen output device, None means default device
+    pub output_device: Option<OutputDevice>,
+    // folders scanned into library
+    pub library_folders: Vec<PathBuf>,
+    // crossfade seconds between songs, 0 disables it
+    pub crossfade_secs: f32,
+    // loudness normalization mode and preamp (dB)
+    pub replay_gain_mode: ReplayGainMode,
+    pub replay_gain_preamp: f32,
+    // processors in order, None uses default chain
+    pub dsp_chain: Option<Vec<DspStage>>,
+    // address of MPD server like 127.0.0.1:6600, None disables it
+    pub mpd_address: Option<String>,
+    // port of HTTP remote API, None disables it
+    pub http_port: Option<u16>,
+    // host HTTP remote API binds, localhost if None
+    pub http_host: Option<String>,
+    // token clients must give to HTTP remote API, None allows all
+    pub http_token: Option<String>,
+}
+
+impl Config {
+    /// Load config from file, use default if not exist or broken
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save config into file
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let dir = config_dir().ok_or_else(|| anyhow::anyhow!("config folder not found"))?;
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(CONFIG_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Address of HTTP remote API, None if disabled
+    pub fn http_address(&self) -> Option<String> {
+        let port = self.http_port?;
+        let host = self.http_host.as_deref().unwrap_or("127.0.0.1");
+        Some(format!("{}:{}", host, port))
+    }
+
+    fn path() -> Option<PathBuf> {
+        config_dir().map(|d| d.join(CONFIG_FILE))
+    }
+}
+
+/// Folder of app data, like ~/.config/the_player
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+    };
+    base.map(|b| b.join("the_player"))
+}
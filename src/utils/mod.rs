@@ -1 +1,6 @@
+pub mod config;
+pub mod palette;
+#[cfg(test)]
+pub mod temp_dir;
+#[allow(clippy::module_inception)]
 pub mod utils;
@@ -0,0 +1,220 @@
+// clusters of k-means
+static CLUSTERS: usize = 5;
+static ITERATIONS: usize = 10;
+// size of thumbnail used for extraction
+static THUMBNAIL_SIZE: u32 = 64;
+// minimum contrast ratio of white text on background and white icons on accent
+static TEXT_CONTRAST: f64 = 4.5;
+static ICON_CONTRAST: f64 = 3.0;
+
+/// Colors of app, as 0xRRGGBB
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub background: u32,
+    pub accent: u32,
+    pub text: u32,
+}
+
+impl Default for Palette {
+    /// Blue palette, darkened the same way as extracted ones
+    fn default() -> Self {
+        Self {
+            background: to_hex(readable(from_hex(0x398ad7), TEXT_CONTRAST)),
+            accent: to_hex(readable(from_hex(0x88b7e7), ICON_CONTRAST)),
+            text: 0xffffff,
+        }
+    }
+}
+
+impl Palette {
+    /// Extract palette from encoded image, colors are clustered in OKLab space.
+    /// Background and accent are darkened until white text and icons on them are readable.
+    pub fn from_image(data: &[u8]) -> Option<Self> {
+        let image = image::load_from_memory(data).ok()?;
+        let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+        let points: Vec<[f64; 3]> = thumbnail
+            .pixels()
+            .map(|p| to_oklab(p.0[0], p.0[1], p.0[2]))
+            .collect();
+        let clusters = kmeans(&points, CLUSTERS);
+        let (background, _) = *clusters.iter().max_by_key(|(_, n)| *n)?;
+
+        // most colorful cluster which differs from background
+        let accent = clusters
+            .iter()
+            .filter(|(c, _)| distance(c, &background) > 0.1)
+            .max_by(|(a, na), (b, nb)| {
+                let score = |c: &[f64; 3], n: usize| chroma(c) * (n as f64).sqrt();
+                score(a, *na).total_cmp(&score(b, *nb))
+            })
+            .map(|(c, _)| *c)
+            .unwrap_or([background[0] + 0.15, background[1], background[2]]);
+
+        Some(Self {
+            background: to_hex(readable(background, TEXT_CONTRAST)),
+            accent: to_hex(readable(accent, ICON_CONTRAST)),
+            text: 0xffffff,
+        })
+    }
+
+    /// Blend two palettes, t from 0.0 (self) to 1.0 (other)
+    pub fn mix(&self, other: &Palette, t: f32) -> Palette {
+        let t = t.clamp(0.0, 1.0) as f64;
+        let mix = |a: u32, b: u32| {
+            let (a, b) = (from_hex(a), from_hex(b));
+            to_hex([
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ])
+        };
+        Palette {
+            background: mix(self.background, other.background),
+            accent: mix(self.accent, other.accent),
+            text: mix(self.text, other.text),
+        }
+    }
+}
+
+/// Cluster points, return centers with their sizes
+fn kmeans(points: &[[f64; 3]], k: usize) -> Vec<([f64; 3], usize)> {
+    // init with farthest points, start from the one nearest to mean
+    let mean = points
+        .iter()
+        .fold([0.0; 3], |m, p| [m[0] + p[0], m[1] + p[1], m[2] + p[2]]);
+    let mean = mean.map(|v| v / points.len() as f64);
+    let nearest = |centers: &[[f64; 3]], p: &[f64; 3]| {
+        centers
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, distance(c, p)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or((0, 0.0))
+    };
+    let Some(first) = points
+        .iter()
+        .min_by(|a, b| distance(a, &mean).total_cmp(&distance(b, &mean)))
+    else {
+        return Vec::new();
+    };
+    let mut centers = vec![*first];
+    while centers.len() < k {
+        let Some((p, d)) = points
+            .iter()
+            .map(|p| (p, nearest(&centers, p).1))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+        else {
+            break;
+        };
+        if d <= 0.0 {
+            break;
+        }
+        centers.push(*p);
+    }
+
+    let mut sizes = vec![0; centers.len()];
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![[0.0; 3]; centers.len()];
+        sizes = vec![0; centers.len()];
+        for p in points {
+            let (i, _) = nearest(&centers, p);
+            sums[i] = [sums[i][0] + p[0], sums[i][1] + p[1], sums[i][2] + p[2]];
+            sizes[i] += 1;
+        }
+        for (i, c) in centers.iter_mut().enumerate() {
+            if sizes[i] > 0 {
+                *c = sums[i].map(|v| v / sizes[i] as f64);
+            }
+        }
+    }
+    centers.into_iter().zip(sizes).collect()
+}
+
+/// Lower lightness until white reaches contrast ratio
+fn readable(mut lab: [f64; 3], contrast: f64) -> [f64; 3] {
+    lab[0] = lab[0].clamp(0.0, 1.0);
+    while lab[0] > 0.0 && (1.05 / (luminance(lab) + 0.05)) < contrast {
+        lab[0] = (lab[0] - 0.02).max(0.0);
+    }
+    lab
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn chroma(lab: &[f64; 3]) -> f64 {
+    (lab[1].powi(2) + lab[2].powi(2)).sqrt()
+}
+
+/// Relative luminance of WCAG
+fn luminance(lab: [f64; 3]) -> f64 {
+    let [r, g, b] = to_linear_rgb(lab);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn to_oklab(r: u8, g: u8, b: u8) -> [f64; 3] {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(r), linear(g), linear(b));
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+fn to_linear_rgb(lab: [f64; 3]) -> [f64; 3] {
+    let [l, a, b] = lab;
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_,
+        -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_,
+        -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_,
+    ]
+    .map(|c| c.clamp(0.0, 1.0))
+}
+
+fn to_hex(lab: [f64; 3]) -> u32 {
+    let srgb = |c: f64| {
+        let c = if c <= 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (c * 255.0).round().clamp(0.0, 255.0) as u32
+    };
+    let [r, g, b] = to_linear_rgb(lab);
+    (srgb(r) << 16) | (srgb(g) << 8) | srgb(b)
+}
+
+fn from_hex(hex: u32) -> [f64; 3] {
+    to_oklab((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contrast(hex: u32) -> f64 {
+        1.05 / (luminance(from_hex(hex)) + 0.05)
+    }
+
+    #[test]
+    fn default_is_readable() {
+        let palette = Palette::default();
+        assert!(contrast(palette.background) >= TEXT_CONTRAST);
+        assert!(contrast(palette.accent) >= ICON_CONTRAST);
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// folders created by this run, makes names unique between tests
+static CREATED: AtomicU64 = AtomicU64::new(0);
+
+/// Folder in temp folder for a test, removed with its content when dropped
+/// so that a failed assert leaves nothing behind
+pub struct TempDir {
+    root: PathBuf,
+}
+
+impl TempDir {
+    /// Create an empty folder named after the test
+    pub fn new(name: &str) -> Self {
+        let root = env::temp_dir().join(format!(
+            "the_player_{}_{}_{}",
+            name,
+            process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get full path of a relative path inside
+    pub fn join(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// Create an empty file with its parent folders, return its full path
+    pub fn file(&self, relative: impl AsRef<Path>) -> PathBuf {
+        let path = self.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, b"").unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
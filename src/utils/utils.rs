@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use gpui::{Image, ImageFormat, ImageSource};
 use lofty::picture::Picture;
@@ -7,6 +10,14 @@ pub fn format_time(sec: u64) -> String {
     format!("{:02}:{:02}", sec / 60, sec % 60)
 }
 
+/// Current time in unix ms
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
 /// Convert to image source
 pub fn convert_picture(pic: &Picture) -> Option<ImageSource> {
     if let Some(mime) = pic.mime_type() {
@@ -18,9 +29,9 @@ pub fn convert_picture(pic: &Picture) -> Option<ImageSource> {
             lofty::picture::MimeType::Gif => Some(ImageFormat::Gif),
             _ => None,
         };
-        if mtype != None {
+        if let Some(mtype) = mtype {
             return Some(ImageSource::Image(Arc::new(Image::from_bytes(
-                mtype.unwrap(),
+                mtype,
                 pic.data().to_vec(),
             ))));
         }